@@ -1,8 +1,11 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use phone_data::{PhoneData};
+use phone_data::{PhoneData, PhoneLookup, PhoneStats};
 use phone_data::phone_hash::PhoneDataHash;
 use phone_data::phone_simd::PhoneDataSimd;
-use phone_data::phone_bloom::PhoneDataBloom;
+use phone_data::phone_bloom::{FilterType, PhoneDataBloom};
+use phone_data::phone_eytzinger::PhoneDataEytzinger;
+use phone_data::compression::{self, Compression};
+use phone_data::format_v2::{convert_v1_to_v2, PhoneDataV2};
 use std::time::Duration;
 
 // 加载二分法版本数据
@@ -25,11 +28,17 @@ fn load_bloom_data() -> PhoneDataBloom {
     PhoneDataBloom::new().expect("Failed to load bloom filter phone data")
 }
 
+// 加载Eytzinger布局版本数据
+fn load_eytzinger_data() -> PhoneDataEytzinger {
+    PhoneDataEytzinger::new().expect("Failed to load eytzinger phone data")
+}
+
 fn bench_single_lookup_comparison(c: &mut Criterion) {
     let binary_data = load_binary_search_data();
     let hash_data = load_hash_data();
     let simd_data = load_simd_data();
     let bloom_data = load_bloom_data();
+    let eytzinger_data = load_eytzinger_data();
 
     let test_phones = vec![
         "18086834111",
@@ -99,6 +108,19 @@ fn bench_single_lookup_comparison(c: &mut Criterion) {
         );
     }
 
+    // Eytzinger布局查找基准测试
+    for phone in &test_phones {
+        group.bench_with_input(
+            format!("eytzinger_lookup_{}", phone),
+            black_box(phone),
+            |b, phone| {
+                b.iter(|| {
+                    black_box(eytzinger_data.find(black_box(phone)).ok());
+                })
+            },
+        );
+    }
+
     group.finish();
 }
 
@@ -107,6 +129,7 @@ fn bench_bulk_lookup_comparison(c: &mut Criterion) {
     let hash_data = load_hash_data();
     let simd_data = load_simd_data();
     let bloom_data = load_bloom_data();
+    let eytzinger_data = load_eytzinger_data();
 
     // 生成测试用的手机号码
     let test_phones: Vec<String> = (13000000000i64..=18999999999i64)
@@ -146,6 +169,61 @@ fn bench_bulk_lookup_comparison(c: &mut Criterion) {
             }
         })
     });
+
+    c.bench_function("eytzinger_lookup_bulk_1000", |b| {
+        b.iter(|| {
+            for phone in &test_phones {
+                black_box(eytzinger_data.find(phone).ok());
+            }
+        })
+    });
+}
+
+fn bench_batch_lookup_comparison(c: &mut Criterion) {
+    let simd_data = load_simd_data();
+    let bloom_data = load_bloom_data();
+    let sorted_binary_data = phone_data::binary_search::PhoneData::new().expect("Failed to load binary search phone data");
+
+    let mut group = c.benchmark_group("batch_lookup_comparison");
+    group.measurement_time(Duration::from_secs(10));
+
+    for size in [1000u64, 10000] {
+        // 伪随机打乱的号码，模拟真实批量任务的输入顺序
+        let test_phones: Vec<String> = (0..size)
+            .map(|i| (13000000000 + (i * 2654435761) % 7000000000).to_string())
+            .collect();
+        let test_phones: Vec<&str> = test_phones.iter().map(|phone| phone.as_str()).collect();
+
+        group.bench_function(format!("simd_find_loop_{}", size), |b| {
+            b.iter(|| {
+                for phone in &test_phones {
+                    black_box(simd_data.find(phone).ok());
+                }
+            })
+        });
+
+        group.bench_function(format!("simd_find_batch_{}", size), |b| {
+            b.iter(|| black_box(simd_data.find_batch(black_box(&test_phones))))
+        });
+
+        group.bench_function(format!("bloom_find_loop_{}", size), |b| {
+            b.iter(|| {
+                for phone in &test_phones {
+                    black_box(bloom_data.find(phone).ok());
+                }
+            })
+        });
+
+        group.bench_function(format!("bloom_find_batch_{}", size), |b| {
+            b.iter(|| black_box(bloom_data.find_batch(black_box(&test_phones))))
+        });
+
+        group.bench_function(format!("binary_search_find_batch_{}", size), |b| {
+            b.iter(|| black_box(sorted_binary_data.find_batch(black_box(&test_phones))))
+        });
+    }
+
+    group.finish();
 }
 
 fn bench_initialization_time(c: &mut Criterion) {
@@ -180,6 +258,60 @@ fn bench_initialization_time(c: &mut Criterion) {
         })
     });
 
+    // 测试Eytzinger布局数据初始化时间
+    group.bench_function("eytzinger_init", |b| {
+        b.iter(|| {
+            black_box(PhoneDataEytzinger::new().ok());
+        })
+    });
+
+    // 测试v2格式文件的加载时间
+    let v2_path = std::env::temp_dir().join(format!("phone_data_bench_v2_{}.dat", std::process::id()));
+    convert_v1_to_v2("phone.dat", &v2_path).expect("Failed to convert phone.dat to v2");
+    group.bench_function("v2_init", |b| {
+        b.iter(|| {
+            black_box(PhoneDataV2::from_path(&v2_path).ok());
+        })
+    });
+
+    // 测试压缩文件的加载时间，并输出磁盘大小、加载时的临时缓冲和加载后的常驻内存
+    let v1 = std::fs::read("phone.dat").expect("Failed to read phone.dat");
+    let v2 = std::fs::read(&v2_path).expect("Failed to read v2 file");
+    for (name, data, compression) in [
+        ("v1_gzip_init", &v1, Compression::Gzip),
+        ("v1_zstd_init", &v1, Compression::Zstd),
+        ("v2_zstd_init", &v2, Compression::Zstd),
+    ] {
+        let path = std::env::temp_dir().join(format!("phone_data_bench_{}_{}.dat", name, std::process::id()));
+        let compressed = compression::compress(data, compression).expect("Failed to compress phone.dat");
+        std::fs::write(&path, &compressed).expect("Failed to write compressed file");
+        let resident = if name.starts_with("v2") {
+            PhoneDataV2::from_path(&path).expect("Failed to load compressed file").memory_usage_bytes()
+        } else {
+            PhoneDataSimd::from_path(&path).expect("Failed to load compressed file").memory_usage_bytes()
+        };
+        println!(
+            "{}: {} bytes on disk ({} uncompressed), {} bytes transient, {} bytes resident",
+            name,
+            compressed.len(),
+            data.len(),
+            compressed.len() + data.len(),
+            resident,
+        );
+
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                if name.starts_with("v2") {
+                    black_box(PhoneDataV2::from_path(&path).ok());
+                } else {
+                    black_box(PhoneDataSimd::from_path(&path).ok());
+                }
+            })
+        });
+        let _ = std::fs::remove_file(&path);
+    }
+    let _ = std::fs::remove_file(&v2_path);
+
     group.finish();
 }
 
@@ -188,6 +320,7 @@ fn bench_memory_efficiency(c: &mut Criterion) {
     let hash_data = load_hash_data();
     let simd_data = load_simd_data();
     let bloom_data = load_bloom_data();
+    let eytzinger_data = load_eytzinger_data();
 
     let mut group = c.benchmark_group("memory_efficiency");
     group.measurement_time(Duration::from_secs(5));
@@ -227,6 +360,14 @@ fn bench_memory_efficiency(c: &mut Criterion) {
         })
     });
 
+    group.bench_function("eytzinger_memory_access", |b| {
+        b.iter(|| {
+            for _ in 0..100 {
+                black_box(eytzinger_data.find(black_box(test_phone)).ok());
+            }
+        })
+    });
+
     group.finish();
 }
 
@@ -235,6 +376,11 @@ fn bench_failed_lookups(c: &mut Criterion) {
     let hash_data = load_hash_data();
     let simd_data = load_simd_data();
     let bloom_data = load_bloom_data();
+    let eytzinger_data = load_eytzinger_data();
+    let xor_data = PhoneDataBloom::builder()
+        .filter_type(FilterType::Xor)
+        .build()
+        .expect("Failed to load xor filter phone data");
 
     let mut group = c.benchmark_group("failed_lookups");
     group.measurement_time(Duration::from_secs(5));
@@ -287,6 +433,26 @@ fn bench_failed_lookups(c: &mut Criterion) {
                 })
             },
         );
+
+        group.bench_with_input(
+            format!("xor_failed_{}", phone),
+            black_box(phone),
+            |b, phone| {
+                b.iter(|| {
+                    black_box(xor_data.find(black_box(phone)).ok());
+                })
+            },
+        );
+
+        group.bench_with_input(
+            format!("eytzinger_failed_{}", phone),
+            black_box(phone),
+            |b, phone| {
+                b.iter(|| {
+                    black_box(eytzinger_data.find(black_box(phone)).ok());
+                })
+            },
+        );
     }
 
     group.finish();
@@ -296,6 +462,7 @@ criterion_group!(
     benches,
     bench_single_lookup_comparison,
     bench_bulk_lookup_comparison,
+    bench_batch_lookup_comparison,
     bench_initialization_time,
     bench_memory_efficiency,
     bench_failed_lookups
@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Context, Result};
+
+/// 限流桶数量超过该值时清理已回满的桶，避免按IP计数时内存无限增长
+const BUCKET_CLEANUP_THRESHOLD: usize = 100_000;
+
+/// 令牌桶配额
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quota {
+    /// 每秒补充的令牌数，不大于0表示不限流
+    pub per_second: f64,
+    /// 桶容量，即允许的突发请求数
+    pub burst: u32,
+}
+
+impl Quota {
+    pub fn new(per_second: f64, burst: u32) -> Self {
+        Quota { per_second, burst: burst.max(1) }
+    }
+
+    /// 从配置创建，每秒请求数必须是有限的正数
+    pub fn try_new(per_second: f64, burst: u32) -> Result<Self> {
+        if !per_second.is_finite() || per_second <= 0.0 {
+            return Err(anyhow!("rate must be a positive number, got {}", per_second));
+        }
+        Ok(Quota::new(per_second, burst))
+    }
+
+    /// 不限流
+    pub fn unlimited() -> Self {
+        Quota { per_second: 0.0, burst: 0 }
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.per_second > 0.0
+    }
+}
+
+/// 单次限流检查后的桶状态，用于生成`X-RateLimit-*`响应头
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitStatus {
+    /// 桶容量
+    pub limit: u32,
+    /// 剩余令牌数
+    pub remaining: u32,
+    /// 桶回满所需时间
+    pub reset_after: Duration,
+    /// 被拒绝时，距离下一个令牌可用的时间
+    pub retry_after: Option<Duration>,
+}
+
+/// 令牌桶
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(quota: &Quota, now: Instant) -> Self {
+        TokenBucket { tokens: quota.burst as f64, updated: now }
+    }
+
+    fn refill(&mut self, quota: &Quota, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * quota.per_second).min(quota.burst as f64);
+        self.updated = now;
+    }
+
+    /// 补充令牌后的桶状态，令牌不足时带`retry_after`，不消耗令牌
+    fn peek(&mut self, quota: &Quota, now: Instant) -> RateLimitStatus {
+        self.refill(quota, now);
+        self.status(quota, self.tokens >= 1.0)
+    }
+
+    /// 取一个令牌，调用前应先用`peek`确认有令牌
+    fn take(&mut self, quota: &Quota) -> RateLimitStatus {
+        self.tokens = (self.tokens - 1.0).max(0.0);
+        self.status(quota, true)
+    }
+
+    fn status(&self, quota: &Quota, allowed: bool) -> RateLimitStatus {
+        let missing = quota.burst as f64 - self.tokens;
+        RateLimitStatus {
+            limit: quota.burst,
+            remaining: self.tokens.floor() as u32,
+            reset_after: saturating_secs(missing / quota.per_second),
+            retry_after: if allowed {
+                None
+            } else {
+                Some(saturating_secs((1.0 - self.tokens) / quota.per_second))
+            },
+        }
+    }
+
+    fn is_full(&mut self, quota: &Quota, now: Instant) -> bool {
+        self.refill(quota, now);
+        self.tokens >= quota.burst as f64
+    }
+}
+
+/// 秒数转为时长，速率极小时超出范围的结果取最大值
+fn saturating_secs(secs: f64) -> Duration {
+    Duration::try_from_secs_f64(secs.max(0.0)).unwrap_or(Duration::MAX)
+}
+
+/// 按标识（API Key或IP）分别计数的令牌桶限流器
+#[derive(Debug)]
+pub struct RateLimiter {
+    quota: Quota,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(quota: Quota) -> Self {
+        RateLimiter { quota, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    /// 默认配额
+    pub fn quota(&self) -> Quota {
+        self.quota
+    }
+
+    /// 按默认配额检查，不限流时返回None
+    pub fn check(&self, id: &str, now: Instant) -> Option<RateLimitStatus> {
+        self.check_with_quota(id, self.quota, now)
+    }
+
+    /// 按指定配额检查，用于单独配置了配额的API Key
+    pub fn check_with_quota(&self, id: &str, quota: Quota, now: Instant) -> Option<RateLimitStatus> {
+        if !quota.is_limited() {
+            return None;
+        }
+
+        let mut locked = self.lock(&quota, now);
+        let status = locked.peek(id, &quota, now);
+        if status.retry_after.is_some() {
+            return Some(status);
+        }
+        Some(locked.take(id, &quota, now))
+    }
+
+    /// 加锁，桶过多时先清理
+    fn lock(&self, quota: &Quota, now: Instant) -> LockedLimiter<'_> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= BUCKET_CLEANUP_THRESHOLD {
+            // 已回满的桶与新建的桶等价，可以直接丢弃
+            buckets.retain(|_, bucket| !bucket.is_full(quota, now));
+        }
+        LockedLimiter { buckets }
+    }
+}
+
+/// 持有锁的限流器，用于先检查多个限流器、全部放行后再扣减令牌
+struct LockedLimiter<'a> {
+    buckets: MutexGuard<'a, HashMap<String, TokenBucket>>,
+}
+
+impl LockedLimiter<'_> {
+    fn peek(&mut self, id: &str, quota: &Quota, now: Instant) -> RateLimitStatus {
+        self.bucket(id, quota, now).peek(quota, now)
+    }
+
+    fn take(&mut self, id: &str, quota: &Quota, now: Instant) -> RateLimitStatus {
+        self.bucket(id, quota, now).take(quota)
+    }
+
+    fn bucket(&mut self, id: &str, quota: &Quota, now: Instant) -> &mut TokenBucket {
+        if !self.buckets.contains_key(id) {
+            self.buckets.insert(id.to_string(), TokenBucket::new(quota, now));
+        }
+        self.buckets.get_mut(id).expect("bucket was just inserted")
+    }
+}
+
+/// 一个API Key的信息
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiKey {
+    /// 调用方名称，用于追踪访问来源
+    pub name: String,
+    /// 单独配置的配额，未配置时使用默认配额
+    pub quota: Option<Quota>,
+}
+
+/// API Key列表
+///
+/// 文件每行一个Key：`key[,name[,每秒请求数,突发数]]`，空行和`#`开头的行忽略
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeys {
+    keys: HashMap<String, ApiKey>,
+}
+
+impl ApiKeys {
+    /// 从文件加载
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ApiKeys> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read api keys from {}", path.display()))?;
+        Self::parse(&content)
+    }
+
+    /// 解析Key列表文本
+    pub fn parse(content: &str) -> Result<ApiKeys> {
+        let mut keys = HashMap::new();
+        for (line_no, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let key = fields[0];
+            if key.is_empty() {
+                return Err(anyhow!("line {}: empty api key", line_no + 1));
+            }
+            let name = fields.get(1).filter(|name| !name.is_empty()).unwrap_or(&key).to_string();
+            let quota = match (fields.get(2), fields.get(3)) {
+                (None, None) => None,
+                (Some(rate), Some(burst)) => {
+                    let rate = rate.parse().with_context(|| format!("line {}: invalid rate", line_no + 1))?;
+                    let burst = burst.parse().with_context(|| format!("line {}: invalid burst", line_no + 1))?;
+                    Some(Quota::try_new(rate, burst).with_context(|| format!("line {}: invalid rate", line_no + 1))?)
+                }
+                _ => return Err(anyhow!("line {}: rate and burst must be given together", line_no + 1)),
+            };
+
+            if keys.insert(key.to_string(), ApiKey { name, quota }).is_some() {
+                return Err(anyhow!("line {}: duplicate api key", line_no + 1));
+            }
+        }
+        Ok(ApiKeys { keys })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&ApiKey> {
+        self.keys.get(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// 访问检查结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccessDecision {
+    /// 放行，附带最严格的一个限流桶状态
+    Allowed { client: Option<String>, rate_limit: Option<RateLimitStatus> },
+    /// 启用了API Key认证但未提供或无效
+    Unauthorized,
+    /// 超出配额
+    Limited(RateLimitStatus),
+}
+
+/// API Key认证和按Key、按IP的限流
+#[derive(Debug)]
+pub struct AccessControl {
+    /// Key文件路径，未配置时不启用认证
+    keys_path: Option<PathBuf>,
+    keys: RwLock<Arc<ApiKeys>>,
+    key_limiter: RateLimiter,
+    ip_limiter: RateLimiter,
+}
+
+impl Default for AccessControl {
+    fn default() -> Self {
+        AccessControl::new(None, ApiKeys::default(), Quota::unlimited(), Quota::unlimited())
+    }
+}
+
+impl AccessControl {
+    pub fn new(keys_path: Option<PathBuf>, keys: ApiKeys, key_quota: Quota, ip_quota: Quota) -> Self {
+        AccessControl {
+            keys_path,
+            keys: RwLock::new(Arc::new(keys)),
+            key_limiter: RateLimiter::new(key_quota),
+            ip_limiter: RateLimiter::new(ip_quota),
+        }
+    }
+
+    /// 配置了Key文件时加载并启用认证
+    pub fn load(keys_path: Option<PathBuf>, key_quota: Quota, ip_quota: Quota) -> Result<Self> {
+        let keys = match &keys_path {
+            Some(path) => ApiKeys::load(path)?,
+            None => ApiKeys::default(),
+        };
+        Ok(Self::new(keys_path, keys, key_quota, ip_quota))
+    }
+
+    /// 是否要求API Key
+    pub fn auth_enabled(&self) -> bool {
+        self.keys_path.is_some()
+    }
+
+    /// 重新读取Key文件，返回Key数量；失败时保留原列表
+    pub fn reload_keys(&self) -> Result<usize> {
+        let path = self.keys_path.as_ref().ok_or_else(|| anyhow!("api key authentication is disabled"))?;
+        let keys = ApiKeys::load(path)?;
+        let count = keys.len();
+        *self.keys.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(keys);
+        Ok(count)
+    }
+
+    /// 检查一次请求，Key和IP的配额都有剩余时才各消耗一个令牌
+    pub fn check(&self, api_key: Option<&str>, ip: &str, now: Instant) -> AccessDecision {
+        let mut client = None;
+        let mut key_limit = None;
+
+        if self.auth_enabled() {
+            let keys = self.keys.read().unwrap_or_else(|e| e.into_inner()).clone();
+            let Some((key, info)) = api_key.and_then(|key| keys.get(key).map(|info| (key, info))) else {
+                return AccessDecision::Unauthorized;
+            };
+            let quota = info.quota.unwrap_or(self.key_limiter.quota());
+            if quota.is_limited() {
+                key_limit = Some((key, quota));
+            }
+            client = Some(info.name.clone());
+        }
+        let ip_quota = self.ip_limiter.quota();
+
+        // 固定先锁Key再锁IP；两个桶都检查通过后再扣减，被一方拒绝的请求不消耗另一方的令牌
+        let mut key_limiter = key_limit.map(|(key, quota)| (key, quota, self.key_limiter.lock(&quota, now)));
+        let mut ip_limiter = ip_quota.is_limited().then(|| self.ip_limiter.lock(&ip_quota, now));
+        if let Some((key, quota, limiter)) = &mut key_limiter {
+            let status = limiter.peek(key, quota, now);
+            if status.retry_after.is_some() {
+                return AccessDecision::Limited(status);
+            }
+        }
+        if let Some(limiter) = &mut ip_limiter {
+            let status = limiter.peek(ip, &ip_quota, now);
+            if status.retry_after.is_some() {
+                return AccessDecision::Limited(status);
+            }
+        }
+
+        let key_status = key_limiter.map(|(key, quota, mut limiter)| limiter.take(key, &quota, now));
+        let ip_status = ip_limiter.map(|mut limiter| limiter.take(ip, &ip_quota, now));
+        let rate_limit = key_status.into_iter().chain(ip_status).min_by_key(|status| status.remaining);
+        AccessDecision::Allowed { client, rate_limit }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket_refill() {
+        let limiter = RateLimiter::new(Quota::new(2.0, 3));
+        let start = Instant::now();
+
+        for remaining in [2, 1, 0] {
+            let status = limiter.check("a", start).unwrap();
+            assert_eq!(status.remaining, remaining);
+            assert!(status.retry_after.is_none());
+        }
+        let limited = limiter.check("a", start).unwrap();
+        assert_eq!(limited.retry_after, Some(Duration::from_millis(500)));
+        assert_eq!(limited.reset_after, Duration::from_millis(1500));
+
+        // 其他标识使用独立的桶
+        assert!(limiter.check("b", start).unwrap().retry_after.is_none());
+
+        // 0.5秒后补充一个令牌
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.check("a", later).unwrap().retry_after.is_none());
+        assert!(limiter.check("a", later).unwrap().retry_after.is_some());
+        assert!(RateLimiter::new(Quota::unlimited()).check("a", start).is_none());
+    }
+
+    #[test]
+    fn test_parse_api_keys() {
+        let keys = ApiKeys::parse("# partners\nk1,billing\n\nk2,risk,10,20\nk3\n").unwrap();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys.get("k1").unwrap().name, "billing");
+        assert_eq!(keys.get("k2").unwrap().quota, Some(Quota::new(10.0, 20)));
+        assert_eq!(keys.get("k3").unwrap().name, "k3");
+        assert!(keys.get("k4").is_none());
+
+        assert!(ApiKeys::parse("k1\nk1\n").is_err());
+        assert!(ApiKeys::parse("k1,name,10\n").is_err());
+        assert!(ApiKeys::parse("k1,name,x,1\n").is_err());
+        assert!(ApiKeys::parse("k1,name,0,1\n").is_err());
+        assert!(ApiKeys::parse("k1,name,NaN,1\n").is_err());
+    }
+
+    #[test]
+    fn test_access_decisions() {
+        let keys = ApiKeys::parse("k1,billing\nk2,risk,1,1\n").unwrap();
+        let access = AccessControl::new(Some(PathBuf::from("keys")), keys, Quota::new(100.0, 100), Quota::new(1.0, 2));
+        let now = Instant::now();
+
+        assert_eq!(access.check(None, "10.0.0.1", now), AccessDecision::Unauthorized);
+        assert_eq!(access.check(Some("bad"), "10.0.0.1", now), AccessDecision::Unauthorized);
+
+        // 按IP的桶更严格，响应头取剩余最少的桶
+        match access.check(Some("k1"), "10.0.0.1", now) {
+            AccessDecision::Allowed { client, rate_limit } => {
+                assert_eq!(client.as_deref(), Some("billing"));
+                assert_eq!(rate_limit.unwrap().limit, 2);
+                assert_eq!(rate_limit.unwrap().remaining, 1);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // 单独配置的Key配额
+        assert!(matches!(access.check(Some("k2"), "10.0.0.2", now), AccessDecision::Allowed { .. }));
+        assert!(matches!(access.check(Some("k2"), "10.0.0.3", now), AccessDecision::Limited(_)));
+
+        // 未启用认证时只按IP限流
+        let open = AccessControl::new(None, ApiKeys::default(), Quota::unlimited(), Quota::new(1.0, 1));
+        assert!(matches!(open.check(None, "10.0.0.1", now), AccessDecision::Allowed { client: None, .. }));
+        assert!(matches!(open.check(None, "10.0.0.1", now), AccessDecision::Limited(_)));
+        assert!(open.reload_keys().is_err());
+    }
+
+    #[test]
+    fn test_ip_limit_does_not_consume_key_quota() {
+        let keys = ApiKeys::parse("k1,billing,1,2\n").unwrap();
+        let access = AccessControl::new(Some(PathBuf::from("keys")), keys, Quota::unlimited(), Quota::new(1.0, 1));
+        let now = Instant::now();
+
+        assert!(matches!(access.check(Some("k1"), "10.0.0.1", now), AccessDecision::Allowed { .. }));
+        // 被IP限流的请求不扣减Key的令牌，换一个IP仍有一个令牌
+        for _ in 0..3 {
+            assert!(matches!(access.check(Some("k1"), "10.0.0.1", now), AccessDecision::Limited(_)));
+        }
+        assert!(matches!(access.check(Some("k1"), "10.0.0.2", now), AccessDecision::Allowed { .. }));
+        assert!(matches!(access.check(Some("k1"), "10.0.0.3", now), AccessDecision::Limited(_)));
+    }
+
+    #[test]
+    fn test_tiny_rate_saturates() {
+        let limiter = RateLimiter::new(Quota::new(f64::MIN_POSITIVE, 1));
+        let now = Instant::now();
+        assert!(limiter.check("a", now).unwrap().retry_after.is_none());
+        let limited = limiter.check("a", now).unwrap();
+        assert_eq!(limited.retry_after, Some(Duration::MAX));
+        assert_eq!(limited.reset_after, Duration::MAX);
+        assert!(Quota::try_new(0.0, 1).is_err());
+        assert!(Quota::try_new(f64::INFINITY, 1).is_err());
+        assert!(Quota::try_new(-1.0, 1).is_err());
+    }
+}
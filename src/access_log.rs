@@ -0,0 +1,259 @@
+use std::io::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use anyhow::{anyhow, Result};
+use serde_derive::Serialize;
+
+/// 访问日志格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// 不输出访问日志
+    Off,
+    /// 便于人读的单行文本
+    #[default]
+    Text,
+    /// 每行一个JSON对象，便于日志系统采集
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<LogFormat> {
+        match s {
+            "off" => Ok(LogFormat::Off),
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(anyhow!("unknown log format {:?}, expected off, text or json", s)),
+        }
+    }
+}
+
+/// 一条访问日志
+#[derive(Debug, Clone, Serialize)]
+pub struct AccessLogEntry {
+    /// RFC 3339格式的UTC时间
+    pub timestamp: String,
+    pub method: String,
+    /// 路由模板，避免路径中的号码进入日志
+    pub route: String,
+    pub status: u16,
+    /// 请求耗时（毫秒）
+    pub latency_ms: f64,
+    /// 查找失败的原因，查找成功或非查询请求时为空
+    pub error_kind: Option<&'static str>,
+    /// 查找实现
+    pub backend: &'static str,
+    /// 数据版本
+    pub db_version: String,
+    /// 脱敏后的号码
+    pub phone: Option<String>,
+}
+
+impl AccessLogEntry {
+    /// 文本格式，缺失的字段输出为`-`
+    pub fn to_text(&self) -> String {
+        format!(
+            "{} {} {} {} {:.3}ms error_kind={} phone={} backend={} db_version={}",
+            self.timestamp,
+            self.method,
+            self.route,
+            self.status,
+            self.latency_ms,
+            self.error_kind.unwrap_or("-"),
+            self.phone.as_deref().unwrap_or("-"),
+            self.backend,
+            self.db_version,
+        )
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// 日志输出目标
+enum Sink {
+    Stdout,
+    Writer(Mutex<Box<dyn Write + Send>>),
+}
+
+/// 按格式和采样率输出访问日志
+pub struct AccessLogger {
+    format: LogFormat,
+    /// 采样率，0到1之间
+    sample_rate: f64,
+    /// 已处理的请求数，用于确定性采样
+    seen: AtomicU64,
+    sink: Sink,
+}
+
+impl std::fmt::Debug for AccessLogger {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("AccessLogger")
+            .field("format", &self.format)
+            .field("sample_rate", &self.sample_rate)
+            .finish()
+    }
+}
+
+impl Default for AccessLogger {
+    fn default() -> Self {
+        AccessLogger::new(LogFormat::Off, 1.0)
+    }
+}
+
+impl AccessLogger {
+    /// 输出到标准输出
+    pub fn new(format: LogFormat, sample_rate: f64) -> Self {
+        AccessLogger {
+            format,
+            sample_rate: sample_rate.clamp(0.0, 1.0),
+            seen: AtomicU64::new(0),
+            sink: Sink::Stdout,
+        }
+    }
+
+    /// 输出到指定位置，每条日志一行
+    pub fn with_writer(mut self, writer: Box<dyn Write + Send>) -> Self {
+        self.sink = Sink::Writer(Mutex::new(writer));
+        self
+    }
+
+    /// 是否输出日志
+    pub fn enabled(&self) -> bool {
+        self.format != LogFormat::Off && self.sample_rate > 0.0
+    }
+
+    /// 是否记录本次请求
+    ///
+    /// 按请求序号均匀采样：第n个请求在`floor(n * rate)`增加时记录，
+    /// 不依赖随机数，采样率0.01即每100个请求记录1个
+    pub fn sample(&self) -> bool {
+        if !self.enabled() {
+            return false;
+        }
+        let n = self.seen.fetch_add(1, Ordering::Relaxed) as f64;
+        ((n + 1.0) * self.sample_rate).floor() > (n * self.sample_rate).floor()
+    }
+
+    /// 输出一条日志，调用前应先通过`sample`
+    pub fn log(&self, entry: &AccessLogEntry) {
+        let line = match self.format {
+            LogFormat::Off => return,
+            LogFormat::Text => entry.to_text(),
+            LogFormat::Json => entry.to_json(),
+        };
+        match &self.sink {
+            Sink::Stdout => println!("{}", line),
+            Sink::Writer(writer) => {
+                let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+                let _ = writeln!(writer, "{}", line);
+            }
+        }
+    }
+}
+
+/// 格式化为毫秒精度的RFC 3339 UTC时间，如`2024-10-01T08:30:00.123Z`
+pub fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO);
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs_of_day = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis(),
+    )
+}
+
+/// 1970-01-01起的天数转换为公历日期（Howard Hinnant的civil_from_days算法）
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// 可在测试中读取内容的共享缓冲区
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn entry() -> AccessLogEntry {
+        AccessLogEntry {
+            timestamp: format_timestamp(UNIX_EPOCH + Duration::from_millis(1_727_771_400_123)),
+            method: "GET".to_string(),
+            route: "/query2/{phone}".to_string(),
+            status: 200,
+            latency_ms: 0.25,
+            error_kind: Some("not_found"),
+            backend: "PhoneDataSimd",
+            db_version: "2410".to_string(),
+            phone: Some("1590000****".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(entry().timestamp, "2024-10-01T08:30:00.123Z");
+        assert_eq!(format_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)), "2000-02-29T00:00:00.000Z");
+    }
+
+    #[test]
+    fn test_text_and_json_lines() {
+        assert_eq!(
+            entry().to_text(),
+            "2024-10-01T08:30:00.123Z GET /query2/{phone} 200 0.250ms error_kind=not_found phone=1590000**** backend=PhoneDataSimd db_version=2410"
+        );
+
+        let buffer = SharedBuffer::default();
+        let logger = AccessLogger::new(LogFormat::Json, 1.0).with_writer(Box::new(buffer.clone()));
+        logger.log(&entry());
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(output.trim_end()).unwrap();
+        assert_eq!(value["route"], "/query2/{phone}");
+        assert_eq!(value["error_kind"], "not_found");
+        assert_eq!(value["phone"], "1590000****");
+        assert!(output.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_sampling_rate() {
+        let sampled = |rate: f64| {
+            let logger = AccessLogger::new(LogFormat::Text, rate);
+            (0..1000).filter(|_| logger.sample()).count()
+        };
+        assert_eq!(sampled(1.0), 1000);
+        assert_eq!(sampled(0.1), 100);
+        assert_eq!(sampled(0.0), 0);
+        assert_eq!((0..10).filter(|_| AccessLogger::default().sample()).count(), 0);
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+}
@@ -1,8 +1,7 @@
-use std::fs::File;
-use std::io::{BufReader, Read};
+use std::path::Path;
 use anyhow::Result;
 use serde_derive::Serialize;
-use crate::common::{utils, Index, PhoneNoInfo, PhoneLookup, PhoneStats, ErrorKind};
+use crate::common::{utils, Index, PhoneNoInfo, PhoneLookup, PhoneStats, ErrorKind, LookupStats, DEFAULT_DATA_PATH, MERGE_BATCH_THRESHOLD};
 
 
 
@@ -16,71 +15,22 @@ pub struct PhoneData {
 
 impl PhoneData {
     pub fn new() -> Result<PhoneData> {
-        let data_file = File::open("phone.dat")?;
-        let mut data_file = BufReader::new(data_file);
-
-        // parse version and index offset
-        let mut header_buffer = [0u8; 8];
-        data_file.read_exact(&mut header_buffer)?;
-        let version = String::from_utf8((&header_buffer[..4]).to_vec())?;
-        let index_offset = utils::four_u8_to_i32(&header_buffer[4..]) as u64;
-
-        // read records
-        let mut records = vec![0u8; index_offset as usize - 8];
-        data_file.read_exact(&mut records)?;
-
-        // parse index
-        let mut index = Vec::new();
-        // length of a index is 9
-        let mut index_item = [0u8; 9];
-        loop {
-            match data_file.read_exact(&mut index_item) {
-                Ok(_) => (),
-                Err(e) => match e.kind() {
-                    std::io::ErrorKind::UnexpectedEof => break,
-                    _ => (),
-                },
-            }
-            let phone_no_prefix = utils::four_u8_to_i32(&index_item[..4]);
-            let records_offset = utils::four_u8_to_i32(&index_item[4..8]);
-            let card_type = index_item[8];
-            index.push(Index {
-                phone_no_prefix,
-                records_offset,
-                card_type,
-            });
-        }
+        Self::from_path(DEFAULT_DATA_PATH)
+    }
 
-        let config = PhoneData {
-            version,
+    /// 从指定路径加载数据
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<PhoneData> {
+        let (header, records, index) = utils::read_database(path)?;
+        Ok(PhoneData {
+            version: header.version,
             records,
             index,
-        };
-        Ok(config)
+        })
     }
 
-    /// 辅助函数：构建PhoneNoInfo，减少重复代码
+    /// 二分查找前缀，`probes`累计比较次数
     #[inline]
-    fn build_phone_info(&self, index: &Index) -> Result<PhoneNoInfo> {
-        let record = utils::parse_record_data(&self.records, index.records_offset as usize)?;
-        utils::build_phone_info(&record, index.card_type)
-    }
-}
-
-impl PhoneLookup for PhoneData {
-    fn find(&self, no: &str) -> Result<PhoneNoInfo> {
-        let len = no.len();
-        if len < 7 || len > 11 {
-            return Err(ErrorKind::InvalidLength.into());
-        }
-
-        // 优化：只解析前7位并提前转换为i32
-        let phone_prefix = if len == 7 {
-            no.parse::<i32>()?
-        } else {
-            no[..7].parse::<i32>()?
-        };
-
+    fn search(&self, target: i32, probes: &mut u32) -> Option<&Index> {
         let mut left = 0usize;
         let mut right = self.index.len();
 
@@ -92,8 +42,9 @@ impl PhoneLookup for PhoneData {
                 // unsafe访问提升性能，因为mid保证在有效范围内
                 self.index.get_unchecked(mid)
             };
+            *probes += 1;
 
-            match mid_index.phone_no_prefix.cmp(&phone_prefix) {
+            match mid_index.phone_no_prefix.cmp(&target) {
                 std::cmp::Ordering::Greater => {
                     right = mid;
                 }
@@ -101,13 +52,63 @@ impl PhoneLookup for PhoneData {
                     left = mid + 1;
                 }
                 std::cmp::Ordering::Equal => {
-                    // 找到匹配项，解析记录并返回
-                    return self.build_phone_info(mid_index);
+                    return Some(mid_index);
                 }
             }
         }
 
-        Err(ErrorKind::NotFound.into())
+        None
+    }
+
+    /// 辅助函数：构建PhoneNoInfo，减少重复代码
+    #[inline]
+    fn build_phone_info(&self, index: &Index) -> Result<PhoneNoInfo> {
+        let record = utils::parse_record_data(&self.records, index.records_offset as usize)?;
+        utils::build_phone_info(&record, index.card_type)
+    }
+}
+
+impl PhoneLookup for PhoneData {
+    fn find(&self, no: &str) -> Result<PhoneNoInfo> {
+        let len = no.len();
+        if !(7..=11).contains(&len) {
+            return Err(ErrorKind::InvalidLength.into());
+        }
+
+        // 优化：只解析前7位并提前转换为i32
+        let phone_prefix = if len == 7 {
+            no.parse::<i32>()?
+        } else {
+            no.get(..7).ok_or(ErrorKind::InvalidLength)?.parse::<i32>()?
+        };
+
+        match self.search(phone_prefix, &mut 0) {
+            // 找到匹配项，解析记录并返回
+            Some(index) => self.build_phone_info(index),
+            None => Err(ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn find_with_stats(&self, no: &str) -> (Result<PhoneNoInfo>, LookupStats) {
+        utils::instrumented_find(
+            no,
+            |prefix, probes| self.search(prefix, probes),
+            |index| self.build_phone_info(index),
+        )
+    }
+
+    fn find_batch(&self, phones: &[&str]) -> Vec<Result<PhoneNoInfo>> {
+        if phones.len() < MERGE_BATCH_THRESHOLD {
+            return phones.iter().map(|phone| self.find(phone)).collect();
+        }
+
+        // 大批量：排序后单次归并遍历索引
+        utils::merge_find_batch(
+            phones,
+            self.index.len(),
+            |i| self.index[i].phone_no_prefix,
+            |i| self.build_phone_info(&self.index[i]),
+        )
     }
 }
 
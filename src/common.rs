@@ -1,3 +1,4 @@
+use std::time::{Duration, Instant};
 use anyhow::Result;
 use serde_derive::Serialize;
 
@@ -23,6 +24,80 @@ impl std::fmt::Display for ErrorKind {
 
 impl std::error::Error for ErrorKind {}
 
+/// 单次查找的结果分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LookupOutcome {
+    /// 命中
+    Found,
+    /// 被成员过滤器直接排除
+    FilteredOut,
+    /// 索引中不存在
+    NotFound,
+    /// 号码长度或格式不合法
+    InvalidLength,
+    /// 记录中的运营商编号不合法
+    InvalidOpNo,
+    /// 数据库记录损坏
+    InvalidPhoneDatabase,
+}
+
+impl LookupOutcome {
+    /// 按错误类型分类；非ErrorKind的错误只会来自号码解析，与`validate_phone_no`一致归为InvalidLength
+    pub fn from_error(error: &anyhow::Error) -> LookupOutcome {
+        match error.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::NotFound) => LookupOutcome::NotFound,
+            Some(ErrorKind::InvalidOpNo) => LookupOutcome::InvalidOpNo,
+            Some(ErrorKind::InvalidPhoneDatabase) => LookupOutcome::InvalidPhoneDatabase,
+            Some(ErrorKind::InvalidLength) | None => LookupOutcome::InvalidLength,
+        }
+    }
+
+    /// 按查找结果分类
+    pub fn from_result<T>(result: &Result<T>) -> LookupOutcome {
+        match result {
+            Ok(_) => LookupOutcome::Found,
+            Err(e) => LookupOutcome::from_error(e),
+        }
+    }
+
+    /// 分类名称，与序列化结果一致
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LookupOutcome::Found => "found",
+            LookupOutcome::FilteredOut => "filtered_out",
+            LookupOutcome::NotFound => "not_found",
+            LookupOutcome::InvalidLength => "invalid_length",
+            LookupOutcome::InvalidOpNo => "invalid_op_no",
+            LookupOutcome::InvalidPhoneDatabase => "invalid_phone_database",
+        }
+    }
+}
+
+/// 单次查找各阶段的耗时统计
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct LookupStats {
+    /// 号码校验和前缀解析耗时
+    pub parse_time: Duration,
+    /// 成员过滤器检查耗时，没有过滤器的实现为0
+    pub filter_time: Duration,
+    /// 在索引中定位前缀的耗时
+    pub search_time: Duration,
+    /// 定位过程中的比较次数，一次向量比较或一次哈希探测计为1
+    pub probes: u32,
+    /// 解码记录、构建结果的耗时
+    pub decode_time: Duration,
+    /// 结果分类
+    pub outcome: LookupOutcome,
+}
+
+impl LookupStats {
+    /// 各阶段耗时之和
+    pub fn total_time(&self) -> Duration {
+        self.parse_time + self.filter_time + self.search_time + self.decode_time
+    }
+}
+
 /// 手机号查询结果结构体
 #[derive(Debug, Serialize, Clone)]
 pub struct PhoneNoInfo {
@@ -106,6 +181,21 @@ impl CardType {
     }
 }
 
+impl std::str::FromStr for CardType {
+    type Err = anyhow::Error;
+
+    /// 接受运营商代码（1-8）或描述，如`中国移动`
+    fn from_str(s: &str) -> Result<CardType> {
+        if let Ok(code) = s.parse::<u8>() {
+            return CardType::from_u8(code).map_err(|_| anyhow::anyhow!("unknown carrier code {}", code));
+        }
+        (1..=8)
+            .filter_map(|code| CardType::from_u8(code).ok())
+            .find(|card_type| card_type.get_description() == s)
+            .ok_or_else(|| anyhow::anyhow!("unknown carrier {:?}", s))
+    }
+}
+
 /// 索引结构体 - 用于二分查找等算法
 #[derive(Debug, Serialize, Clone)]
 pub struct Index {
@@ -158,14 +248,45 @@ pub trait PhoneLookup {
     fn find(&self, no: &str) -> Result<PhoneNoInfo>;
 
     /// 批量查找手机号信息
+    ///
+    /// 默认逐个调用`find`；持有有序键数组的实现会在批量较大时改用有序归并
     fn find_batch(&self, phones: &[&str]) -> Vec<Result<PhoneNoInfo>> {
         phones.iter().map(|phone| self.find(phone)).collect()
     }
 
+    /// 带分阶段计时的查找，不需要跑基准测试就能在线上定位性能退化
+    ///
+    /// 默认实现只能把整个`find`计入查找耗时；内置实现都会覆盖它，
+    /// 分别报告探测次数和记录解码耗时
+    fn find_with_stats(&self, no: &str) -> (Result<PhoneNoInfo>, LookupStats) {
+        let start = Instant::now();
+        let parsed = self.validate_phone_no(no);
+        let parse_time = start.elapsed();
+
+        let (result, search_time) = match parsed {
+            Ok(_) => {
+                let search_start = Instant::now();
+                let result = self.find(no);
+                (result, search_start.elapsed())
+            }
+            Err(e) => (Err(e), Duration::ZERO),
+        };
+
+        let stats = LookupStats {
+            parse_time,
+            filter_time: Duration::ZERO,
+            search_time,
+            probes: 0,
+            decode_time: Duration::ZERO,
+            outcome: LookupOutcome::from_result(&result),
+        };
+        (result, stats)
+    }
+
     /// 验证手机号格式
     fn validate_phone_no(&self, no: &str) -> Result<i32> {
         let len = no.len();
-        if len < 7 || len > 11 {
+        if !(7..=11).contains(&len) {
             return Err(ErrorKind::InvalidLength.into());
         }
 
@@ -173,7 +294,7 @@ pub trait PhoneLookup {
         if len == 7 {
             no.parse::<i32>()
         } else {
-            no[..7].parse::<i32>()
+            no.get(..7).ok_or(ErrorKind::InvalidLength)?.parse::<i32>()
         }.map_err(|_| ErrorKind::InvalidLength.into())
     }
 }
@@ -206,9 +327,303 @@ impl DatabaseHeader {
     }
 }
 
+/// 批量查询超过该数量时改用有序归并，数量较少时排序开销得不偿失
+pub const MERGE_BATCH_THRESHOLD: usize = 64;
+
+/// 各实现`new()`默认读取的数据文件
+pub const DEFAULT_DATA_PATH: &str = "phone.dat";
+
 /// 通用工具函数
 pub mod utils {
     use super::*;
+    use std::path::Path;
+    use sha2::{Digest, Sha256};
+
+    /// 读取phone.dat，返回头部、记录区和按前缀有序的索引区；存在`.sha256`文件时先校验
+    pub fn read_database<P: AsRef<Path>>(path: P) -> Result<(DatabaseHeader, Vec<u8>, Vec<Index>)> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)?;
+        crate::integrity::IntegrityPolicy::default().verify(path, &data)?;
+        parse_database(&data)
+    }
+
+    /// 解析数据文件内容，按魔数先解压zstd/gzip，再区分v2格式，其余按v1格式解析
+    pub fn parse_database(data: &[u8]) -> Result<(DatabaseHeader, Vec<u8>, Vec<Index>)> {
+        let data = crate::compression::decompress(data)?;
+        let data = data.as_ref();
+        if data.starts_with(&crate::format_v2::MAGIC) {
+            return Ok(crate::format_v2::PhoneDataV2::from_bytes(data)?.to_index());
+        }
+
+        // 解析版本号和索引偏移
+        let header = data.get(..8).ok_or(ErrorKind::InvalidPhoneDatabase)?;
+        let version = String::from_utf8(header[..4].to_vec())?;
+        let index_offset = four_u8_to_i32(&header[4..]);
+        if index_offset < 8 {
+            return Err(ErrorKind::InvalidPhoneDatabase.into());
+        }
+
+        // 读取记录区
+        let records = data.get(8..index_offset as usize).ok_or(ErrorKind::InvalidPhoneDatabase)?.to_vec();
+
+        // 解析索引区，每项9字节，末尾不足一项的字节忽略
+        let index = data[index_offset as usize..]
+            .chunks_exact(9)
+            .map(|item| Index {
+                phone_no_prefix: four_u8_to_i32(&item[..4]),
+                records_offset: four_u8_to_i32(&item[4..8]),
+                card_type: item[8],
+            })
+            .collect();
+
+        Ok((DatabaseHeader::new(version, index_offset as u64), records, index))
+    }
+
+    /// CRC-32（IEEE 802.3）校验值，按slicing-by-8每次处理8字节
+    pub fn crc32(data: &[u8]) -> u32 {
+        const TABLES: [[u32; 256]; 8] = {
+            let mut tables = [[0u32; 256]; 8];
+            let mut i = 0;
+            while i < 256 {
+                let mut crc = i as u32;
+                let mut bit = 0;
+                while bit < 8 {
+                    crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+                    bit += 1;
+                }
+                tables[0][i] = crc;
+                i += 1;
+            }
+            let mut t = 1;
+            while t < 8 {
+                let mut i = 0;
+                while i < 256 {
+                    let prev = tables[t - 1][i];
+                    tables[t][i] = (prev >> 8) ^ tables[0][(prev & 0xFF) as usize];
+                    i += 1;
+                }
+                t += 1;
+            }
+            tables
+        };
+
+        let mut crc = !0u32;
+        let mut chunks = data.chunks_exact(8);
+        for chunk in &mut chunks {
+            let lo = crc ^ u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            crc = TABLES[7][(lo & 0xFF) as usize]
+                ^ TABLES[6][((lo >> 8) & 0xFF) as usize]
+                ^ TABLES[5][((lo >> 16) & 0xFF) as usize]
+                ^ TABLES[4][(lo >> 24) as usize]
+                ^ TABLES[3][chunk[4] as usize]
+                ^ TABLES[2][chunk[5] as usize]
+                ^ TABLES[1][chunk[6] as usize]
+                ^ TABLES[0][chunk[7] as usize];
+        }
+        for &b in chunks.remainder() {
+            crc = TABLES[0][((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8);
+        }
+        !crc
+    }
+
+    /// 跨Rust版本和进程稳定的64位摘要，取SHA-256的前8字节；各部分带长度前缀，避免拼接产生歧义
+    ///
+    /// 用于会写入响应的`ETag`等缓存键，不能使用实现随版本变化的`DefaultHasher`
+    pub fn stable_hash(parts: &[&[u8]]) -> u64 {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+        let digest = hasher.finalize();
+        u64::from_le_bytes(digest[..8].try_into().expect("sha-256 digest has 32 bytes"))
+    }
+
+    /// 解析手机号前7位，长度不合法时返回InvalidLength
+    #[inline]
+    pub fn parse_phone_prefix(no: &str) -> Result<i32> {
+        let len = no.len();
+        if !(7..=11).contains(&len) {
+            return Err(ErrorKind::InvalidLength.into());
+        }
+
+        Ok(if len == 7 {
+            no.parse::<i32>()?
+        } else {
+            no.get(..7).ok_or(ErrorKind::InvalidLength)?.parse::<i32>()?
+        })
+    }
+
+    /// 查找只使用号码的前7位
+    pub const PHONE_PREFIX_LEN: usize = 7;
+
+    /// 截取号码前7个字符，不足7个时原样返回；与`mask_phone_no`一样按字符计数
+    pub fn phone_prefix(no: &str) -> &str {
+        no.char_indices().nth(PHONE_PREFIX_LEN).map_or(no, |(end, _)| &no[..end])
+    }
+
+    /// 号码的字符数，隐私模式判断是否超过前7位时使用
+    pub fn phone_len(no: &str) -> usize {
+        no.chars().count()
+    }
+
+    /// 号码脱敏：保留前7个字符，其后每个字符替换为`*`，用于日志和回显
+    pub fn mask_phone_no(no: &str) -> String {
+        no.chars()
+            .enumerate()
+            .map(|(i, c)| if i < PHONE_PREFIX_LEN { c } else { '*' })
+            .collect()
+    }
+
+    /// 有序归并批量查找：解析全部前缀并排序后，单次遍历有序键数组
+    ///
+    /// `key_at(i)`返回有序键数组中第i个键，`build(i)`构建命中位置的结果；
+    /// 返回结果与输入顺序一致
+    pub fn merge_find_batch<K, B>(phones: &[&str], len: usize, key_at: K, build: B) -> Vec<Result<PhoneNoInfo>>
+    where
+        K: Fn(usize) -> i32,
+        B: Fn(usize) -> Result<PhoneNoInfo>,
+    {
+        merge_find_batch_filtered(phones, len, |_| true, key_at, build)
+    }
+
+    /// 带预过滤的有序归并批量查找，`may_contain`返回false的前缀直接判定为不存在
+    pub fn merge_find_batch_filtered<F, K, B>(
+        phones: &[&str],
+        len: usize,
+        may_contain: F,
+        key_at: K,
+        build: B,
+    ) -> Vec<Result<PhoneNoInfo>>
+    where
+        F: Fn(i32) -> bool,
+        K: Fn(usize) -> i32,
+        B: Fn(usize) -> Result<PhoneNoInfo>,
+    {
+        let mut results: Vec<Option<Result<PhoneNoInfo>>> = Vec::with_capacity(phones.len());
+        let mut queries = Vec::with_capacity(phones.len());
+
+        for (i, phone) in phones.iter().enumerate() {
+            match parse_phone_prefix(phone) {
+                Ok(prefix) => {
+                    if may_contain(prefix) {
+                        queries.push((prefix, i));
+                    }
+                    results.push(None);
+                }
+                Err(e) => results.push(Some(Err(e))),
+            }
+        }
+
+        queries.sort_unstable();
+
+        let mut cursor = 0usize;
+        for (prefix, i) in queries {
+            cursor = gallop_lower_bound(len, &key_at, cursor, prefix);
+            if cursor < len && key_at(cursor) == prefix {
+                results[i] = Some(build(cursor));
+            }
+        }
+
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| Err(ErrorKind::NotFound.into())))
+            .collect()
+    }
+
+    /// 分阶段计时的查找
+    ///
+    /// `search(prefix, &mut probes)`定位前缀并累计探测次数，`decode`把命中位置解码为结果
+    pub fn instrumented_find<P, S, D>(no: &str, search: S, decode: D) -> (Result<PhoneNoInfo>, LookupStats)
+    where
+        S: FnOnce(i32, &mut u32) -> Option<P>,
+        D: FnOnce(P) -> Result<PhoneNoInfo>,
+    {
+        instrumented_find_filtered(no, None::<fn(i32) -> bool>, search, decode)
+    }
+
+    /// 带预过滤的分阶段计时查找，`may_contain`返回false时结果为FilteredOut
+    pub fn instrumented_find_filtered<P, F, S, D>(
+        no: &str,
+        may_contain: Option<F>,
+        search: S,
+        decode: D,
+    ) -> (Result<PhoneNoInfo>, LookupStats)
+    where
+        F: FnOnce(i32) -> bool,
+        S: FnOnce(i32, &mut u32) -> Option<P>,
+        D: FnOnce(P) -> Result<PhoneNoInfo>,
+    {
+        let mut stats = LookupStats {
+            parse_time: Duration::ZERO,
+            filter_time: Duration::ZERO,
+            search_time: Duration::ZERO,
+            probes: 0,
+            decode_time: Duration::ZERO,
+            outcome: LookupOutcome::Found,
+        };
+
+        let start = Instant::now();
+        let parsed = parse_phone_prefix(no);
+        stats.parse_time = start.elapsed();
+        let prefix = match parsed {
+            Ok(prefix) => prefix,
+            Err(e) => {
+                stats.outcome = LookupOutcome::from_error(&e);
+                return (Err(e), stats);
+            }
+        };
+
+        if let Some(may_contain) = may_contain {
+            let filter_start = Instant::now();
+            let passed = may_contain(prefix);
+            stats.filter_time = filter_start.elapsed();
+            if !passed {
+                stats.outcome = LookupOutcome::FilteredOut;
+                return (Err(ErrorKind::NotFound.into()), stats);
+            }
+        }
+
+        let search_start = Instant::now();
+        let position = search(prefix, &mut stats.probes);
+        stats.search_time = search_start.elapsed();
+
+        let result = match position {
+            Some(position) => {
+                let decode_start = Instant::now();
+                let result = decode(position);
+                stats.decode_time = decode_start.elapsed();
+                result
+            }
+            None => Err(ErrorKind::NotFound.into()),
+        };
+
+        stats.outcome = LookupOutcome::from_result(&result);
+        (result, stats)
+    }
+
+    /// 从start开始倍增步长查找第一个不小于target的位置
+    ///
+    /// 查询已排序，游标只会前进，总代价为 O(m log(n/m))
+    #[inline]
+    pub fn gallop_lower_bound<K: Fn(usize) -> i32>(len: usize, key_at: &K, start: usize, target: i32) -> usize {
+        let mut bound = 1usize;
+        while start + bound < len && key_at(start + bound) < target {
+            bound *= 2;
+        }
+
+        let mut lo = start + bound / 2;
+        let mut hi = (start + bound + 1).min(len);
+        while lo < hi {
+            let mid = lo + ((hi - lo) >> 1);
+            if key_at(mid) < target {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
 
     /// 将4个字节转换为i32（小端序）
     pub fn four_u8_to_i32(s: &[u8]) -> i32 {
@@ -218,14 +633,15 @@ pub mod utils {
         i32::from_le_bytes([s[0], s[1], s[2], s[3]])
     }
 
-    /// 解析记录数据
+    /// 解析记录数据，偏移量越界时返回`InvalidPhoneDatabase`
     pub fn parse_record_data(records: &[u8], offset: usize) -> Result<ParsedRecord> {
-        let record_end = match records[offset - 8..].iter().position(|&b| b == 0) {
-            Some(pos) => offset - 8 + pos,
-            None => return Err(ErrorKind::InvalidPhoneDatabase.into()),
-        };
+        let record = offset
+            .checked_sub(8)
+            .and_then(|start| records.get(start..))
+            .ok_or(ErrorKind::InvalidPhoneDatabase)?;
+        let record_end = record.iter().position(|&b| b == 0).ok_or(ErrorKind::InvalidPhoneDatabase)?;
 
-        let record_slice = &records[offset - 8..record_end];
+        let record_slice = &record[..record_end];
         let record_str = std::str::from_utf8(record_slice)
             .map_err(|_| ErrorKind::InvalidPhoneDatabase)?;
 
@@ -268,13 +684,59 @@ mod tests {
         let card_type = CardType::Cmcc;
         assert_eq!(card_type.get_code(), 1);
         assert_eq!(card_type.get_description(), "中国移动");
+        assert_eq!("3".parse::<CardType>().unwrap().get_code(), 3);
+        assert_eq!("中国广电虚拟运营商".parse::<CardType>().unwrap().get_code(), 8);
+        assert!("9".parse::<CardType>().is_err());
+        assert!("中国邮政".parse::<CardType>().is_err());
+    }
+
+    /// 只用于测试trait默认方法的空实现
+    struct NoopLookup;
+
+    impl PhoneLookup for NoopLookup {
+        fn find(&self, _no: &str) -> Result<PhoneNoInfo> {
+            Err(ErrorKind::NotFound.into())
+        }
     }
 
     #[test]
     fn test_phone_validation() {
-        // 这个测试需要具体的实现来提供
-        // 这里只是一个示例
-        assert!(true); // 占位符
+        let lookup = NoopLookup;
+        assert_eq!(lookup.validate_phone_no("1808683").unwrap(), 1808683);
+        assert_eq!(lookup.validate_phone_no("18086834111").unwrap(), 1808683);
+        assert!(lookup.validate_phone_no("180868").is_err());
+        assert!(lookup.validate_phone_no("180868341112").is_err());
+        assert!(lookup.validate_phone_no("18o8683").is_err());
+    }
+
+    #[test]
+    fn test_gallop_lower_bound() {
+        let keys = [10, 20, 30, 40, 50, 60, 70, 80, 90];
+        let key_at = |i: usize| keys[i];
+        for start in 0..keys.len() {
+            for target in 0..100 {
+                let expected = start + keys[start..].partition_point(|&k| k < target);
+                assert_eq!(utils::gallop_lower_bound(keys.len(), &key_at, start, target), expected);
+            }
+        }
+        assert_eq!(utils::gallop_lower_bound(0, &key_at, 0, 5), 0);
+    }
+
+    #[test]
+    fn test_merge_find_batch_keeps_input_order() {
+        let keys = [1300000, 1300005, 1300010, 1390000];
+        let build = |i: usize| Ok(PhoneNoInfo::new(i.to_string(), String::new(), String::new(), String::new(), String::new()));
+        let phones = ["13900001234", "1300005", "123", "1300001", "13000101111", "1300000", "13o0000"];
+        let results = utils::merge_find_batch(&phones, keys.len(), |i| keys[i], build);
+
+        assert_eq!(results.len(), phones.len());
+        assert_eq!(results[0].as_ref().unwrap().province, "3");
+        assert_eq!(results[1].as_ref().unwrap().province, "1");
+        assert!(matches!(results[2].as_ref().unwrap_err().downcast_ref::<ErrorKind>(), Some(&ErrorKind::InvalidLength)));
+        assert!(matches!(results[3].as_ref().unwrap_err().downcast_ref::<ErrorKind>(), Some(&ErrorKind::NotFound)));
+        assert_eq!(results[4].as_ref().unwrap().province, "2");
+        assert_eq!(results[5].as_ref().unwrap().province, "0");
+        assert!(results[6].is_err());
     }
 
     #[test]
@@ -283,4 +745,103 @@ mod tests {
         let result = utils::four_u8_to_i32(&test_bytes);
         assert_eq!(result, 0x04030201);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_record_data_bounds() {
+        let records = "北京|北京|100000|010\0".as_bytes();
+        assert_eq!(utils::parse_record_data(records, 8).unwrap().zip_code, "100000");
+        for offset in [0, 7, 8 + records.len(), usize::MAX, -1i32 as usize] {
+            let err = utils::parse_record_data(records, offset).unwrap_err();
+            assert!(matches!(err.downcast_ref(), Some(ErrorKind::InvalidPhoneDatabase)), "{}", offset);
+        }
+        // 缺少结尾的\0
+        assert!(utils::parse_record_data(&records[..records.len() - 1], 8).is_err());
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(utils::crc32(b""), 0);
+        assert_eq!(utils::crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(utils::crc32(b"The quick brown fox jumps over the lazy dog"), 0x414F_A339);
+    }
+
+    #[test]
+    fn test_stable_hash() {
+        // 固定值，摘要变化会让已发出的ETag全部失效
+        assert_eq!(utils::stable_hash(&[b"abc"]), 0xad39_01ec_5edc_91ce);
+        assert_ne!(utils::stable_hash(&[b"ab", b"c"]), utils::stable_hash(&[b"a", b"bc"]));
+        assert_ne!(utils::stable_hash(&[]), utils::stable_hash(&[b""]));
+    }
+
+    #[test]
+    fn test_mask_phone_no() {
+        assert_eq!(utils::mask_phone_no("18086834111"), "1808683****");
+        assert_eq!(utils::mask_phone_no("1808683"), "1808683");
+        assert_eq!(utils::mask_phone_no("180"), "180");
+        assert_eq!(utils::mask_phone_no("180868341哈"), "1808683***");
+        assert_eq!(utils::phone_prefix("18086834111"), "1808683");
+        assert_eq!(utils::phone_prefix("180"), "180");
+        assert_eq!(utils::phone_prefix("123456é12"), "123456é");
+        assert_eq!(utils::mask_phone_no("123456é12"), "123456é**");
+        assert_eq!(utils::phone_len("123456é12"), 9);
+    }
+
+    #[test]
+    fn test_non_ascii_phone_no() {
+        // 第7个字节落在多字节字符中间时返回InvalidLength，不能panic
+        for no in ["123456é12", "123456é", "哈哈哈1"] {
+            let err = utils::parse_phone_prefix(no).unwrap_err();
+            assert!(matches!(err.downcast_ref(), Some(ErrorKind::InvalidLength)), "{}", no);
+            assert!(NoopLookup.validate_phone_no(no).is_err(), "{}", no);
+        }
+        assert_eq!(utils::parse_phone_prefix("1808683哈").unwrap(), 1808683);
+    }
+
+    #[test]
+    fn test_default_find_with_stats() {
+        let (result, stats) = NoopLookup.find_with_stats("18086834111");
+        assert!(result.is_err());
+        assert_eq!(stats.outcome, LookupOutcome::NotFound);
+        assert_eq!(stats.probes, 0);
+
+        let (result, stats) = NoopLookup.find_with_stats("180");
+        assert!(result.is_err());
+        assert_eq!(stats.outcome, LookupOutcome::InvalidLength);
+        assert_eq!(stats.search_time, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_instrumented_find_outcomes() {
+        let keys = [1300000, 1300005, 1300010];
+        let search = |prefix: i32, probes: &mut u32| {
+            *probes += 2;
+            keys.iter().position(|&k| k == prefix)
+        };
+        let decode = |i: usize| {
+            if i == 2 {
+                return Err(ErrorKind::InvalidPhoneDatabase.into());
+            }
+            Ok(PhoneNoInfo::new(i.to_string(), String::new(), String::new(), String::new(), String::new()))
+        };
+
+        let (result, stats) = utils::instrumented_find("13000051234", search, decode);
+        assert_eq!(result.unwrap().province, "1");
+        assert_eq!(stats.outcome, LookupOutcome::Found);
+        assert_eq!(stats.probes, 2);
+
+        let (_, stats) = utils::instrumented_find("1300001", search, decode);
+        assert_eq!(stats.outcome, LookupOutcome::NotFound);
+
+        let (_, stats) = utils::instrumented_find("1300010", search, decode);
+        assert_eq!(stats.outcome, LookupOutcome::InvalidPhoneDatabase);
+
+        let (_, stats) = utils::instrumented_find("13o0000", search, decode);
+        assert_eq!(stats.outcome, LookupOutcome::InvalidLength);
+        assert_eq!(stats.probes, 0);
+
+        let (result, stats) = utils::instrumented_find_filtered("1300005", Some(|_| false), search, decode);
+        assert!(matches!(result.unwrap_err().downcast_ref::<ErrorKind>(), Some(&ErrorKind::NotFound)));
+        assert_eq!(stats.outcome, LookupOutcome::FilteredOut);
+        assert_eq!(stats.probes, 0);
+    }
+}
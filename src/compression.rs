@@ -0,0 +1,108 @@
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use std::str::FromStr;
+use anyhow::{anyhow, Context, Result};
+
+/// gzip魔数
+pub const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// zstd帧魔数
+pub const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// 解压后的最大字节数，防止异常文件耗尽内存
+pub const MAX_DECOMPRESSED_SIZE: u64 = 256 << 20;
+
+/// 数据文件的压缩方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// 按魔数识别压缩方式，未识别时视为未压缩
+    pub fn detect(data: &[u8]) -> Compression {
+        if data.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else if data.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else {
+            Compression::None
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Compression> {
+        match s {
+            "none" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            _ => Err(anyhow!("unknown compression {:?}, expected none, gzip or zstd", s)),
+        }
+    }
+}
+
+/// 按魔数解压，未压缩的内容原样返回
+pub fn decompress(data: &[u8]) -> Result<Cow<'_, [u8]>> {
+    let compression = Compression::detect(data);
+    let reader: Box<dyn Read + '_> = match compression {
+        Compression::None => return Ok(Cow::Borrowed(data)),
+        Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(data)),
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(data)?),
+    };
+
+    let mut output = Vec::with_capacity(data.len() * 4);
+    reader
+        .take(MAX_DECOMPRESSED_SIZE + 1)
+        .read_to_end(&mut output)
+        .with_context(|| format!("failed to decompress {:?} data", compression))?;
+    if output.len() as u64 > MAX_DECOMPRESSED_SIZE {
+        return Err(anyhow!("decompressed data exceeds {} bytes", MAX_DECOMPRESSED_SIZE));
+    }
+    Ok(Cow::Owned(output))
+}
+
+/// 按指定方式压缩，`Compression::None`时原样复制
+pub fn compress(data: &[u8], compression: Compression) -> Result<Vec<u8>> {
+    match compression {
+        Compression::None => Ok(data.to_vec()),
+        Compression::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        }
+        Compression::Zstd => Ok(zstd::encode_all(data, 19)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let data = "北京|北京|100000|010\0".repeat(100).into_bytes();
+        assert!(matches!(decompress(&data).unwrap(), Cow::Borrowed(_)));
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let compressed = compress(&data, compression).unwrap();
+            assert_eq!(Compression::detect(&compressed), compression);
+            assert!(compressed.len() < data.len());
+            assert_eq!(decompress(&compressed).unwrap(), data.as_slice());
+        }
+        assert_eq!("zst".parse::<Compression>().unwrap(), Compression::Zstd);
+        assert!("lz4".parse::<Compression>().is_err());
+    }
+
+    #[test]
+    fn test_corrupt_input() {
+        let mut compressed = compress(b"phone data phone data", Compression::Zstd).unwrap();
+        compressed.truncate(compressed.len() - 3);
+        assert!(decompress(&compressed).is_err());
+        assert!(decompress(&[0x1f, 0x8b, 0, 0]).is_err());
+    }
+}
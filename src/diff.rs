@@ -0,0 +1,310 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use anyhow::{Context, Result};
+use serde_derive::Serialize;
+
+use crate::common::{utils, PhoneNoInfo};
+
+/// 号段变化类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// 新增号段
+    Added,
+    /// 删除号段
+    Removed,
+    /// 归属地变化（省份、城市、邮编或区号）
+    Moved,
+    /// 运营商变化
+    CarrierChanged,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Moved => "moved",
+            ChangeKind::CarrierChanged => "carrier_changed",
+        }
+    }
+}
+
+/// 一个号段的一项变化，同时改变归属地和运营商的号段会产生两项
+#[derive(Debug, Clone, Serialize)]
+pub struct PrefixChange {
+    pub kind: ChangeKind,
+    /// 号码前7位
+    pub prefix: i32,
+    /// 旧数据中的记录，新增时为空
+    pub old: Option<PhoneNoInfo>,
+    /// 新数据中的记录，删除时为空
+    pub new: Option<PhoneNoInfo>,
+}
+
+impl PrefixChange {
+    /// 用于统计归属的记录：优先取新记录，删除时取旧记录
+    fn current(&self) -> &PhoneNoInfo {
+        self.new.as_ref().or(self.old.as_ref()).expect("a change has at least one side")
+    }
+}
+
+/// 各变化类型的数量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ChangeCounts {
+    pub added: usize,
+    pub removed: usize,
+    pub moved: usize,
+    pub carrier_changed: usize,
+}
+
+impl ChangeCounts {
+    fn add(&mut self, kind: ChangeKind) {
+        match kind {
+            ChangeKind::Added => self.added += 1,
+            ChangeKind::Removed => self.removed += 1,
+            ChangeKind::Moved => self.moved += 1,
+            ChangeKind::CarrierChanged => self.carrier_changed += 1,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.added + self.removed + self.moved + self.carrier_changed
+    }
+}
+
+/// 变化汇总，按运营商和省份分组时取新记录（删除的号段取旧记录）
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DiffSummary {
+    pub total: ChangeCounts,
+    pub by_carrier: BTreeMap<String, ChangeCounts>,
+    pub by_province: BTreeMap<String, ChangeCounts>,
+}
+
+/// 两个版本数据的差异
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseDiff {
+    pub old_version: String,
+    pub new_version: String,
+    pub old_entries: usize,
+    pub new_entries: usize,
+    pub summary: DiffSummary,
+    /// 按号段排序的变化列表
+    pub changes: Vec<PrefixChange>,
+}
+
+/// 读取数据文件中全部号段及其记录，按号段排序
+pub fn load_entries<P: AsRef<Path>>(path: P) -> Result<(String, Vec<(i32, PhoneNoInfo)>)> {
+    let path = path.as_ref();
+    let (header, records, index) = utils::read_database(path)
+        .with_context(|| format!("failed to load {}", path.display()))?;
+    let mut entries = Vec::with_capacity(index.len());
+    for idx in &index {
+        let record = utils::parse_record_data(&records, idx.records_offset as usize)
+            .and_then(|record| utils::build_phone_info(&record, idx.card_type))
+            .with_context(|| format!("invalid record for prefix {} in {}", idx.phone_no_prefix, path.display()))?;
+        entries.push((idx.phone_no_prefix, record));
+    }
+    entries.sort_by_key(|(prefix, _)| *prefix);
+    Ok((header.version, entries))
+}
+
+/// 比较两个数据文件
+pub fn diff_databases<P: AsRef<Path>, Q: AsRef<Path>>(old: P, new: Q) -> Result<DatabaseDiff> {
+    let (old_version, old_entries) = load_entries(old)?;
+    let (new_version, new_entries) = load_entries(new)?;
+    Ok(DatabaseDiff::compare(old_version, &old_entries, new_version, &new_entries))
+}
+
+impl DatabaseDiff {
+    /// 比较两组按号段排序的记录
+    pub fn compare(
+        old_version: String,
+        old: &[(i32, PhoneNoInfo)],
+        new_version: String,
+        new: &[(i32, PhoneNoInfo)],
+    ) -> DatabaseDiff {
+        let mut changes = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < old.len() || j < new.len() {
+            let old_prefix = old.get(i).map(|(prefix, _)| *prefix);
+            let new_prefix = new.get(j).map(|(prefix, _)| *prefix);
+            match (old_prefix, new_prefix) {
+                (Some(o), Some(n)) if o == n => {
+                    let (before, after) = (&old[i].1, &new[j].1);
+                    let moved = before.province != after.province
+                        || before.city != after.city
+                        || before.zip_code != after.zip_code
+                        || before.area_code != after.area_code;
+                    for (changed, kind) in [(moved, ChangeKind::Moved), (before.card_type != after.card_type, ChangeKind::CarrierChanged)] {
+                        if changed {
+                            changes.push(PrefixChange { kind, prefix: o, old: Some(before.clone()), new: Some(after.clone()) });
+                        }
+                    }
+                    i += 1;
+                    j += 1;
+                }
+                (Some(o), n) if n.is_none_or(|n| o < n) => {
+                    changes.push(PrefixChange { kind: ChangeKind::Removed, prefix: o, old: Some(old[i].1.clone()), new: None });
+                    i += 1;
+                }
+                (_, Some(n)) => {
+                    changes.push(PrefixChange { kind: ChangeKind::Added, prefix: n, old: None, new: Some(new[j].1.clone()) });
+                    j += 1;
+                }
+                (_, None) => unreachable!("loop ends when both sides are exhausted"),
+            }
+        }
+
+        let mut summary = DiffSummary::default();
+        for change in &changes {
+            let current = change.current();
+            summary.total.add(change.kind);
+            summary.by_carrier.entry(current.card_type.clone()).or_default().add(change.kind);
+            summary.by_province.entry(current.province.clone()).or_default().add(change.kind);
+        }
+
+        DatabaseDiff {
+            old_version,
+            new_version,
+            old_entries: old.len(),
+            new_entries: new.len(),
+            summary,
+            changes,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// JSON格式，包含汇总和全部变化
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// CSV格式，每项变化一行
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from(
+            "change,prefix,old_province,old_city,old_zip_code,old_area_code,old_card_type,\
+             new_province,new_city,new_zip_code,new_area_code,new_card_type\n",
+        );
+        for change in &self.changes {
+            let mut fields = vec![change.kind.as_str().to_string(), change.prefix.to_string()];
+            for side in [&change.old, &change.new] {
+                match side {
+                    Some(info) => fields.extend(
+                        [&info.province, &info.city, &info.zip_code, &info.area_code, &info.card_type]
+                            .map(|field| csv_field(field)),
+                    ),
+                    None => fields.extend(std::iter::repeat_n(String::new(), 5)),
+                }
+            }
+            out.push_str(&fields.join(","));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// 便于人读的汇总表
+    pub fn summary_table(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{} ({} entries) -> {} ({} entries)", self.old_version, self.old_entries, self.new_version, self.new_entries);
+        let header = format!("{:<24}{:>10}{:>10}{:>10}{:>18}", "", "added", "removed", "moved", "carrier_changed");
+        let row = |out: &mut String, name: &str, counts: &ChangeCounts| {
+            let _ = writeln!(out, "{}{:>10}{:>10}{:>10}{:>18}", pad(name, 24), counts.added, counts.removed, counts.moved, counts.carrier_changed);
+        };
+
+        let _ = writeln!(out, "\n{}", header);
+        row(&mut out, "total", &self.summary.total);
+        for (title, groups) in [("carrier", &self.summary.by_carrier), ("province", &self.summary.by_province)] {
+            let _ = writeln!(out, "\nby {}", title);
+            for (name, counts) in groups {
+                row(&mut out, name, counts);
+            }
+        }
+        out
+    }
+}
+
+/// 按显示宽度补齐，中文字符按两列计算
+pub(crate) fn pad(s: &str, width: usize) -> String {
+    let display: usize = s.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum();
+    format!("{}{}", s, " ".repeat(width.saturating_sub(display)))
+}
+
+/// 含逗号、引号或换行的字段加引号
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(province: &str, city: &str, card_type: &str) -> PhoneNoInfo {
+        PhoneNoInfo::new(province.to_string(), city.to_string(), "000000".to_string(), "000".to_string(), card_type.to_string())
+    }
+
+    fn sample_diff() -> DatabaseDiff {
+        let old = vec![
+            (1300000, info("北京", "北京", "中国联通")),
+            (1300001, info("北京", "北京", "中国联通")),
+            (1380013, info("北京", "北京", "中国移动")),
+            (1808683, info("四川", "成都", "中国电信")),
+        ];
+        let new = vec![
+            (1300000, info("北京", "北京", "中国联通")),
+            (1380013, info("河北", "石家庄", "中国移动")),
+            (1808683, info("四川", "绵阳", "中国移动")),
+            (1990000, info("广东", "广州", "中国电信")),
+        ];
+        DatabaseDiff::compare("2409".to_string(), &old, "2410".to_string(), &new)
+    }
+
+    #[test]
+    fn test_compare_classifies_changes() {
+        let diff = sample_diff();
+        let changes: Vec<(ChangeKind, i32)> = diff.changes.iter().map(|c| (c.kind, c.prefix)).collect();
+        assert_eq!(changes, vec![
+            (ChangeKind::Removed, 1300001),
+            (ChangeKind::Moved, 1380013),
+            (ChangeKind::Moved, 1808683),
+            (ChangeKind::CarrierChanged, 1808683),
+            (ChangeKind::Added, 1990000),
+        ]);
+        assert_eq!(diff.summary.total, ChangeCounts { added: 1, removed: 1, moved: 2, carrier_changed: 1 });
+        assert_eq!(diff.summary.by_carrier["中国移动"], ChangeCounts { added: 0, removed: 0, moved: 2, carrier_changed: 1 });
+        assert_eq!(diff.summary.by_province["河北"].moved, 1);
+        assert_eq!(diff.summary.by_province["北京"].removed, 1);
+
+        let same = DatabaseDiff::compare("1".to_string(), &[(1300000, info("北京", "北京", "中国联通"))], "2".to_string(), &[(1300000, info("北京", "北京", "中国联通"))]);
+        assert!(same.is_empty());
+    }
+
+    #[test]
+    fn test_csv_and_json_output() {
+        let diff = sample_diff();
+        let csv = diff.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].starts_with("change,prefix,old_province"));
+        assert_eq!(lines[1], "removed,1300001,北京,北京,000000,000,中国联通,,,,,");
+        assert_eq!(lines[5], "added,1990000,,,,,,广东,广州,000000,000,中国电信");
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+
+        let json: serde_json::Value = serde_json::from_str(&diff.to_json()).unwrap();
+        assert_eq!(json["summary"]["total"]["moved"], 2);
+        assert_eq!(json["changes"][3]["kind"], "carrier_changed");
+        assert_eq!(json["changes"][0]["new"], serde_json::Value::Null);
+
+        let table = diff.summary_table();
+        assert!(table.starts_with("2409 (4 entries) -> 2410 (4 entries)"));
+        assert!(table.contains("by carrier"));
+    }
+}
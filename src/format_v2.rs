@@ -0,0 +1,524 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use anyhow::{anyhow, Context, Result};
+
+use crate::compression::{self, Compression};
+use crate::integrity::IntegrityPolicy;
+use crate::common::{utils, CardType, DatabaseHeader, ErrorKind, Index, PhoneLookup, PhoneNoInfo, PhoneStats, DEFAULT_DATA_PATH};
+
+/// v2文件的魔数
+pub const MAGIC: [u8; 4] = *b"PHD2";
+
+/// 当前的格式版本
+pub const FORMAT_VERSION: u16 = 2;
+
+/// 头部字节数
+const HEADER_SIZE: usize = 32;
+
+/// 记录表每项字节数：省份、城市、邮编、区号的字符串ID各2字节，卡类型1字节，3字节保留
+const RECORD_SIZE: usize = 12;
+
+/// v2文件头部
+///
+/// ```text
+/// | 4 bytes | 魔数 PHD2
+/// | 2 bytes | 格式版本
+/// | 2 bytes | 保留
+/// | 4 bytes | 数据版本号（如2410）
+/// | 4 bytes | 头部之后全部内容的CRC-32
+/// | 4 bytes | 字符串数
+/// | 4 bytes | 记录数
+/// | 4 bytes | 号段数
+/// | 4 bytes | 字符串数据字节数
+/// ```
+///
+/// 头部之后依次为字符串结束偏移表（(字符串数+1)×u32，首项为0）、字符串数据（补齐到4字节）、
+/// 记录表（记录数×12字节）、号段数组（号段数×u32，升序）、记录ID数组（号段数×u16），文件末尾补齐到4字节。
+/// 所有整数均为小端序。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderV2 {
+    pub format_version: u16,
+    pub version: String,
+    pub checksum: u32,
+    pub string_count: u32,
+    pub record_count: u32,
+    pub key_count: u32,
+    pub string_bytes: u32,
+}
+
+/// 各区在文件中的位置，均为字节偏移
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    string_offsets: usize,
+    string_data: usize,
+    records: usize,
+    keys: usize,
+    record_ids: usize,
+    total: usize,
+}
+
+impl HeaderV2 {
+    fn parse(bytes: &[u8]) -> Result<HeaderV2> {
+        if bytes.len() < HEADER_SIZE || bytes[..4] != MAGIC {
+            return Err(ErrorKind::InvalidPhoneDatabase.into());
+        }
+        let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let format_version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if format_version != FORMAT_VERSION {
+            return Err(anyhow!("unsupported phone.dat format version {}", format_version));
+        }
+        Ok(HeaderV2 {
+            format_version,
+            version: String::from_utf8(bytes[8..12].to_vec())?,
+            checksum: u32_at(12),
+            string_count: u32_at(16),
+            record_count: u32_at(20),
+            key_count: u32_at(24),
+            string_bytes: u32_at(28),
+        })
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&MAGIC);
+        out.extend_from_slice(&self.format_version.to_le_bytes());
+        out.extend_from_slice(&[0, 0]);
+        out.extend_from_slice(self.version.as_bytes());
+        for value in [self.checksum, self.string_count, self.record_count, self.key_count, self.string_bytes] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn layout(&self) -> Layout {
+        let string_offsets = HEADER_SIZE;
+        let string_data = string_offsets + (self.string_count as usize + 1) * 4;
+        let records = string_data + (self.string_bytes as usize).next_multiple_of(4);
+        let keys = records + self.record_count as usize * RECORD_SIZE;
+        let record_ids = keys + self.key_count as usize * 4;
+        let total = (record_ids + self.key_count as usize * 2).next_multiple_of(4);
+        Layout { string_offsets, string_data, records, keys, record_ids, total }
+    }
+}
+
+/// 直接在v2文件内容上查找的数据
+///
+/// 整个文件读入按4字节对齐的缓冲区，号段数组直接作为`&[u32]`二分查找，加载时只做校验不做解析
+#[derive(Debug)]
+pub struct PhoneDataV2 {
+    header: HeaderV2,
+    layout: Layout,
+    /// 文件内容，按u32分配以保证对齐
+    words: Vec<u32>,
+}
+
+impl PhoneDataV2 {
+    pub fn new() -> Result<PhoneDataV2> {
+        Self::from_path(DEFAULT_DATA_PATH)
+    }
+
+    /// 从指定路径加载，压缩文件先解压，v1文件会先在内存中转换为v2
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<PhoneDataV2> {
+        let path = path.as_ref();
+        let mut file = std::fs::File::open(path)?;
+        let mut magic = [0u8; 4];
+        if file.read_exact(&mut magic).is_err() || magic != MAGIC {
+            let data = std::fs::read(path)?;
+            IntegrityPolicy::default().verify(path, &data)?;
+            let data = compression::decompress(&data)?;
+            if data.starts_with(&MAGIC) {
+                return Self::from_bytes(&data);
+            }
+            let (header, records, index) = utils::parse_database(&data)?;
+            return Self::from_bytes(&encode(&header.version, &records, &index)?);
+        }
+
+        let len = file.metadata()?.len() as usize;
+        if !len.is_multiple_of(4) {
+            return Err(ErrorKind::InvalidPhoneDatabase.into());
+        }
+        let mut words = vec![0u32; len / 4];
+        let bytes = bytes_of_mut(&mut words);
+        bytes[..4].copy_from_slice(&magic);
+        file.read_exact(&mut bytes[4..])?;
+        IntegrityPolicy::default().verify(path, bytes_of(&words))?;
+        Self::from_words(words)
+    }
+
+    /// 从内存中的v2文件内容加载
+    pub fn from_bytes(data: &[u8]) -> Result<PhoneDataV2> {
+        if !data.len().is_multiple_of(4) {
+            return Err(ErrorKind::InvalidPhoneDatabase.into());
+        }
+        let mut words = vec![0u32; data.len() / 4];
+        bytes_of_mut(&mut words).copy_from_slice(data);
+        Self::from_words(words)
+    }
+
+    /// 校验头部、校验值和各区内容，之后的查找不再检查边界以外的错误
+    fn from_words(words: Vec<u32>) -> Result<PhoneDataV2> {
+        let bytes = bytes_of(&words);
+        let header = HeaderV2::parse(bytes)?;
+        let layout = header.layout();
+        if layout.total != bytes.len() {
+            return Err(anyhow!("phone.dat v2 is {} bytes, header describes {}", bytes.len(), layout.total));
+        }
+        let checksum = utils::crc32(&bytes[HEADER_SIZE..]);
+        if checksum != header.checksum {
+            return Err(anyhow!("phone.dat v2 checksum mismatch: header {:08x}, content {:08x}", header.checksum, checksum));
+        }
+
+        let data = PhoneDataV2 { header, layout, words };
+        let mut previous = 0u32;
+        for i in 0..=data.header.string_count as usize {
+            let offset = data.string_offset(i);
+            if offset < previous || offset > data.header.string_bytes || (i == 0 && offset != 0) {
+                return Err(ErrorKind::InvalidPhoneDatabase.into());
+            }
+            previous = offset;
+        }
+        for i in 0..data.header.string_count as usize {
+            std::str::from_utf8(data.string_bytes(i)).map_err(|_| ErrorKind::InvalidPhoneDatabase)?;
+        }
+        for i in 0..data.header.record_count as usize {
+            let (strings, card_type) = data.record(i);
+            if strings.iter().any(|&id| id as u32 >= data.header.string_count) {
+                return Err(ErrorKind::InvalidPhoneDatabase.into());
+            }
+            CardType::from_u8(card_type)?;
+        }
+        let keys = data.keys();
+        if keys.windows(2).any(|pair| u32::from_le(pair[0]) >= u32::from_le(pair[1])) {
+            return Err(anyhow!("phone.dat v2 keys are not strictly ascending"));
+        }
+        if (0..keys.len()).any(|i| data.record_id(i) as u32 >= data.header.record_count) {
+            return Err(ErrorKind::InvalidPhoneDatabase.into());
+        }
+        Ok(data)
+    }
+
+    pub fn header(&self) -> &HeaderV2 {
+        &self.header
+    }
+
+    fn bytes(&self) -> &[u8] {
+        bytes_of(&self.words)
+    }
+
+    /// 按文件中的字节序存放的号段数组
+    fn keys(&self) -> &[u32] {
+        let start = self.layout.keys / 4;
+        &self.words[start..start + self.header.key_count as usize]
+    }
+
+    fn record_id(&self, i: usize) -> u16 {
+        let offset = self.layout.record_ids + i * 2;
+        u16::from_le_bytes([self.bytes()[offset], self.bytes()[offset + 1]])
+    }
+
+    fn string_offset(&self, i: usize) -> u32 {
+        u32::from_le(self.words[self.layout.string_offsets / 4 + i])
+    }
+
+    fn string_bytes(&self, i: usize) -> &[u8] {
+        let start = self.layout.string_data + self.string_offset(i) as usize;
+        let end = self.layout.string_data + self.string_offset(i + 1) as usize;
+        &self.bytes()[start..end]
+    }
+
+    fn string(&self, i: usize) -> Result<&str> {
+        Ok(std::str::from_utf8(self.string_bytes(i)).map_err(|_| ErrorKind::InvalidPhoneDatabase)?)
+    }
+
+    /// 记录的省份、城市、邮编、区号字符串ID和卡类型
+    fn record(&self, i: usize) -> ([u16; 4], u8) {
+        let item = &self.bytes()[self.layout.records + i * RECORD_SIZE..][..RECORD_SIZE];
+        let id = |field: usize| u16::from_le_bytes([item[field * 2], item[field * 2 + 1]]);
+        ([id(0), id(1), id(2), id(3)], item[8])
+    }
+
+    fn phone_info(&self, record_id: usize) -> Result<PhoneNoInfo> {
+        let ([province, city, zip_code, area_code], card_type) = self.record(record_id);
+        Ok(PhoneNoInfo::new(
+            self.string(province as usize)?.to_string(),
+            self.string(city as usize)?.to_string(),
+            self.string(zip_code as usize)?.to_string(),
+            self.string(area_code as usize)?.to_string(),
+            CardType::from_u8(card_type)?.get_description(),
+        ))
+    }
+
+    /// 还原为v1的头部、记录区和索引区，供其它查找实现加载v2文件
+    pub fn to_index(&self) -> (DatabaseHeader, Vec<u8>, Vec<Index>) {
+        let mut records = Vec::new();
+        let mut offsets = Vec::with_capacity(self.header.record_count as usize);
+        for i in 0..self.header.record_count as usize {
+            offsets.push(8 + records.len() as i32);
+            let (strings, _) = self.record(i);
+            for (n, id) in strings.iter().enumerate() {
+                if n > 0 {
+                    records.push(b'|');
+                }
+                records.extend_from_slice(self.string_bytes(*id as usize));
+            }
+            records.push(0);
+        }
+        let index = self
+            .keys()
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let record_id = self.record_id(i) as usize;
+                Index::new(u32::from_le(*key) as i32, offsets[record_id], self.record(record_id).1)
+            })
+            .collect();
+        let header = DatabaseHeader::new(self.header.version.clone(), 8 + records.len() as u64);
+        (header, records, index)
+    }
+}
+
+impl PhoneLookup for PhoneDataV2 {
+    fn find(&self, no: &str) -> Result<PhoneNoInfo> {
+        let prefix = utils::parse_phone_prefix(no)? as u32;
+        let pos = self
+            .keys()
+            .binary_search_by(|key| u32::from_le(*key).cmp(&prefix))
+            .map_err(|_| ErrorKind::NotFound)?;
+        self.phone_info(self.record_id(pos) as usize)
+    }
+}
+
+impl PhoneStats for PhoneDataV2 {
+    fn total_entries(&self) -> usize {
+        self.header.key_count as usize
+    }
+
+    fn version(&self) -> &str {
+        &self.header.version
+    }
+
+    fn memory_usage_bytes(&self) -> usize {
+        std::mem::size_of::<Self>() + self.words.capacity() * 4
+    }
+}
+
+fn bytes_of(words: &[u32]) -> &[u8] {
+    // SAFETY: u8的对齐要求不高于u32，字节视图与words的内存完全重合
+    unsafe { std::slice::from_raw_parts(words.as_ptr().cast::<u8>(), words.len() * 4) }
+}
+
+fn bytes_of_mut(words: &mut [u32]) -> &mut [u8] {
+    // SAFETY: 同上，且u32没有无效的位模式，任意写入的字节都是合法值
+    unsafe { std::slice::from_raw_parts_mut(words.as_mut_ptr().cast::<u8>(), words.len() * 4) }
+}
+
+/// 把v1的头部版本号、记录区和索引区编码为v2文件内容
+///
+/// 相同的字符串和相同的（归属地，卡类型）组合只存一份，字符串和记录均不能超过65535个
+pub fn encode(version: &str, records: &[u8], index: &[Index]) -> Result<Vec<u8>> {
+    if version.len() != 4 {
+        return Err(anyhow!("version {:?} must be 4 bytes", version));
+    }
+
+    let mut string_ids: HashMap<String, u16> = HashMap::new();
+    let mut record_table: Vec<([u16; 4], u8)> = Vec::new();
+    let mut record_ids: HashMap<([u16; 4], u8), u16> = HashMap::new();
+    let mut parsed = HashMap::new();
+    let mut entries: Vec<(u32, u16)> = Vec::with_capacity(index.len());
+
+    for item in index {
+        let prefix = u32::try_from(item.phone_no_prefix).map_err(|_| anyhow!("invalid prefix {}", item.phone_no_prefix))?;
+        CardType::from_u8(item.card_type).with_context(|| format!("invalid card type for prefix {}", prefix))?;
+        let fields = match parsed.entry(item.records_offset) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let record = utils::parse_record_data(records, item.records_offset as usize)
+                    .with_context(|| format!("invalid record for prefix {}", prefix))?;
+                entry.insert([record.province, record.city, record.zip_code, record.area_code])
+            }
+        };
+        let mut ids = [0u16; 4];
+        for (id, field) in ids.iter_mut().zip(fields.iter()) {
+            *id = match string_ids.get(field) {
+                Some(&id) => id,
+                None => {
+                    let id = u16::try_from(string_ids.len()).map_err(|_| anyhow!("more than 65535 distinct strings"))?;
+                    string_ids.insert(field.clone(), id);
+                    id
+                }
+            };
+        }
+        let key = (ids, item.card_type);
+        let record_id = match record_ids.get(&key) {
+            Some(&id) => id,
+            None => {
+                let id = u16::try_from(record_table.len()).map_err(|_| anyhow!("more than 65535 distinct records"))?;
+                record_ids.insert(key, id);
+                record_table.push(key);
+                id
+            }
+        };
+        entries.push((prefix, record_id));
+    }
+    entries.sort_unstable_by_key(|(prefix, _)| *prefix);
+    if let Some(pair) = entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        return Err(anyhow!("duplicate prefix {}", pair[0].0));
+    }
+
+    let mut strings: Vec<(&str, u16)> = string_ids.iter().map(|(s, id)| (s.as_str(), *id)).collect();
+    strings.sort_unstable_by_key(|(_, id)| *id);
+    let strings: Vec<&str> = strings.into_iter().map(|(s, _)| s).collect();
+
+    let mut body = Vec::new();
+    let mut offset = 0u32;
+    body.extend_from_slice(&offset.to_le_bytes());
+    for s in &strings {
+        offset += s.len() as u32;
+        body.extend_from_slice(&offset.to_le_bytes());
+    }
+    for s in &strings {
+        body.extend_from_slice(s.as_bytes());
+    }
+    pad4(&mut body);
+    for (ids, card_type) in &record_table {
+        for id in ids {
+            body.extend_from_slice(&id.to_le_bytes());
+        }
+        body.extend_from_slice(&[*card_type, 0, 0, 0]);
+    }
+    for (prefix, _) in &entries {
+        body.extend_from_slice(&prefix.to_le_bytes());
+    }
+    for (_, record_id) in &entries {
+        body.extend_from_slice(&record_id.to_le_bytes());
+    }
+    pad4(&mut body);
+
+    let header = HeaderV2 {
+        format_version: FORMAT_VERSION,
+        version: version.to_string(),
+        checksum: utils::crc32(&body),
+        string_count: strings.len() as u32,
+        record_count: record_table.len() as u32,
+        key_count: entries.len() as u32,
+        string_bytes: offset,
+    };
+    let mut out = Vec::with_capacity(HEADER_SIZE + body.len());
+    header.write(&mut out);
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+fn pad4(out: &mut Vec<u8>) {
+    out.resize(out.len().next_multiple_of(4), 0);
+}
+
+/// 转换结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertReport {
+    pub version: String,
+    pub entries: usize,
+    pub records: usize,
+    pub strings: usize,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+}
+
+/// 把v1文件转换为v2文件，写入前会重新加载校验
+pub fn convert_v1_to_v2<P: AsRef<Path>, Q: AsRef<Path>>(input: P, output: Q) -> Result<ConvertReport> {
+    convert_to_v2(input, output, Compression::None)
+}
+
+/// 把数据文件转换为v2文件并按`compression`压缩，输入可以是压缩过的v1或v2文件
+pub fn convert_to_v2<P: AsRef<Path>, Q: AsRef<Path>>(input: P, output: Q, compression: Compression) -> Result<ConvertReport> {
+    let (input, output) = (input.as_ref(), output.as_ref());
+    let data = std::fs::read(input).with_context(|| format!("failed to read {}", input.display()))?;
+    let (header, records, index) = utils::parse_database(&data).with_context(|| format!("failed to load {}", input.display()))?;
+    let encoded = encode(&header.version, &records, &index)?;
+    let check = PhoneDataV2::from_bytes(&encoded)?;
+    let encoded = compression::compress(&encoded, compression)?;
+    std::fs::write(output, &encoded).with_context(|| format!("failed to write {}", output.display()))?;
+    Ok(ConvertReport {
+        version: header.version,
+        entries: index.len(),
+        records: check.header.record_count as usize,
+        strings: check.header.string_count as usize,
+        input_bytes: data.len() as u64,
+        output_bytes: encoded.len() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PhoneData;
+
+    fn sample() -> (Vec<u8>, Vec<Index>) {
+        let records = "北京|北京|100000|010\0四川|成都|610000|028\0".as_bytes().to_vec();
+        let index = vec![Index::new(1300000, 8, 2), Index::new(1380013, 8, 1), Index::new(1808683, 33, 3), Index::new(1300001, 8, 2)];
+        (records, index)
+    }
+
+    #[test]
+    fn test_encode_and_lookup() {
+        let (records, index) = sample();
+        let encoded = encode("2410", &records, &index).unwrap();
+        assert_eq!(encoded.len() % 4, 0);
+        let data = PhoneDataV2::from_bytes(&encoded).unwrap();
+        assert_eq!((data.header().string_count, data.header().record_count, data.header().key_count), (7, 3, 4));
+        assert_eq!(data.version(), "2410");
+
+        let info = data.find("18086834111").unwrap();
+        assert_eq!((info.province.as_str(), info.city.as_str(), info.card_type.as_str()), ("四川", "成都", "中国电信"));
+        assert_eq!(data.find("13800138000").unwrap().card_type, "中国移动");
+        assert!(data.find("15900000000").is_err());
+        assert!(data.find("123").is_err());
+
+        // 还原为v1后其它实现的查询结果一致
+        let (header, records, index) = data.to_index();
+        let v1 = PhoneData::from_index(header.version, records, index);
+        for no in ["1300000", "1300001", "1380013", "1808683"] {
+            assert_eq!(format!("{:?}", v1.find(no).unwrap()), format!("{:?}", data.find(no).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_rejects_corrupted_files() {
+        let (records, index) = sample();
+        let encoded = encode("2410", &records, &index).unwrap();
+
+        let mut flipped = encoded.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        assert!(format!("{:#}", PhoneDataV2::from_bytes(&flipped).unwrap_err()).contains("checksum"));
+        assert!(PhoneDataV2::from_bytes(&encoded[..encoded.len() - 4]).is_err());
+        assert!(PhoneDataV2::from_bytes(&encoded[..16]).is_err());
+        let mut future = encoded.clone();
+        future[4] = 3;
+        assert!(PhoneDataV2::from_bytes(&future).is_err());
+
+        let duplicate = vec![Index::new(1300000, 8, 2), Index::new(1300000, 8, 1)];
+        assert!(encode("2410", &records, &duplicate).is_err());
+        assert!(encode("2410", &records, &[Index::new(1300000, 8, 9)]).is_err());
+        assert!(encode("24100", &records, &index).is_err());
+    }
+
+    #[test]
+    fn test_convert_real_database() {
+        let output = std::env::temp_dir().join(format!("phone_data_v2_{}.dat", std::process::id()));
+        let report = convert_v1_to_v2(DEFAULT_DATA_PATH, &output).unwrap();
+        assert!(report.output_bytes < report.input_bytes, "{:?}", report);
+
+        let v1 = PhoneData::new().unwrap();
+        let v2 = PhoneDataV2::from_path(&output).unwrap();
+        assert_eq!(v2.total_entries(), v1.total_entries());
+        assert_eq!(v2.version(), v1.version());
+        for no in ["18086834111", "13800138000", "1300000", "19900000000"] {
+            assert_eq!(format!("{:?}", v1.find(no).ok()), format!("{:?}", v2.find(no).ok()), "{}", no);
+        }
+        // 其它实现可以直接加载v2文件
+        assert_eq!(PhoneData::from_path(&output).unwrap().find("18086834111").unwrap().city, v1.find("18086834111").unwrap().city);
+        // v2实现也可以直接加载v1文件
+        assert_eq!(PhoneDataV2::new().unwrap().total_entries(), v1.total_entries());
+        std::fs::remove_file(output).unwrap();
+    }
+}
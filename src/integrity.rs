@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Context, Result};
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde_derive::Serialize;
+use sha2::{Digest, Sha256};
+
+/// 数据文件通过的校验，按强度排序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verification {
+    /// 没有校验文件，未经校验
+    None,
+    /// 与`.sha256`文件中的SHA-256一致
+    Checksum,
+    /// `.sig`文件中的Ed25519签名有效
+    Signature,
+}
+
+/// 加载数据文件时的校验策略
+///
+/// 存在的校验文件总会被校验，不一致时拒绝加载；严格模式下还要求校验文件存在：
+/// 配置了公钥时要求签名，否则要求校验和
+#[derive(Debug, Clone, Default)]
+pub struct IntegrityPolicy {
+    /// 签名公钥，未配置时忽略`.sig`文件
+    pub public_key: Option<VerifyingKey>,
+    /// 是否拒绝未经校验的数据
+    pub strict: bool,
+}
+
+impl IntegrityPolicy {
+    /// 校验数据文件内容，返回通过的最强校验
+    pub fn verify(&self, path: &Path, data: &[u8]) -> Result<Verification> {
+        let mut verification = Verification::None;
+        if let Some(expected) = read_checksum(path)? {
+            let actual = sha256_hex(data);
+            if actual != expected {
+                return Err(anyhow!("checksum mismatch for {}: expected {}, got {}", path.display(), expected, actual));
+            }
+            verification = Verification::Checksum;
+        }
+        if let Some(public_key) = &self.public_key
+            && let Some(signature) = read_signature(path)?
+        {
+            public_key
+                .verify_strict(data, &signature)
+                .map_err(|_| anyhow!("invalid signature for {}", path.display()))?;
+            verification = Verification::Signature;
+        }
+
+        let required = if self.public_key.is_some() { Verification::Signature } else { Verification::Checksum };
+        if self.strict && verification < required {
+            return Err(anyhow!(
+                "{} is not verified: strict mode requires {}",
+                path.display(),
+                if required == Verification::Signature { "a valid signature" } else { "a checksum file" }
+            ));
+        }
+        Ok(verification)
+    }
+}
+
+/// 校验和文件路径：`<数据文件>.sha256`
+pub fn checksum_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".sha256")
+}
+
+/// 签名文件路径：`<数据文件>.sig`
+pub fn signature_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".sig")
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// SHA-256的小写十六进制形式
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 解析十六进制的32字节Ed25519公钥
+pub fn parse_public_key(hex: &str) -> Result<VerifyingKey> {
+    let bytes: [u8; 32] = decode_hex(hex.trim())
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("public key must be 64 hex characters"))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| anyhow!("invalid Ed25519 public key"))
+}
+
+/// 读取校验和文件，兼容`sha256sum`输出的`<hash>  <文件名>`格式；文件不存在时为空
+fn read_checksum(path: &Path) -> Result<Option<String>> {
+    let checksum_path = checksum_path(path);
+    let content = match std::fs::read_to_string(&checksum_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", checksum_path.display())),
+    };
+    let checksum = content.split_whitespace().next().unwrap_or_default().to_ascii_lowercase();
+    if checksum.len() != 64 || decode_hex(&checksum).is_none() {
+        return Err(anyhow!("{} does not contain a SHA-256 checksum", checksum_path.display()));
+    }
+    Ok(Some(checksum))
+}
+
+/// 读取签名文件，内容为64字节原始签名或128位十六进制；文件不存在时为空
+fn read_signature(path: &Path) -> Result<Option<Signature>> {
+    let signature_path = signature_path(path);
+    let content = match std::fs::read(&signature_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", signature_path.display())),
+    };
+    let bytes = match content.len() {
+        64 => Some(content),
+        _ => std::str::from_utf8(&content).ok().and_then(|hex| decode_hex(hex.trim())),
+    };
+    let bytes: [u8; 64] = bytes
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("{} does not contain an Ed25519 signature", signature_path.display()))?;
+    Ok(Some(Signature::from_bytes(&bytes)))
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const DATA: &[u8] = b"phone data";
+
+    fn temp_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("phone_data_integrity_{}_{}.dat", name, std::process::id()));
+        std::fs::write(&path, DATA).unwrap();
+        let _ = std::fs::remove_file(checksum_path(&path));
+        let _ = std::fs::remove_file(signature_path(&path));
+        path
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+
+        let path = temp_file("checksum");
+        let lenient = IntegrityPolicy::default();
+        let strict = IntegrityPolicy { strict: true, ..Default::default() };
+        assert_eq!(lenient.verify(&path, DATA).unwrap(), Verification::None);
+        assert!(strict.verify(&path, DATA).is_err());
+
+        std::fs::write(checksum_path(&path), format!("{}  phone.dat\n", sha256_hex(DATA).to_uppercase())).unwrap();
+        assert_eq!(strict.verify(&path, DATA).unwrap(), Verification::Checksum);
+        assert!(lenient.verify(&path, b"tampered").is_err());
+
+        std::fs::write(checksum_path(&path), "not a checksum").unwrap();
+        assert!(lenient.verify(&path, DATA).is_err());
+    }
+
+    #[test]
+    fn test_signature() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let hex: String = signing_key.verifying_key().as_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        let policy = IntegrityPolicy { public_key: Some(parse_public_key(&hex).unwrap()), strict: true };
+        assert!(parse_public_key("abcd").is_err());
+
+        let path = temp_file("signature");
+        std::fs::write(checksum_path(&path), sha256_hex(DATA)).unwrap();
+        // 配置公钥后严格模式要求签名，只有校验和不够
+        assert!(policy.verify(&path, DATA).is_err());
+
+        std::fs::write(signature_path(&path), signing_key.sign(DATA).to_bytes()).unwrap();
+        assert_eq!(policy.verify(&path, DATA).unwrap(), Verification::Signature);
+
+        let other_key = SigningKey::from_bytes(&[8; 32]);
+        let forged: String = other_key.sign(DATA).to_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        std::fs::write(signature_path(&path), forged).unwrap();
+        assert!(policy.verify(&path, DATA).is_err());
+        // 未配置公钥时忽略签名文件
+        assert_eq!(IntegrityPolicy::default().verify(&path, DATA).unwrap(), Verification::Checksum);
+    }
+}
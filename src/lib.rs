@@ -8,9 +8,53 @@ pub mod binary_search;
 pub mod phone_hash;
 pub mod phone_simd;
 pub mod phone_bloom;
+pub mod phone_eytzinger;
+
+// 多线程批量查找模块
+pub mod parallel;
+
+// v2数据文件格式
+pub mod format_v2;
+
+// 数据文件压缩
+pub mod compression;
+
+// 数据文件完整性校验
+pub mod integrity;
+
+// 多版本数据
+pub mod versioned;
+
+// 数据版本比较
+pub mod diff;
+
+// 数据内容统计
+pub mod stats;
+
+// 人工覆盖数据
+pub mod overlay;
+
+// 携号转网
+pub mod portability;
+
+// HTTP服务指标模块
+pub mod metrics;
+
+// API Key认证和限流
+pub mod access;
+
+// 结构化访问日志
+pub mod access_log;
+
+// OpenAPI文档
+pub mod openapi;
+
+// HTTP服务路由和状态
+pub mod server;
 
 // 重新导出公共类型
-pub use common::{PhoneNoInfo, ErrorKind, CardType, PhoneLookup, PhoneStats};
+pub use common::{PhoneNoInfo, ErrorKind, CardType, PhoneLookup, PhoneStats, LookupStats, LookupOutcome};
+pub use parallel::{ParallelConfig, ParallelLookup};
 
 // 重新导出SIMD优化算法作为默认实现
 pub use phone_simd::PhoneDataSimd as PhoneData;
@@ -18,4 +62,6 @@ pub use phone_simd::PhoneDataSimd as PhoneData;
 // 便于从crate根导出其它实现类型
 pub use phone_hash::PhoneDataHash;
 pub use phone_bloom::PhoneDataBloom;
-pub use phone_simd::PhoneDataSimd;
\ No newline at end of file
+pub use phone_simd::PhoneDataSimd;
+pub use phone_eytzinger::PhoneDataEytzinger;
+pub use format_v2::PhoneDataV2;
\ No newline at end of file
@@ -1,99 +1,209 @@
-use actix_web::{get, post, web, App, HttpResponse, HttpServer, Responder};
-use lazy_static::lazy_static;
-use serde_derive::{Serialize, Deserialize};
+use anyhow::{anyhow, Result};
+use phone_data::common::DEFAULT_DATA_PATH;
+use phone_data::diff::diff_databases;
+use phone_data::format_v2::convert_to_v2;
+use phone_data::phone_bloom::{FilterType, PhoneDataBloom};
+use phone_data::stats::dataset_stats;
+use phone_data::server::{serve, AppState, Database, ServerConfig};
+
+const USAGE: &str = "\
+usage:
+  phone_data [serve]                                        启动HTTP服务，配置见环境变量
+  phone_data diff <old.dat> <new.dat> [--format json|csv|summary]
+                                                            比较两个版本的数据文件
+  phone_data convert <input.dat> <output.dat> [--compress none|gzip|zstd]
+                                                            把数据文件转换为v2格式，可选压缩输出
+  phone_data stats [data.dat] [--format json|table]          统计数据文件的运营商、省份、号段覆盖率和空缺
+  phone_data filter [data.dat] [--type bloom|xor] [--rate <误报率>]
+                                                            按参数构建成员过滤器并实测误报率
+";
 
-use phone_data::{PhoneData};
-
-struct AppState {
-    pub phone_data: PhoneData,
-}
-
-lazy_static! {
-    static ref STATE: AppState  = AppState {
-        phone_data: PhoneData::new().unwrap(),
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        None | Some("serve") => return run_server().await,
+        Some("diff") => run_diff(&args[1..]),
+        Some("convert") => run_convert(&args[1..]),
+        Some("stats") => run_stats(&args[1..]),
+        Some("filter") => run_filter(&args[1..]),
+        Some("help" | "-h" | "--help") => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        Some(other) => Err(anyhow!("unknown command {:?}\n\n{}", other, USAGE)),
     };
+    if let Err(e) = result {
+        eprintln!("{:#}", e);
+        std::process::exit(2);
+    }
+    Ok(())
 }
 
+async fn run_server() -> std::io::Result<()> {
+    let config = match ServerConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("invalid configuration: {:#}", e);
+            std::process::exit(1);
+        }
+    };
 
-#[derive(Debug, Serialize)]
-struct Message<T>
-where
-    T: serde::Serialize,
-{
-    code: i32,
-    data: Option<T>,
-    success: bool,
-    result: String,
-}
+    // 启动时加载数据并自检，失败时直接退出，不会带着空数据接受请求
+    let db = match Database::load(&config) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("failed to start: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    for info in db.versions.versions() {
+        let latest = if info.latest { ", latest" } else { "" };
+        println!("loaded version {} ({} entries{})", info.version, info.total_entries, latest);
+    }
+    println!(
+        "loaded {} database file(s) from {} in {:?}, verification: {:?}",
+        1 + config.archive_paths.len(),
+        config.data_path.display(),
+        db.load_duration,
+        db.verification,
+    );
+
+    let access = match config.access_control() {
+        Ok(access) => access,
+        Err(e) => {
+            eprintln!("failed to start: {:#}", e);
+            std::process::exit(1);
+        }
+    };
 
-impl<T: serde::Serialize> Message<T> {
-    pub fn ok(data: T) -> Self {
-        Message { code: 0, result: "ok".to_owned(), data: Some(data), success: true }
+    let overlay = match config.overlay() {
+        Ok(overlay) => overlay,
+        Err(e) => {
+            eprintln!("failed to start: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(path) = &config.overlay_path {
+        println!("loaded {} override(s) from {}", overlay.len(), path.display());
     }
-    pub fn err(message: &str) -> Self {
-        Message { code: -1, result: message.to_owned(), data: None, success: false }
+    let portability = match config.portability() {
+        Ok(portability) => portability,
+        Err(e) => {
+            eprintln!("failed to start: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(path) = &config.portability_path {
+        println!("loaded {} ported number(s) from {}", portability.len(), path.display());
     }
-}
 
+    let state = AppState::new(db)
+        .with_access_control(access)
+        .with_access_log(config.access_logger())
+        .with_overlay(overlay)
+        .with_portability(portability);
+    serve(config, state).await
+}
 
-async fn hello() -> impl Responder {
-    HttpResponse::Ok().body("Hello world!")
+/// 拆分位置参数和`--<option>`选项
+fn parse_option(args: &[String], option: &str, default: &str) -> Result<(Vec<String>, String)> {
+    let (positional, [value]) = parse_options(args, [(option, default)])?;
+    Ok((positional, value))
 }
 
-#[derive(Debug, Deserialize)]
-struct IParams {
-    phone: String,
+/// 拆分位置参数和多个`--<option>`选项，未出现的选项取默认值
+fn parse_options<const N: usize>(args: &[String], options: [(&str, &str); N]) -> Result<(Vec<String>, [String; N])> {
+    let flags = options.map(|(option, _)| format!("--{}", option));
+    let mut values = options.map(|(_, default)| default.to_string());
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    'args: while let Some(arg) = iter.next() {
+        for (flag, value) in flags.iter().zip(values.iter_mut()) {
+            if let Some(inline) = arg.strip_prefix(flag.as_str()).and_then(|rest| rest.strip_prefix('=')) {
+                *value = inline.to_string();
+                continue 'args;
+            } else if arg == flag {
+                *value = iter.next().ok_or_else(|| anyhow!("{} requires a value", flag))?.clone();
+                continue 'args;
+            }
+        }
+        if arg.starts_with('-') {
+            return Err(anyhow!("unknown option {}\n\n{}", arg, USAGE));
+        }
+        positional.push(arg.clone());
+    }
+    Ok((positional, values))
 }
 
-#[get("/query")]
-async fn query_phone(info: web::Query<IParams>) -> impl Responder {
-    let params: IParams = info.into_inner();
-    let phone = params.phone;
-    let msg = match STATE.phone_data.find(&phone) {
-        Ok(info) => Message::ok(info),
-        Err(_) => Message::err("查询失败")
+fn run_diff(args: &[String]) -> Result<()> {
+    let (paths, format) = parse_option(args, "format", "json")?;
+    let [old, new] = paths.as_slice() else {
+        return Err(anyhow!("diff takes exactly two data files\n\n{}", USAGE));
     };
-    HttpResponse::Ok().json(msg)
+
+    let diff = diff_databases(old, new)?;
+    match format.as_str() {
+        "json" => println!("{}", diff.to_json()),
+        "csv" => print!("{}", diff.to_csv()),
+        "summary" => print!("{}", diff.summary_table()),
+        other => return Err(anyhow!("unknown format {:?}, expected json, csv or summary", other)),
+    }
+    Ok(())
 }
 
-async fn query_phone2(phone: web::Path<String>) -> impl Responder {
-    let str = phone.into_inner();
-    let msg = match STATE.phone_data.find(&str) {
-        Ok(info) => Message::ok(info),
-        Err(_) => Message::err("查询失败")
+fn run_convert(args: &[String]) -> Result<()> {
+    let (paths, compression) = parse_option(args, "compress", "none")?;
+    let [input, output] = paths.as_slice() else {
+        return Err(anyhow!("convert takes an input and an output file\n\n{}", USAGE));
     };
-    HttpResponse::Ok().json(msg)
-}
 
-#[post("/echo")]
-async fn echo(req_body: String) -> impl Responder {
-    HttpResponse::Ok().body(req_body)
+    let report = convert_to_v2(input, output, compression.parse()?)?;
+    println!(
+        "converted version {}: {} entries, {} records, {} strings, {} -> {} bytes",
+        report.version, report.entries, report.records, report.strings, report.input_bytes, report.output_bytes,
+    );
+    Ok(())
 }
 
-#[derive(Debug, Deserialize)]
-struct DemoPa {
-    province: String,
-}
+fn run_stats(args: &[String]) -> Result<()> {
+    let (paths, format) = parse_option(args, "format", "json")?;
+    let path = match paths.as_slice() {
+        [] => DEFAULT_DATA_PATH,
+        [path] => path.as_str(),
+        _ => return Err(anyhow!("stats takes at most one data file\n\n{}", USAGE)),
+    };
 
-#[post("/hey")]
-async fn manual_hello(pa: web::Json<DemoPa>) -> impl Responder {
-    let _pa = pa.into_inner();
-    println!("province is : {}", _pa.province);
-    HttpResponse::Ok().body("Hey there!")
+    let stats = dataset_stats(path)?;
+    match format.as_str() {
+        "json" => println!("{}", stats.to_json()),
+        "table" => print!("{}", stats.table()),
+        other => return Err(anyhow!("unknown format {:?}, expected json or table", other)),
+    }
+    Ok(())
 }
 
+fn run_filter(args: &[String]) -> Result<()> {
+    let (paths, [filter_type, rate]) = parse_options(args, [("type", "bloom"), ("rate", "0.01")])?;
+    let path = match paths.as_slice() {
+        [] => DEFAULT_DATA_PATH,
+        [path] => path.as_str(),
+        _ => return Err(anyhow!("filter takes at most one data file\n\n{}", USAGE)),
+    };
+    let filter_type = match filter_type.as_str() {
+        "bloom" => FilterType::Bloom,
+        "xor" => FilterType::Xor,
+        other => return Err(anyhow!("unknown filter type {:?}, expected bloom or xor", other)),
+    };
+    let rate: f64 = rate.parse().map_err(|_| anyhow!("--rate must be a number"))?;
+    if !(rate > 0.0 && rate < 1.0) {
+        return Err(anyhow!("--rate must be between 0 and 1"));
+    }
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| {
-        App::new()
-            .service(echo)
-            .service(manual_hello)
-            .service(query_phone)
-            .route("/", web::get().to(hello))
-            .route("/query2/{phone}", web::get().to(query_phone2))
-    }).workers(200)
-        .bind(("0.0.0.0", 8080))?
-        .run()
-        .await
-}
\ No newline at end of file
+    let phone_data = PhoneDataBloom::builder()
+        .filter_type(filter_type)
+        .false_positive_rate(rate)
+        .build_from_path(path)?;
+    println!("{}", serde_json::to_string_pretty(&phone_data.stats_with_diagnostics())?);
+    Ok(())
+}
@@ -0,0 +1,496 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::hash::{BuildHasher, RandomState};
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use anyhow::Result;
+use crate::common::{LookupOutcome, LookupStats, PhoneNoInfo, PhoneStats};
+
+/// HTTP请求耗时的分桶上界（秒）
+const REQUEST_BUCKETS: [f64; 12] = [0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0];
+
+/// 单次查找耗时的分桶上界（秒），查找本身在百纳秒到微秒量级
+const LOOKUP_BUCKETS: [f64; 10] = [1e-7, 2.5e-7, 5e-7, 1e-6, 2.5e-6, 5e-6, 1e-5, 2.5e-5, 1e-4, 1e-3];
+
+/// 查找结果分类的标签值，下标与`LookupOutcome`的声明顺序一致
+const OUTCOME_LABELS: [&str; 6] = ["found", "filtered_out", "not_found", "invalid_length", "invalid_op_no", "invalid_phone_database"];
+
+/// 重新加载结果，下标0为成功
+const RELOAD_LABELS: [&str; 2] = ["success", "failure"];
+
+/// 标签映射的分片数
+const SHARDS: usize = 16;
+
+/// 一个分片：标签值的哈希到标签值和指标，哈希冲突时同一桶内有多项
+type Shard<T> = RwLock<HashMap<u64, Vec<(Box<[String]>, T)>>>;
+
+/// 按标签值分片的映射，用于取值不固定的标签
+///
+/// 已出现过的标签值只取所在分片的读锁，不分配内存；首次出现时才取写锁插入
+#[derive(Debug)]
+struct LabelMap<T> {
+    hasher: RandomState,
+    shards: Vec<Shard<T>>,
+}
+
+impl<T> LabelMap<T> {
+    fn new() -> Self {
+        LabelMap { hasher: RandomState::new(), shards: (0..SHARDS).map(|_| RwLock::new(HashMap::new())).collect() }
+    }
+
+    /// 对标签值对应的指标执行`f`，不存在时先用`init`创建
+    fn with(&self, label_values: &[&str], init: impl FnOnce() -> T, f: impl Fn(&T)) {
+        let hash = self.hasher.hash_one(label_values);
+        let shard = &self.shards[hash as usize % SHARDS];
+        let matches = |labels: &[String]| labels.iter().map(String::as_str).eq(label_values.iter().copied());
+
+        let entries = shard.read().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, value)) = entries.get(&hash).and_then(|bucket| bucket.iter().find(|(labels, _)| matches(labels))) {
+            return f(value);
+        }
+        drop(entries);
+
+        let mut entries = shard.write().unwrap_or_else(|e| e.into_inner());
+        let bucket = entries.entry(hash).or_default();
+        let index = match bucket.iter().position(|(labels, _)| matches(labels)) {
+            Some(index) => index,
+            None => {
+                bucket.push((label_values.iter().map(|v| v.to_string()).collect(), init()));
+                bucket.len() - 1
+            }
+        };
+        f(&bucket[index].1);
+    }
+
+    /// 按标签值排序后遍历，保证输出稳定
+    fn for_each_sorted(&self, mut f: impl FnMut(&[String], &T)) {
+        let shards: Vec<_> = self.shards.iter().map(|shard| shard.read().unwrap_or_else(|e| e.into_inner())).collect();
+        let mut entries: Vec<_> = shards.iter().flat_map(|entries| entries.values().flatten()).collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        for (labels, value) in entries {
+            f(labels, value);
+        }
+    }
+}
+
+/// 按标签值分组的计数器
+#[derive(Debug)]
+struct CounterVec {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    values: LabelMap<AtomicU64>,
+}
+
+impl CounterVec {
+    fn new(name: &'static str, help: &'static str, labels: &'static [&'static str]) -> Self {
+        CounterVec { name, help, labels, values: LabelMap::new() }
+    }
+
+    fn inc(&self, label_values: &[&str]) {
+        self.values.with(label_values, || AtomicU64::new(0), |value| {
+            value.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} counter", self.name);
+        self.values.for_each_sorted(|label_values, value| {
+            let _ = writeln!(out, "{}{} {}", self.name, format_labels(self.labels, label_values, None), value.load(Ordering::Relaxed));
+        });
+    }
+}
+
+/// 标签取值固定的计数器，每个取值预先分配一个原子计数
+#[derive(Debug)]
+struct FixedCounterVec {
+    name: &'static str,
+    help: &'static str,
+    label: &'static str,
+    label_values: &'static [&'static str],
+    values: Vec<AtomicU64>,
+}
+
+impl FixedCounterVec {
+    fn new(name: &'static str, help: &'static str, label: &'static str, label_values: &'static [&'static str]) -> Self {
+        let values = label_values.iter().map(|_| AtomicU64::new(0)).collect();
+        FixedCounterVec { name, help, label, label_values, values }
+    }
+
+    /// `index`为标签值在`label_values`中的下标
+    fn inc(&self, index: usize) {
+        self.values[index].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get(&self, index: usize) -> u64 {
+        self.values[index].load(Ordering::Relaxed)
+    }
+
+    /// 只输出计数不为0的标签值，与按需创建的计数器一致
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} counter", self.name);
+        let mut rows: Vec<_> = self.label_values.iter().zip(&self.values).collect();
+        rows.sort_by_key(|(label_value, _)| **label_value);
+        for (label_value, value) in rows {
+            let value = value.load(Ordering::Relaxed);
+            if value > 0 {
+                let labels = format_labels(&[self.label], &[label_value.to_string()], None);
+                let _ = writeln!(out, "{}{} {}", self.name, labels, value);
+            }
+        }
+    }
+}
+
+/// 固定分桶的直方图
+#[derive(Debug)]
+struct Histogram {
+    /// 每个桶的计数（不累加），最后一个为+Inf
+    buckets: Vec<AtomicU64>,
+    sum_nanos: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &[f64]) -> Self {
+        Histogram {
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum_nanos: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, bounds: &[f64], duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = bounds.partition_point(|&bound| bound < seconds);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos.fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, label_names: &[&str], label_values: &[String], bounds: &[f64]) {
+        let mut cumulative = 0u64;
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = bounds.get(i).map_or_else(|| "+Inf".to_string(), |bound| bound.to_string());
+            let labels = format_labels(label_names, label_values, Some(&le));
+            let _ = writeln!(out, "{}_bucket{} {}", name, labels, cumulative);
+        }
+        let labels = format_labels(label_names, label_values, None);
+        let sum = self.sum_nanos.load(Ordering::Relaxed) as f64 / 1e9;
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, self.count.load(Ordering::Relaxed));
+    }
+}
+
+/// 按标签值分组的直方图
+#[derive(Debug)]
+struct HistogramVec {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    bounds: &'static [f64],
+    values: LabelMap<Histogram>,
+}
+
+impl HistogramVec {
+    fn new(name: &'static str, help: &'static str, labels: &'static [&'static str], bounds: &'static [f64]) -> Self {
+        HistogramVec { name, help, labels, bounds, values: LabelMap::new() }
+    }
+
+    fn observe(&self, label_values: &[&str], duration: Duration) {
+        self.values.with(label_values, || Histogram::new(self.bounds), |histogram| histogram.observe(self.bounds, duration));
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} histogram", self.name);
+        self.values.for_each_sorted(|label_values, histogram| {
+            histogram.render(out, self.name, self.labels, label_values, self.bounds);
+        });
+    }
+}
+
+/// 不带标签的直方图
+#[derive(Debug)]
+struct SingleHistogram {
+    name: &'static str,
+    help: &'static str,
+    bounds: &'static [f64],
+    histogram: Histogram,
+}
+
+impl SingleHistogram {
+    fn new(name: &'static str, help: &'static str, bounds: &'static [f64]) -> Self {
+        SingleHistogram { name, help, bounds, histogram: Histogram::new(bounds) }
+    }
+
+    fn observe(&self, duration: Duration) {
+        self.histogram.observe(self.bounds, duration);
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} histogram", self.name);
+        if self.histogram.count.load(Ordering::Relaxed) > 0 {
+            self.histogram.render(out, self.name, &[], &[], self.bounds);
+        }
+    }
+}
+
+/// 拼接标签，`le`为直方图分桶标签
+fn format_labels(names: &[&str], values: &[String], le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = names
+        .iter()
+        .zip(values)
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label(value)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+
+    if pairs.is_empty() { String::new() } else { format!("{{{}}}", pairs.join(",")) }
+}
+
+/// 不分配内存地把状态码写成十进制标签值
+fn status_label(status: u16, buf: &mut [u8; 5]) -> &str {
+    let mut start = buf.len();
+    let mut rest = status;
+    loop {
+        start -= 1;
+        buf[start] = b'0' + (rest % 10) as u8;
+        rest /= 10;
+        if rest == 0 {
+            break;
+        }
+    }
+    std::str::from_utf8(&buf[start..]).expect("digits are ascii")
+}
+
+/// 按文本格式要求转义标签值中的反斜杠、引号和换行
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// HTTP服务的Prometheus指标
+///
+/// 只在进程内累计，`render`输出文本格式，不依赖运行中的Prometheus
+#[derive(Debug)]
+pub struct Metrics {
+    requests: CounterVec,
+    request_duration: HistogramVec,
+    lookups: FixedCounterVec,
+    lookup_duration: SingleHistogram,
+    carrier_hits: CounterVec,
+    province_hits: CounterVec,
+    reloads: FixedCounterVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            requests: CounterVec::new(
+                "phone_data_http_requests_total",
+                "HTTP requests by endpoint and status.",
+                &["endpoint", "status"],
+            ),
+            request_duration: HistogramVec::new(
+                "phone_data_http_request_duration_seconds",
+                "HTTP request latency by endpoint.",
+                &["endpoint"],
+                &REQUEST_BUCKETS,
+            ),
+            lookups: FixedCounterVec::new(
+                "phone_data_lookups_total",
+                "Phone number lookups by outcome.",
+                "outcome",
+                &OUTCOME_LABELS,
+            ),
+            lookup_duration: SingleHistogram::new(
+                "phone_data_lookup_duration_seconds",
+                "Time spent in the lookup backend.",
+                &LOOKUP_BUCKETS,
+            ),
+            carrier_hits: CounterVec::new(
+                "phone_data_hits_by_carrier_total",
+                "Successful lookups by carrier.",
+                &["carrier"],
+            ),
+            province_hits: CounterVec::new(
+                "phone_data_hits_by_province_total",
+                "Successful lookups by province.",
+                &["province"],
+            ),
+            reloads: FixedCounterVec::new(
+                "phone_data_reloads_total",
+                "Database reloads by result.",
+                "result",
+                &RELOAD_LABELS,
+            ),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一次HTTP请求，endpoint应使用路由模板以免标签基数膨胀
+    pub fn observe_request(&self, endpoint: &str, status: u16, duration: Duration) {
+        let mut buf = [0u8; 5];
+        self.requests.inc(&[endpoint, status_label(status, &mut buf)]);
+        self.request_duration.observe(&[endpoint], duration);
+    }
+
+    /// 记录一次查找的结果分类、耗时，命中时按运营商和省份计数
+    pub fn observe_lookup(&self, result: &Result<PhoneNoInfo>, stats: &LookupStats) {
+        self.lookups.inc(stats.outcome as usize);
+        self.lookup_duration.observe(stats.total_time());
+        if let Ok(info) = result {
+            self.carrier_hits.inc(&[&info.card_type]);
+            self.province_hits.inc(&[&info.province]);
+        }
+    }
+
+    /// 记录一次数据库重新加载
+    pub fn observe_reload(&self, success: bool) {
+        self.reloads.inc(if success { 0 } else { 1 });
+    }
+
+    /// 某个结果分类的累计查找次数
+    pub fn lookup_count(&self, outcome: LookupOutcome) -> u64 {
+        self.lookups.get(outcome as usize)
+    }
+
+    /// 输出Prometheus文本格式，数据库版本和记录数取自当前加载的数据
+    pub fn render(&self, db: &dyn PhoneStats) -> String {
+        let mut out = String::new();
+        self.requests.render(&mut out);
+        self.request_duration.render(&mut out);
+        self.lookups.render(&mut out);
+        self.lookup_duration.render(&mut out);
+        self.carrier_hits.render(&mut out);
+        self.province_hits.render(&mut out);
+        self.reloads.render(&mut out);
+
+        let _ = writeln!(out, "# HELP phone_data_database_info Loaded database version.");
+        let _ = writeln!(out, "# TYPE phone_data_database_info gauge");
+        let _ = writeln!(out, "phone_data_database_info{{version=\"{}\"}} 1", escape_label(db.version()));
+        let _ = writeln!(out, "# HELP phone_data_database_entries Number of prefixes in the loaded database.");
+        let _ = writeln!(out, "# TYPE phone_data_database_entries gauge");
+        let _ = writeln!(out, "phone_data_database_entries {}", db.total_entries());
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ErrorKind;
+
+    struct FixedStats;
+
+    impl PhoneStats for FixedStats {
+        fn total_entries(&self) -> usize {
+            42
+        }
+
+        fn version(&self) -> &str {
+            "2410"
+        }
+
+        fn memory_usage_bytes(&self) -> usize {
+            0
+        }
+    }
+
+    fn stats(outcome: LookupOutcome) -> LookupStats {
+        LookupStats {
+            parse_time: Duration::from_nanos(50),
+            filter_time: Duration::ZERO,
+            search_time: Duration::from_nanos(200),
+            probes: 20,
+            decode_time: Duration::from_nanos(100),
+            outcome,
+        }
+    }
+
+    #[test]
+    fn test_render_text_format() {
+        let metrics = Metrics::new();
+        metrics.observe_request("/query2/{phone}", 200, Duration::from_micros(300));
+        metrics.observe_request("/query2/{phone}", 200, Duration::from_millis(2));
+        metrics.observe_request("/query", 400, Duration::from_micros(50));
+
+        let info = PhoneNoInfo::new("广东".into(), "深圳".into(), "518000".into(), "0755".into(), "中国移动".into());
+        metrics.observe_lookup(&Ok(info), &stats(LookupOutcome::Found));
+        metrics.observe_lookup(&Err(ErrorKind::NotFound.into()), &stats(LookupOutcome::NotFound));
+        metrics.observe_reload(true);
+        metrics.observe_reload(false);
+        metrics.observe_reload(false);
+
+        let text = metrics.render(&FixedStats);
+        assert!(text.contains("phone_data_http_requests_total{endpoint=\"/query2/{phone}\",status=\"200\"} 2"));
+        assert!(text.contains("phone_data_http_requests_total{endpoint=\"/query\",status=\"400\"} 1"));
+        assert!(text.contains("phone_data_http_request_duration_seconds_bucket{endpoint=\"/query2/{phone}\",le=\"0.0005\"} 1"));
+        assert!(text.contains("phone_data_http_request_duration_seconds_bucket{endpoint=\"/query2/{phone}\",le=\"+Inf\"} 2"));
+        assert!(text.contains("phone_data_http_request_duration_seconds_count{endpoint=\"/query2/{phone}\"} 2"));
+        assert!(text.contains("phone_data_lookups_total{outcome=\"found\"} 1"));
+        assert!(text.contains("phone_data_lookups_total{outcome=\"not_found\"} 1"));
+        assert!(text.contains("phone_data_lookup_duration_seconds_bucket{le=\"0.0000005\"} 2"));
+        assert!(text.contains("phone_data_hits_by_carrier_total{carrier=\"中国移动\"} 1"));
+        assert!(text.contains("phone_data_hits_by_province_total{province=\"广东\"} 1"));
+        assert!(text.contains("phone_data_reloads_total{result=\"failure\"} 2"));
+        assert!(text.contains("phone_data_reloads_total{result=\"success\"} 1"));
+        assert!(text.contains("phone_data_database_info{version=\"2410\"} 1"));
+        assert!(text.contains("phone_data_database_entries 42"));
+        assert_eq!(metrics.lookup_count(LookupOutcome::Found), 1);
+    }
+
+    #[test]
+    fn test_fixed_labels_match_outcomes() {
+        let outcomes = [
+            LookupOutcome::Found,
+            LookupOutcome::FilteredOut,
+            LookupOutcome::NotFound,
+            LookupOutcome::InvalidLength,
+            LookupOutcome::InvalidOpNo,
+            LookupOutcome::InvalidPhoneDatabase,
+        ];
+        for (i, outcome) in outcomes.iter().enumerate() {
+            assert_eq!(*outcome as usize, i);
+            assert_eq!(OUTCOME_LABELS[i], outcome.as_str());
+        }
+        let mut buf = [0u8; 5];
+        assert_eq!(status_label(200, &mut buf), "200");
+        assert_eq!(status_label(0, &mut buf), "0");
+        assert_eq!(status_label(u16::MAX, &mut buf), "65535");
+    }
+
+    #[test]
+    fn test_concurrent_counters() {
+        let metrics = Metrics::new();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for i in 0..1000 {
+                        let endpoint = if i % 2 == 0 { "/query" } else { "/prefix/{prefix}" };
+                        metrics.observe_request(endpoint, 200, Duration::from_micros(100));
+                    }
+                });
+            }
+        });
+        let text = metrics.render(&FixedStats);
+        assert!(text.contains("phone_data_http_requests_total{endpoint=\"/query\",status=\"200\"} 2000"));
+        assert!(text.contains("phone_data_http_requests_total{endpoint=\"/prefix/{prefix}\",status=\"200\"} 2000"));
+    }
+
+    #[test]
+    fn test_label_escaping() {
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+        assert_eq!(format_labels(&[], &[], None), "");
+    }
+}
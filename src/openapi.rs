@@ -0,0 +1,456 @@
+use serde_json::{json, Map, Value};
+
+use crate::common::{CardType, PhoneNoInfo};
+use crate::overlay::{AppliedOverride, OverlaidInfo};
+use crate::server::{KeysReloadResult, LookupData, OverlayReloadResult, RateLimited, ReloadResult, ServiceInfo};
+use crate::stats::{DatasetStats, PrefixGap, ProvinceStats, SegmentStats};
+use crate::versioned::VersionInfo;
+
+/// 响应类型的JSON Schema，字段需与序列化结果一一对应
+pub trait ApiSchema {
+    /// `components.schemas`中的名称
+    const NAME: &'static str;
+
+    fn schema() -> Value;
+}
+
+/// 字段定义：名称、Schema、说明
+type Field = (&'static str, Value, &'static str);
+
+fn string() -> Value {
+    json!({"type": "string"})
+}
+
+fn integer() -> Value {
+    json!({"type": "integer", "minimum": 0})
+}
+
+fn number() -> Value {
+    json!({"type": "number"})
+}
+
+/// 全部字段必填、不允许额外字段的对象
+fn object(description: &str, fields: Vec<Field>) -> Value {
+    let mut properties = Map::new();
+    for (name, mut schema, field_description) in fields.iter().cloned() {
+        if !field_description.is_empty() {
+            schema["description"] = json!(field_description);
+        }
+        properties.insert(name.to_string(), schema);
+    }
+    json!({
+        "type": "object",
+        "description": description,
+        "required": fields.iter().map(|(name, _, _)| *name).collect::<Vec<_>>(),
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({"$ref": format!("#/components/schemas/{}", name)})
+}
+
+impl ApiSchema for PhoneNoInfo {
+    const NAME: &'static str = "PhoneNoInfo";
+
+    fn schema() -> Value {
+        let card_types: Vec<String> = (0..=u8::MAX)
+            .filter_map(|code| CardType::from_u8(code).ok())
+            .map(|card_type| card_type.get_description())
+            .collect();
+        object("号码归属地", vec![
+            ("province", string(), "省份"),
+            ("city", string(), "城市"),
+            ("zip_code", string(), "邮政编码"),
+            ("area_code", string(), "长途区号"),
+            ("card_type", json!({"type": "string", "enum": card_types}), "运营商"),
+        ])
+    }
+}
+
+impl ApiSchema for AppliedOverride {
+    const NAME: &'static str = "AppliedOverride";
+
+    fn schema() -> Value {
+        object("结果上应用的人工覆盖", vec![
+            ("level", json!({"type": "string", "enum": ["prefix", "number"]}), "按号段还是完整号码覆盖"),
+            ("reason", string(), "修改原因"),
+            ("source", string(), "数据来源"),
+        ])
+    }
+}
+
+impl ApiSchema for OverlaidInfo {
+    const NAME: &'static str = "OverlaidInfo";
+
+    fn schema() -> Value {
+        let mut schema = PhoneNoInfo::schema();
+        schema["description"] = json!("号码归属地，叠加人工覆盖后的结果");
+        schema["required"].as_array_mut().unwrap().push(json!("override"));
+        schema["properties"]["override"] = json!({
+            "allOf": [schema_ref(AppliedOverride::NAME)],
+            "nullable": true,
+            "description": "应用了人工覆盖时为覆盖信息，否则为null",
+        });
+        schema
+    }
+}
+
+impl ApiSchema for LookupData {
+    const NAME: &'static str = "LookupData";
+
+    fn schema() -> Value {
+        let mut schema = OverlaidInfo::schema();
+        schema["description"] = json!("查询结果：叠加人工覆盖后的归属地，以及携号转网后的运营商");
+        let card_type = schema["properties"]["card_type"].clone();
+        for (name, field, description) in [
+            ("original_carrier", card_type.clone(), "按号段得到的运营商，与card_type相同"),
+            ("current_carrier", card_type, "当前运营商，未转网时与original_carrier相同"),
+            ("ported", json!({"type": "boolean"}), "号码是否在携号转网表中"),
+        ] {
+            let mut field = field;
+            field["description"] = json!(description);
+            schema["required"].as_array_mut().unwrap().push(json!(name));
+            schema["properties"][name] = field;
+        }
+        schema
+    }
+}
+
+impl ApiSchema for ServiceInfo {
+    const NAME: &'static str = "ServiceInfo";
+
+    fn schema() -> Value {
+        object("服务和当前数据的基本信息", vec![
+            ("backend", string(), "查找实现"),
+            ("version", string(), "数据版本"),
+            ("total_entries", integer(), ""),
+            ("memory_usage_bytes", integer(), ""),
+            ("loaded_at", integer(), "数据加载完成的Unix时间戳（秒）"),
+            ("load_duration_ms", integer(), "数据加载耗时（毫秒）"),
+            ("verification", json!({"type": "string", "enum": ["none", "checksum", "signature"]}), "数据文件通过的校验"),
+            ("build_version", string(), "服务构建版本"),
+        ])
+    }
+}
+
+impl ApiSchema for ReloadResult {
+    const NAME: &'static str = "ReloadResult";
+
+    fn schema() -> Value {
+        object("重新加载后的数据", vec![
+            ("version", string(), "数据版本"),
+            ("total_entries", integer(), ""),
+        ])
+    }
+}
+
+impl ApiSchema for KeysReloadResult {
+    const NAME: &'static str = "KeysReloadResult";
+
+    fn schema() -> Value {
+        object("重新加载后的API Key", vec![("keys", integer(), "Key数量")])
+    }
+}
+
+impl ApiSchema for OverlayReloadResult {
+    const NAME: &'static str = "OverlayReloadResult";
+
+    fn schema() -> Value {
+        object("重新加载后的人工覆盖", vec![("entries", integer(), "覆盖记录数")])
+    }
+}
+
+impl ApiSchema for RateLimited {
+    const NAME: &'static str = "RateLimited";
+
+    fn schema() -> Value {
+        object("超出配额时的限流信息", vec![
+            ("limit", integer(), "桶容量"),
+            ("remaining", integer(), "剩余令牌数"),
+            ("retry_after", integer(), "距离下一个令牌可用的秒数"),
+        ])
+    }
+}
+
+impl ApiSchema for ProvinceStats {
+    const NAME: &'static str = "ProvinceStats";
+
+    fn schema() -> Value {
+        object("一个省份的号段数和城市数", vec![
+            ("prefixes", integer(), ""),
+            ("cities", integer(), "不同城市数"),
+        ])
+    }
+}
+
+impl ApiSchema for SegmentStats {
+    const NAME: &'static str = "SegmentStats";
+
+    fn schema() -> Value {
+        object("一个3位号段的覆盖情况", vec![
+            ("segment", integer(), "号码前3位，如130"),
+            ("prefixes", integer(), ""),
+            ("coverage", number(), "已收录的前7位占号段内10000个的比例"),
+        ])
+    }
+}
+
+impl ApiSchema for PrefixGap {
+    const NAME: &'static str = "PrefixGap";
+
+    fn schema() -> Value {
+        object("号段内连续未收录的前7位", vec![
+            ("start", integer(), "第一个未收录的前7位"),
+            ("end", integer(), "最后一个未收录的前7位"),
+            ("length", integer(), ""),
+        ])
+    }
+}
+
+impl ApiSchema for DatasetStats {
+    const NAME: &'static str = "DatasetStats";
+
+    fn schema() -> Value {
+        object("数据内容的统计报告", vec![
+            ("version", string(), "数据版本"),
+            ("total_prefixes", integer(), ""),
+            ("distinct_cities", integer(), "不同城市数，不同省份的同名城市分别计数"),
+            ("by_carrier", json!({"type": "object", "additionalProperties": integer()}), "按运营商的号段数"),
+            ("by_province", json!({"type": "object", "additionalProperties": schema_ref(ProvinceStats::NAME)}), "按省份的号段数和城市数"),
+            ("by_segment", json!({"type": "array", "items": schema_ref(SegmentStats::NAME)}), "按号段升序的覆盖情况"),
+            ("largest_gaps", json!({"type": "array", "items": schema_ref(PrefixGap::NAME)}), "已收录号段内最长的空缺，按长度降序"),
+        ])
+    }
+}
+
+impl ApiSchema for VersionInfo {
+    const NAME: &'static str = "VersionInfo";
+
+    fn schema() -> Value {
+        object("已加载的数据版本", vec![
+            ("version", string(), "数据版本"),
+            ("total_entries", integer(), ""),
+            ("latest", json!({"type": "boolean"}), "是否为默认查询的最新版本"),
+        ])
+    }
+}
+
+/// `Message<T>`统一响应结构，`data`为`None`时序列化为null
+fn message(name: &str, data: Value) -> (String, Value) {
+    let mut data = data;
+    data["nullable"] = json!(true);
+    let schema = object("统一响应结构", vec![
+        ("code", json!({"type": "integer"}), "0表示成功，其它值为错误码"),
+        ("data", data, "成功时的数据，失败时为null"),
+        ("success", json!({"type": "boolean"}), ""),
+        ("result", string(), "ok或错误信息"),
+    ]);
+    (format!("Message_{}", name), schema)
+}
+
+fn message_of<T: ApiSchema>() -> (String, Value) {
+    // $ref不能与nullable并列，用allOf包一层
+    message(T::NAME, json!({"allOf": [schema_ref(T::NAME)]}))
+}
+
+/// JSON响应
+fn json_response(description: &str, schema: &str) -> Value {
+    json!({
+        "description": description,
+        "content": {"application/json": {"schema": schema_ref(schema)}},
+    })
+}
+
+/// 查询类接口的响应：查找结果、参数错误、认证失败和限流
+fn lookup_responses() -> Value {
+    json!({
+        "200": {
+            "description": "查找结果，未找到时`success`为false",
+            "headers": cache_headers(),
+            "content": {"application/json": {"schema": schema_ref("Message_LookupData")}},
+        },
+        "304": {"description": "`If-None-Match`与当前`ETag`一致，可继续使用缓存", "headers": cache_headers()},
+        "404": json_response("指定的数据版本未加载", "Message_Error"),
+        "400": json_response("隐私模式拒绝完整号码，或号段格式错误", "Message_Error"),
+        "401": json_response("启用了API Key认证但未提供或无效", "Message_Error"),
+        "429": {
+            "description": "超出配额，`Retry-After`为需要等待的秒数",
+            "headers": rate_limit_headers(),
+            "content": {"application/json": {"schema": schema_ref("Message_RateLimited")}},
+        },
+    })
+}
+
+fn cache_headers() -> Value {
+    json!({
+        "ETag": {"schema": string(), "description": "由数据版本、号码前7位和涉及该号段的人工覆盖及转网号码生成，任一变化后失效"},
+        "Cache-Control": {"schema": string(), "description": "`public`或`private`（启用API Key认证时），max-age可配置"},
+    })
+}
+
+fn rate_limit_headers() -> Value {
+    json!({
+        "X-RateLimit-Limit": {"schema": integer(), "description": "桶容量"},
+        "X-RateLimit-Remaining": {"schema": integer(), "description": "剩余令牌数"},
+        "X-RateLimit-Reset": {"schema": integer(), "description": "桶回满所需秒数"},
+    })
+}
+
+fn path_param(name: &str, description: &str, example: &str) -> Value {
+    json!({"name": name, "in": "path", "required": true, "description": description, "schema": string(), "example": example})
+}
+
+fn query_param(name: &str, required: bool, description: &str, schema: Value, example: Value) -> Value {
+    json!({"name": name, "in": "query", "required": required, "description": description, "schema": schema, "example": example})
+}
+
+/// 查询的数据版本，默认最新版本
+fn version_param() -> Value {
+    query_param("version", false, "数据版本，默认最新版本，可选值见`/versions`", string(), json!("9999"))
+}
+
+fn operation(tag: &str, summary: &str, parameters: Vec<Value>, responses: Value) -> Value {
+    json!({
+        "tags": [tag],
+        "summary": summary,
+        "parameters": parameters,
+        "responses": responses,
+        "security": [{}, {"ApiKey": []}, {"Bearer": []}],
+    })
+}
+
+/// 管理接口，要求管理Key
+fn admin_operation(summary: &str, responses: Value) -> Value {
+    let mut op = operation("管理", summary, vec![], responses);
+    op["security"] = json!([{"AdminKey": []}, {"Bearer": []}]);
+    op["responses"]["401"] = json_response("管理Key缺少或错误", "Message_Error");
+    op["responses"]["403"] = json_response("未配置管理Key，管理接口不可用", "Message_Error");
+    op
+}
+
+/// 不需要认证的接口
+fn open_operation(tag: &str, summary: &str, parameters: Vec<Value>, responses: Value) -> Value {
+    let mut op = operation(tag, summary, parameters, responses);
+    op.as_object_mut().unwrap().remove("security");
+    op
+}
+
+/// 生成OpenAPI 3文档
+pub fn spec() -> Value {
+    let mut schemas = Map::new();
+    for (name, schema) in [
+        (PhoneNoInfo::NAME.to_string(), PhoneNoInfo::schema()),
+        (AppliedOverride::NAME.to_string(), AppliedOverride::schema()),
+        (LookupData::NAME.to_string(), LookupData::schema()),
+        (ServiceInfo::NAME.to_string(), ServiceInfo::schema()),
+        (ReloadResult::NAME.to_string(), ReloadResult::schema()),
+        (KeysReloadResult::NAME.to_string(), KeysReloadResult::schema()),
+        (OverlayReloadResult::NAME.to_string(), OverlayReloadResult::schema()),
+        (RateLimited::NAME.to_string(), RateLimited::schema()),
+        message_of::<LookupData>(),
+        message_of::<ServiceInfo>(),
+        message_of::<ReloadResult>(),
+        message_of::<KeysReloadResult>(),
+        message_of::<OverlayReloadResult>(),
+        message_of::<RateLimited>(),
+        (ProvinceStats::NAME.to_string(), ProvinceStats::schema()),
+        (SegmentStats::NAME.to_string(), SegmentStats::schema()),
+        (PrefixGap::NAME.to_string(), PrefixGap::schema()),
+        (DatasetStats::NAME.to_string(), DatasetStats::schema()),
+        message_of::<DatasetStats>(),
+        (VersionInfo::NAME.to_string(), VersionInfo::schema()),
+        message("VersionList", json!({"type": "array", "items": schema_ref(VersionInfo::NAME)})),
+        message("Status", string()),
+        message("Error", json!({"type": "object", "additionalProperties": false})),
+    ] {
+        schemas.insert(name, schema);
+    }
+
+    let phone = path_param("phone", "手机号，7到11位，查找只使用前7位", "18086834111");
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "phone_data",
+            "description": "手机号归属地查询服务",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": {
+            "/query": {"get": operation("查询", "GET参数查询",
+                vec![query_param("phone", true, "手机号，7到11位", string(), json!("18086834111")), version_param()],
+                lookup_responses())},
+            "/query2/{phone}": {"get": operation("查询", "路径参数查询", vec![phone, version_param()], lookup_responses())},
+            "/prefix/{prefix}": {"get": operation("查询", "号段查询，只接受号码前7位",
+                vec![path_param("prefix", "号码前7位", "1808683"), version_param()],
+                lookup_responses())},
+            "/healthz": {"get": open_operation("探针", "存活探针", vec![],
+                json!({"200": json_response("进程能处理请求", "Message_Status")}))},
+            "/readyz": {"get": open_operation("探针", "就绪探针", vec![], json!({
+                "200": json_response("数据已加载且自检通过", "Message_Status"),
+                "503": json_response("未就绪或正在停机", "Message_Error"),
+            }))},
+            "/versions": {"get": open_operation("查询", "已加载的数据版本及条目数", vec![],
+                json!({"200": json_response("按版本号升序", "Message_VersionList")}))},
+            "/info": {"get": open_operation("探针", "服务和当前数据的基本信息", vec![],
+                json!({"200": json_response("服务信息", "Message_ServiceInfo")}))},
+            "/metrics": {"get": open_operation("监控", "Prometheus文本格式的指标", vec![], json!({
+                "200": {"description": "Prometheus文本格式", "content": {"text/plain": {"schema": string()}}},
+            }))},
+            "/stats": {"get": open_operation("查询", "当前数据的统计报告：运营商、省份、号段覆盖率和最长空缺", vec![
+                query_param("format", false, "输出格式，默认json", json!({"type": "string", "enum": ["json", "table"]}), json!("json")),
+            ], json!({
+                "200": {
+                    "description": "`format=json`时为统一响应结构，`format=table`时为文本表格",
+                    "content": {
+                        "application/json": {"schema": schema_ref("Message_DatasetStats")},
+                        "text/plain": {"schema": string()},
+                    },
+                },
+            }))},
+            "/admin/reload": {"post": admin_operation("重新加载数据文件，失败时继续使用原数据",
+                json!({"200": json_response("新数据的版本和条目数", "Message_ReloadResult")}))},
+            "/admin/keys/reload": {"post": admin_operation("重新加载API Key文件，失败时继续使用原列表",
+                json!({"200": json_response("新的Key数量", "Message_KeysReloadResult")}))},
+            "/admin/overlay/reload": {"post": admin_operation("重新加载人工覆盖文件，失败时继续使用原覆盖",
+                json!({"200": json_response("新的覆盖记录数", "Message_OverlayReloadResult")}))},
+        },
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "ApiKey": {"type": "apiKey", "in": "header", "name": "X-API-Key"},
+                "Bearer": {"type": "http", "scheme": "bearer"},
+                "AdminKey": {"type": "apiKey", "in": "header", "name": "X-Admin-Key"},
+            },
+        },
+    })
+}
+
+/// 自带的文档页面，读取`/openapi.json`渲染，不依赖外部资源
+pub const DOCS_HTML: &str = include_str!("docs.html");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refs_resolve() {
+        let spec = spec();
+        let text = spec.to_string();
+        let schemas = spec["components"]["schemas"].as_object().unwrap();
+        for part in text.split("\"#/components/schemas/").skip(1) {
+            let name = &part[..part.find('"').unwrap()];
+            assert!(schemas.contains_key(name), "unresolved $ref {}", name);
+        }
+    }
+
+    #[test]
+    fn test_object_lists_every_field_as_required() {
+        let schema = PhoneNoInfo::schema();
+        let required: Vec<&str> = schema["required"].as_array().unwrap().iter().map(|v| v.as_str().unwrap()).collect();
+        let properties: Vec<&str> = schema["properties"].as_object().unwrap().keys().map(String::as_str).collect();
+        assert_eq!(required.len(), properties.len());
+        assert!(required.iter().all(|name| properties.contains(name)));
+        assert_eq!(schema["additionalProperties"], false);
+    }
+}
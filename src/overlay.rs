@@ -0,0 +1,458 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use anyhow::{anyhow, Context, Result};
+use serde_derive::Serialize;
+
+use crate::common::{utils, CardType, ErrorKind, PhoneLookup, PhoneNoInfo};
+
+/// 覆盖的粒度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverrideLevel {
+    /// 号码前7位
+    Prefix,
+    /// 完整11位号码
+    Number,
+}
+
+/// 一条覆盖记录，未填写的字段沿用基础数据
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OverrideEntry {
+    pub province: Option<String>,
+    pub city: Option<String>,
+    pub zip_code: Option<String>,
+    pub area_code: Option<String>,
+    /// 运营商描述，如`中国移动`
+    pub card_type: Option<String>,
+    /// 修改原因
+    pub reason: String,
+    /// 数据来源，如工单号
+    pub source: String,
+}
+
+impl OverrideEntry {
+    /// 是否覆盖了全部字段，此时不需要查询基础数据
+    fn is_complete(&self) -> bool {
+        self.province.is_some() && self.city.is_some() && self.zip_code.is_some() && self.area_code.is_some() && self.card_type.is_some()
+    }
+
+    /// 是否没有覆盖任何字段
+    fn is_empty(&self) -> bool {
+        [&self.province, &self.city, &self.zip_code, &self.area_code, &self.card_type].iter().all(|field| field.is_none())
+    }
+
+    /// 按字段名设置值
+    fn set(&mut self, field: &str, value: &str) -> Result<()> {
+        let value = value.trim();
+        let slot = match field {
+            "province" => &mut self.province,
+            "city" => &mut self.city,
+            "zip_code" => &mut self.zip_code,
+            "area_code" => &mut self.area_code,
+            "card_type" => {
+                self.card_type = if value.is_empty() { None } else { Some(parse_card_type(value)?) };
+                return Ok(());
+            }
+            "reason" => {
+                self.reason = value.to_string();
+                return Ok(());
+            }
+            "source" => {
+                self.source = value.to_string();
+                return Ok(());
+            }
+            other => return Err(anyhow!("unknown field {:?}", other)),
+        };
+        *slot = (!value.is_empty()).then(|| value.to_string());
+        Ok(())
+    }
+
+    fn apply_to(&self, base: Option<PhoneNoInfo>) -> PhoneNoInfo {
+        let base = base.unwrap_or_else(|| PhoneNoInfo::new(String::new(), String::new(), String::new(), String::new(), String::new()));
+        let pick = |value: &Option<String>, base: String| value.clone().unwrap_or(base);
+        PhoneNoInfo::new(
+            pick(&self.province, base.province),
+            pick(&self.city, base.city),
+            pick(&self.zip_code, base.zip_code),
+            pick(&self.area_code, base.area_code),
+            pick(&self.card_type, base.card_type),
+        )
+    }
+}
+
+/// 运营商可以写描述或代码
+fn parse_card_type(value: &str) -> Result<String> {
+    Ok(value.parse::<CardType>()?.get_description())
+}
+
+/// 结果上标注的覆盖信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AppliedOverride {
+    pub level: OverrideLevel,
+    pub reason: String,
+    pub source: String,
+}
+
+/// 叠加覆盖后的查询结果
+#[derive(Debug, Clone, Serialize)]
+pub struct OverlaidInfo {
+    #[serde(flatten)]
+    pub info: PhoneNoInfo,
+    /// 应用了覆盖时不为空
+    #[serde(rename = "override")]
+    pub applied: Option<AppliedOverride>,
+}
+
+/// 叠加在基础数据之上的人工覆盖，查询时先于基础数据生效
+///
+/// 支持两种文件格式，按扩展名区分：
+/// - CSV：首行为列名，`key`为7位号段或11位号码，其余列为`province,city,zip_code,area_code,card_type,reason,source`，留空表示沿用基础数据
+/// - TOML（`.toml`）：每条为一个`[[override]]`表，字段同上，值为字符串或整数
+#[derive(Debug, Clone, Default)]
+pub struct Overlay {
+    prefixes: HashMap<i32, OverrideEntry>,
+    numbers: HashMap<u64, OverrideEntry>,
+    /// 有完整号码级覆盖的号段，计算缓存键时不必遍历全部号码
+    number_prefixes: HashSet<i32>,
+    /// 文件内容的摘要，用于让缓存随覆盖内容失效
+    fingerprint: u64,
+}
+
+impl Overlay {
+    /// 按扩展名选择格式加载
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Overlay> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read overlay {}", path.display()))?;
+        let overlay = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml")) {
+            Self::parse_toml(&content)
+        } else {
+            Self::parse_csv(&content)
+        };
+        overlay.with_context(|| format!("invalid overlay {}", path.display()))
+    }
+
+    /// 解析CSV格式
+    pub fn parse_csv(content: &str) -> Result<Overlay> {
+        let mut lines = content.lines().enumerate().filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        });
+        let Some((_, header)) = lines.next() else {
+            return Ok(Overlay::default());
+        };
+        let columns: Vec<String> = split_csv_line(header)?.into_iter().map(|c| c.trim().to_string()).collect();
+        if !columns.iter().any(|c| c == "key") {
+            return Err(anyhow!("header must contain a key column"));
+        }
+
+        let mut entries = Vec::new();
+        for (line_no, line) in lines {
+            let fields = split_csv_line(line).with_context(|| format!("line {}", line_no + 1))?;
+            if fields.len() != columns.len() {
+                return Err(anyhow!("line {}: expected {} fields, found {}", line_no + 1, columns.len(), fields.len()));
+            }
+            let mut key = String::new();
+            let mut entry = OverrideEntry::default();
+            for (column, value) in columns.iter().zip(&fields) {
+                if column == "key" {
+                    key = value.trim().to_string();
+                } else {
+                    entry.set(column, value).with_context(|| format!("line {}", line_no + 1))?;
+                }
+            }
+            entries.push((line_no + 1, key, entry));
+        }
+        Self::from_entries(entries, content)
+    }
+
+    /// 解析TOML格式，只支持`[[override]]`表和字符串、整数值
+    pub fn parse_toml(content: &str) -> Result<Overlay> {
+        let mut entries = Vec::new();
+        let mut current: Option<(usize, String, OverrideEntry)> = None;
+        for (line_no, line) in content.lines().enumerate() {
+            let line = strip_toml_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line == "[[override]]" {
+                entries.extend(current.take());
+                current = Some((line_no + 1, String::new(), OverrideEntry::default()));
+                continue;
+            }
+
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("line {}: expected `name = value` or [[override]]", line_no + 1))?;
+            let (name, value) = (name.trim(), parse_toml_value(value.trim()).with_context(|| format!("line {}", line_no + 1))?);
+            let (_, key, entry) = current
+                .as_mut()
+                .ok_or_else(|| anyhow!("line {}: value outside of an [[override]] table", line_no + 1))?;
+            if name == "key" {
+                *key = value;
+            } else {
+                entry.set(name, &value).with_context(|| format!("line {}", line_no + 1))?;
+            }
+        }
+        entries.extend(current);
+        Self::from_entries(entries, content)
+    }
+
+    fn from_entries(entries: Vec<(usize, String, OverrideEntry)>, content: &str) -> Result<Overlay> {
+        let mut overlay = Overlay::default();
+        for (line_no, key, entry) in entries {
+            if entry.reason.is_empty() && entry.source.is_empty() {
+                return Err(anyhow!("line {}: override for {} needs a reason or source", line_no, key));
+            }
+            if entry.is_empty() {
+                return Err(anyhow!("line {}: override for {} changes no field", line_no, key));
+            }
+            let duplicate = match (key.len(), key.bytes().all(|b| b.is_ascii_digit())) {
+                (7, true) => overlay.prefixes.insert(key.parse()?, entry).is_some(),
+                (11, true) => {
+                    let number: u64 = key.parse()?;
+                    overlay.number_prefixes.insert((number / 10_000) as i32);
+                    overlay.numbers.insert(number, entry).is_some()
+                }
+                _ => return Err(anyhow!("line {}: key {:?} must be a 7-digit prefix or an 11-digit number", line_no, key)),
+            };
+            if duplicate {
+                return Err(anyhow!("line {}: duplicate override for {}", line_no, key));
+            }
+        }
+
+        overlay.fingerprint = utils::stable_hash(&[content.as_bytes()]);
+        Ok(overlay)
+    }
+
+    /// 覆盖记录数
+    pub fn len(&self) -> usize {
+        self.prefixes.len() + self.numbers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 号码对应的覆盖记录，完整号码优先于号段
+    pub fn get(&self, no: &str) -> Option<(OverrideLevel, &OverrideEntry)> {
+        if no.len() == 11
+            && let Ok(number) = no.parse::<u64>()
+            && let Some(entry) = self.numbers.get(&number)
+        {
+            return Some((OverrideLevel::Number, entry));
+        }
+        let prefix = utils::parse_phone_prefix(no).ok()?;
+        self.prefixes.get(&prefix).map(|entry| (OverrideLevel::Prefix, entry))
+    }
+
+    /// 把覆盖叠加到基础数据的查询结果上
+    ///
+    /// 覆盖了全部字段时即使基础数据中没有该号段也返回结果，否则沿用基础数据的错误
+    pub fn apply(&self, no: &str, base: Result<PhoneNoInfo>) -> Result<OverlaidInfo> {
+        let Some((level, entry)) = self.get(no) else {
+            return base.map(|info| OverlaidInfo { info, applied: None });
+        };
+        let info = match base {
+            Ok(info) => entry.apply_to(Some(info)),
+            Err(_) if entry.is_complete() => entry.apply_to(None),
+            Err(e) => return Err(e),
+        };
+        let applied = AppliedOverride { level, reason: entry.reason.clone(), source: entry.source.clone() };
+        Ok(OverlaidInfo { info, applied: Some(applied) })
+    }
+
+    /// 先查覆盖再查基础数据，覆盖了全部字段时不查询基础数据
+    pub fn find<L: PhoneLookup + ?Sized>(&self, base: &L, no: &str) -> Result<OverlaidInfo> {
+        match self.get(no) {
+            Some((_, entry)) if entry.is_complete() => self.apply(no, Err(ErrorKind::NotFound.into())),
+            _ => self.apply(no, base.find(no)),
+        }
+    }
+
+    /// 号码的缓存键后缀，覆盖不涉及该号段时为空
+    ///
+    /// 号段下有完整号码级覆盖时，不同号码的结果可能不同，因此同时计入号码本身
+    pub fn cache_key(&self, no: &str) -> Option<u64> {
+        let prefix = utils::parse_phone_prefix(no).ok()?;
+        let number_level = self.number_prefixes.contains(&prefix);
+        if !number_level && !self.prefixes.contains_key(&prefix) {
+            return None;
+        }
+        let fingerprint = self.fingerprint.to_le_bytes();
+        Some(if number_level {
+            utils::stable_hash(&[&fingerprint, no.as_bytes()])
+        } else {
+            utils::stable_hash(&[&fingerprint])
+        })
+    }
+}
+
+/// 拆分一行CSV，支持双引号包裹和`""`转义
+fn split_csv_line(line: &str) -> Result<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            (c, _) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(anyhow!("unterminated quoted field"));
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+/// 去掉字符串外的`#`注释
+fn strip_toml_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' if in_string => escaped = !escaped,
+            '"' if !escaped => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => escaped = false,
+        }
+        if c != '\\' {
+            escaped = false;
+        }
+    }
+    line
+}
+
+/// 解析基本字符串或整数
+fn parse_toml_value(value: &str) -> Result<String> {
+    if let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        let mut out = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                other => return Err(anyhow!("unsupported escape \\{}", other.map(String::from).unwrap_or_default())),
+            }
+        }
+        return Ok(out);
+    }
+    if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(value.to_string());
+    }
+    Err(anyhow!("unsupported value {}, expected a string or integer", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Index;
+    use crate::PhoneData;
+
+    fn base() -> PhoneData {
+        PhoneData::from_index("2410".to_string(), "四川|成都|610000|028\0".as_bytes().to_vec(), vec![Index::new(1808683, 8, 3)])
+    }
+
+    const CSV: &str = "\
+# 人工修正
+key,city,zip_code,area_code,card_type,reason,source
+1808683,绵阳,621000,0816,,\"城市错误, 用户反馈\",TICKET-1
+18086830000,,,,中国移动,携号转网,TICKET-2
+";
+
+    #[test]
+    fn test_csv_overrides() {
+        let overlay = Overlay::parse_csv(CSV).unwrap();
+        assert_eq!(overlay.len(), 2);
+
+        let result = overlay.find(&base(), "18086834111").unwrap();
+        assert_eq!(result.info.city, "绵阳");
+        assert_eq!(result.info.province, "四川");
+        assert_eq!(result.info.card_type, "中国电信");
+        let applied = result.applied.unwrap();
+        assert_eq!(applied.level, OverrideLevel::Prefix);
+        assert_eq!(applied.reason, "城市错误, 用户反馈");
+
+        // 完整号码级覆盖优先，且不叠加号段级覆盖
+        let result = overlay.find(&base(), "18086830000").unwrap();
+        assert_eq!(result.info.city, "成都");
+        assert_eq!(result.info.card_type, "中国移动");
+        assert_eq!(result.applied.unwrap().level, OverrideLevel::Number);
+
+        // 未覆盖的号段保持原样，基础数据中不存在时仍报错
+        let empty = Overlay::default();
+        assert!(empty.find(&base(), "18086834111").unwrap().applied.is_none());
+        assert!(overlay.find(&base(), "15900000000").is_err());
+    }
+
+    #[test]
+    fn test_toml_overrides_and_complete_entries() {
+        let toml = r#"
+            # 新号段，上游数据尚未收录
+            [[override]]
+            key = "1990000"
+            province = "广东"
+            city = "广州"
+            zip_code = "510000"
+            area_code = "020"
+            card_type = 3
+            reason = "新放号 # 2024-10"
+            source = "运营商公告"
+        "#;
+        let overlay = Overlay::parse_toml(toml).unwrap();
+        let result = overlay.find(&base(), "19900001234").unwrap();
+        assert_eq!(result.info.city, "广州");
+        assert_eq!(result.info.card_type, "中国电信");
+        assert_eq!(result.applied.unwrap().reason, "新放号 # 2024-10");
+    }
+
+    #[test]
+    fn test_invalid_overlays() {
+        for csv in [
+            "key,city\n1808683,绵阳\n",
+            "key,city,reason\n180868,绵阳,x\n",
+            "key,city,reason\n1808683,绵阳,x\n1808683,德阳,y\n",
+            "key,reason\n1808683,x\n",
+            "key,card_type,reason\n1808683,中国邮政,x\n",
+            "key,town,reason\n1808683,x,y\n",
+            "key,city,reason\n1808683,\"绵阳,x\n",
+        ] {
+            assert!(Overlay::parse_csv(csv).is_err(), "{}", csv);
+        }
+        assert!(Overlay::parse_toml("key = \"1808683\"\n").is_err());
+        assert!(Overlay::parse_toml("[[override]]\nkey = 1808683\ncity = [\"x\"]\n").is_err());
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let overlay = Overlay::parse_csv(CSV).unwrap();
+        let other = Overlay::parse_csv(&CSV.replace("绵阳", "德阳")).unwrap();
+        assert_eq!(overlay.cache_key("15900000000"), None);
+        assert!(overlay.cache_key("18086834111").is_some());
+        assert_ne!(overlay.cache_key("18086834111"), overlay.cache_key("18086830000"));
+        assert_ne!(overlay.cache_key("18086834111"), other.cache_key("18086834111"));
+        // 同样的内容在不同实例、不同进程中得到同样的缓存键
+        assert_eq!(overlay.cache_key("18086834111"), Overlay::parse_csv(CSV).unwrap().cache_key("18086834111"));
+
+        // 号段下没有号码级覆盖时，同号段的号码共用缓存键
+        let prefix_only = Overlay::parse_csv("key,city,reason\n1808683,绵阳,x\n").unwrap();
+        assert_eq!(prefix_only.cache_key("18086834111"), prefix_only.cache_key("18086830000"));
+    }
+}
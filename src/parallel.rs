@@ -0,0 +1,221 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use anyhow::Result;
+use crate::common::{PhoneNoInfo, PhoneLookup};
+
+/// 并行批量查找配置
+#[derive(Debug, Clone)]
+pub struct ParallelConfig {
+    /// 工作线程数
+    pub threads: usize,
+    /// 每个任务块包含的号码数
+    pub chunk_size: usize,
+}
+
+impl Default for ParallelConfig {
+    fn default() -> Self {
+        ParallelConfig {
+            threads: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            chunk_size: 4096,
+        }
+    }
+}
+
+impl ParallelConfig {
+    /// 使用全部可用CPU核心和默认块大小
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置工作线程数，最少为1
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// 设置任务块大小，最少为1
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+}
+
+/// 多线程批量查找接口，为所有 `PhoneLookup + Sync` 的实现自动提供
+pub trait ParallelLookup: PhoneLookup + Sync {
+    /// 按块分发到多个线程并行查找，结果保持输入顺序
+    fn find_batch_parallel(&self, phones: &[&str], config: &ParallelConfig) -> Vec<Result<PhoneNoInfo>> {
+        let threads = config.threads.max(1);
+        let chunk_size = config.chunk_size.max(1);
+
+        if threads == 1 || phones.len() <= chunk_size {
+            return self.find_batch(phones);
+        }
+
+        let chunks: Vec<&[&str]> = phones.chunks(chunk_size).collect();
+        let next_chunk = AtomicUsize::new(0);
+
+        // 每个线程领取任务块并记录块序号，合并时按序号还原顺序
+        let mut finished: Vec<(usize, Vec<Result<PhoneNoInfo>>)> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads.min(chunks.len()))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let i = next_chunk.fetch_add(1, Ordering::Relaxed);
+                            if i >= chunks.len() {
+                                break;
+                            }
+                            done.push((i, self.find_batch(chunks[i])));
+                        }
+                        done
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("parallel lookup worker panicked"))
+                .collect()
+        });
+
+        finished.sort_unstable_by_key(|(i, _)| *i);
+        finished.into_iter().flat_map(|(_, results)| results).collect()
+    }
+
+    /// 流式并行查找：每次只读取一个窗口（线程数 × 块大小）的输入，
+    /// 不需要把全部输入和结果同时放在内存中
+    fn find_iter_parallel<I, S>(&self, phones: I, config: &ParallelConfig) -> ParallelBatchIter<'_, Self, I::IntoIter, S>
+    where
+        Self: Sized,
+        I: IntoIterator<Item = S>,
+        S: AsRef<str> + Sync,
+    {
+        ParallelBatchIter {
+            lookup: self,
+            input: phones.into_iter(),
+            config: config.clone(),
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl<T: PhoneLookup + Sync + ?Sized> ParallelLookup for T {}
+
+/// 流式并行查找迭代器，按输入顺序产出 `(号码, 查找结果)`
+pub struct ParallelBatchIter<'a, L, I, S>
+where
+    L: ParallelLookup,
+    I: Iterator<Item = S>,
+    S: AsRef<str> + Sync,
+{
+    lookup: &'a L,
+    input: I,
+    config: ParallelConfig,
+    pending: VecDeque<(S, Result<PhoneNoInfo>)>,
+}
+
+impl<L, I, S> ParallelBatchIter<'_, L, I, S>
+where
+    L: ParallelLookup,
+    I: Iterator<Item = S>,
+    S: AsRef<str> + Sync,
+{
+    /// 读取下一个窗口并行查找，输入耗尽时返回false
+    fn fill_window(&mut self) -> bool {
+        let window = self.config.threads.max(1) * self.config.chunk_size.max(1);
+        let inputs: Vec<S> = self.input.by_ref().take(window).collect();
+        if inputs.is_empty() {
+            return false;
+        }
+
+        let phones: Vec<&str> = inputs.iter().map(|phone| phone.as_ref()).collect();
+        let results = self.lookup.find_batch_parallel(&phones, &self.config);
+
+        self.pending.extend(inputs.into_iter().zip(results));
+        true
+    }
+}
+
+impl<L, I, S> Iterator for ParallelBatchIter<'_, L, I, S>
+where
+    L: ParallelLookup,
+    I: Iterator<Item = S>,
+    S: AsRef<str> + Sync,
+{
+    type Item = (S, Result<PhoneNoInfo>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pending.is_empty() && !self.fill_window() {
+            return None;
+        }
+        self.pending.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ErrorKind;
+
+    /// 把号码本身作为省份返回的测试实现，便于校验顺序
+    struct EchoLookup;
+
+    impl PhoneLookup for EchoLookup {
+        fn find(&self, no: &str) -> Result<PhoneNoInfo> {
+            if no.len() != 11 {
+                return Err(ErrorKind::InvalidLength.into());
+            }
+            Ok(PhoneNoInfo::new(no.to_string(), String::new(), String::new(), String::new(), String::new()))
+        }
+    }
+
+    fn phones(count: u64) -> Vec<String> {
+        (0..count)
+            .map(|i| if i % 13 == 0 { "1".to_string() } else { (13000000000 + i).to_string() })
+            .collect()
+    }
+
+    #[test]
+    fn test_parallel_preserves_order() {
+        let owned = phones(10_000);
+        let inputs: Vec<&str> = owned.iter().map(|phone| phone.as_str()).collect();
+
+        for (threads, chunk_size) in [(1, 100), (4, 1), (4, 97), (8, 4096), (3, 20_000)] {
+            let config = ParallelConfig::new().with_threads(threads).with_chunk_size(chunk_size);
+            let results = EchoLookup.find_batch_parallel(&inputs, &config);
+
+            assert_eq!(results.len(), inputs.len());
+            for (phone, result) in inputs.iter().zip(&results) {
+                match result {
+                    Ok(info) => assert_eq!(&info.province, phone),
+                    Err(_) => assert_eq!(*phone, "1"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_streaming_iterator_preserves_order() {
+        let owned = phones(5_000);
+        let config = ParallelConfig::new().with_threads(4).with_chunk_size(64);
+
+        let streamed: Vec<(String, Result<PhoneNoInfo>)> = EchoLookup.find_iter_parallel(owned.clone(), &config).collect();
+
+        assert_eq!(streamed.len(), owned.len());
+        for ((phone, result), expected) in streamed.iter().zip(&owned) {
+            assert_eq!(phone, expected);
+            match result {
+                Ok(info) => assert_eq!(&info.province, phone),
+                Err(_) => assert_eq!(phone, "1"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_zero_config_values_are_clamped() {
+        let config = ParallelConfig::new().with_threads(0).with_chunk_size(0);
+        assert_eq!(config.threads, 1);
+        assert_eq!(config.chunk_size, 1);
+        assert!(EchoLookup.find_batch_parallel(&[], &config).is_empty());
+    }
+}
@@ -1,152 +1,476 @@
-use std::fs::File;
-use std::io::{BufReader, Read};
-use anyhow::Result;
-use serde_derive::Serialize;
-use crate::common::{utils, PhoneNoInfo, ErrorKind, CardType, PhoneLookup, PhoneStats, Index};
+use std::path::Path;
+use anyhow::{anyhow, Result};
+use serde_derive::{Deserialize, Serialize};
+use crate::common::{utils, PhoneNoInfo, ErrorKind, CardType, PhoneLookup, PhoneStats, Index, LookupStats, DEFAULT_DATA_PATH, MERGE_BATCH_THRESHOLD};
 
 #[derive(Debug, Serialize)]
 pub struct PhoneDataBloom {
     version: String,
     records: Vec<u8>,
     index: Vec<Index>,
-    bloom_filter: BloomFilter,
+    #[serde(skip)]
+    filter: Box<dyn MembershipFilter>,
 }
 
+/// 成员过滤器类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterType {
+    /// 分块布隆过滤器，误报率可调
+    Bloom,
+    /// 静态异或过滤器（8位指纹），误报率约1/256，每个键约9.84位
+    Xor,
+}
+
+/// 成员过滤器通用接口：在二分查找前快速排除不存在的前缀
+///
+/// 可能误报，但不会漏报
+pub trait MembershipFilter: std::fmt::Debug + Send + Sync {
+    /// 前缀是否可能存在
+    fn contains(&self, item: i32) -> bool;
+
+    /// 过滤器类型
+    fn filter_type(&self) -> FilterType;
+
+    /// 过滤器总位数
+    fn bit_count(&self) -> usize;
+
+    /// 每次查询探测的位置数
+    fn hash_count(&self) -> u32;
+
+    /// 理论估算的误报率
+    fn false_positive_rate(&self) -> f64;
+
+    /// 已置1的位数
+    fn bits_set(&self) -> usize;
+
+    /// 过滤器占用的字节数
+    fn memory_usage_bytes(&self) -> usize {
+        self.bit_count() / 8
+    }
+}
+
+/// 默认误报率
+pub const DEFAULT_FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// 实测误报率时探测的前缀范围：130–199号段的全部7位前缀
+const DIAGNOSTIC_PREFIX_RANGE: std::ops::Range<i32> = 1_300_000..2_000_000;
+
+/// 每个块的位数，恰好占满一个64字节缓存行
+const BLOCK_BITS: usize = 512;
+const WORDS_PER_BLOCK: usize = BLOCK_BITS / 64;
+
+/// 块内各位置使用的奇数盐，个数即哈希函数个数的上限
+const BLOCK_SALTS: [u32; 16] = [
+    0x47b6137b, 0x44974d91, 0x8824ad5b, 0xa2b7289d, 0x705495c7, 0x2df1424b, 0x9efc4947, 0x5c6bfb31,
+    0x6a09e667, 0xbb67ae85, 0x3c6ef373, 0xa54ff53b, 0x510e527f, 0x9b05688d, 0x1f83d9ab, 0x5be0cd19,
+];
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[repr(C, align(64))]
+struct BloomBlock([u64; WORDS_PER_BLOCK]);
+
+/// 按缓存行分块的布隆过滤器
+///
+/// 每个元素只落在一个512位的块内：一次整数混合得到64位哈希，
+/// 高32位选块，低32位分别乘以k个奇数盐生成块内的k个位置，
+/// 查询最多只访问一个缓存行。
 #[derive(Debug, Serialize)]
 pub struct BloomFilter {
-    bits: Vec<u64>,
+    blocks: Vec<BloomBlock>,
     hash_count: u32,
     item_count: usize,
 }
 
 impl BloomFilter {
     pub fn new(expected_items: usize, false_positive_rate: f64) -> Self {
-        let bit_count = ((expected_items as f64) * false_positive_rate.ln() / (-2.0f64 * (2f64.ln()).powi(2))) as usize;
-        let hash_count = ((bit_count as f64 / expected_items as f64) * 2f64.ln()) as u32;
+        let expected_items = expected_items.max(1);
+        let false_positive_rate = false_positive_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+
+        // 先按经典公式估算，再逐步加大直到分块布局的误报率满足目标
+        let bit_count = (-(expected_items as f64) * false_positive_rate.ln() / (ln2 * ln2)).ceil() as usize;
+        let mut block_count = bit_count.div_ceil(BLOCK_BITS).max(1);
+        let mut hash_count = Self::optimal_hash_count(expected_items, block_count);
+        while Self::blocked_false_positive_rate(expected_items, block_count, hash_count) > false_positive_rate {
+            block_count += block_count / 32 + 1;
+            hash_count = Self::optimal_hash_count(expected_items, block_count);
+        }
 
         BloomFilter {
-            bits: vec![0u64; (bit_count + 63) / 64],
+            blocks: vec![BloomBlock([0u64; WORDS_PER_BLOCK]); block_count],
             hash_count,
             item_count: 0,
         }
     }
 
+    fn optimal_hash_count(items: usize, block_count: usize) -> u32 {
+        let bits_per_item = (block_count * BLOCK_BITS) as f64 / items as f64;
+        (bits_per_item * std::f64::consts::LN_2).round().clamp(1.0, BLOCK_SALTS.len() as f64) as u32
+    }
+
+    /// 分块布隆过滤器的误报率：块内元素数服从泊松分布，对每种负载的误报率加权求和
+    fn blocked_false_positive_rate(items: usize, block_count: usize, hash_count: u32) -> f64 {
+        let lambda = items as f64 / block_count as f64;
+        let k = hash_count as i32;
+        let miss_per_insert = 1.0 - 1.0 / BLOCK_BITS as f64;
+        let max_load = (lambda + 12.0 * lambda.sqrt() + 12.0) as usize;
+
+        let mut probability = (-lambda).exp();
+        let mut rate = 0.0;
+        for load in 0..=max_load {
+            if load > 0 {
+                probability *= lambda / load as f64;
+            }
+            rate += probability * (1.0 - miss_per_insert.powi(k * load as i32)).powi(k);
+        }
+        rate
+    }
+
     pub fn insert(&mut self, item: i32) {
-        for i in 0..self.hash_count {
-            let hash = self.hash(item, i);
-            let bit_index = (hash % (self.bits.len() as u64 * 64)) as usize;
-            let array_index = bit_index / 64;
-            let bit_offset = bit_index % 64;
-            self.bits[array_index] |= 1u64 << bit_offset;
+        let (block, hash) = self.locate(item);
+        let block = &mut self.blocks[block].0;
+        for salt in &BLOCK_SALTS[..self.hash_count as usize] {
+            let bit = Self::bit_in_block(hash, *salt);
+            block[bit / 64] |= 1u64 << (bit % 64);
         }
         self.item_count += 1;
     }
 
+    #[inline]
     pub fn contains(&self, item: i32) -> bool {
-        for i in 0..self.hash_count {
-            let hash = self.hash(item, i);
-            let bit_index = (hash % (self.bits.len() as u64 * 64)) as usize;
-            let array_index = bit_index / 64;
-            let bit_offset = bit_index % 64;
-
-            if (self.bits[array_index] & (1u64 << bit_offset)) == 0 {
-                return false;
-            }
+        let (block, hash) = self.locate(item);
+        let block = unsafe { &self.blocks.get_unchecked(block).0 };
+        let mut present = true;
+        for salt in &BLOCK_SALTS[..self.hash_count as usize] {
+            let bit = Self::bit_in_block(hash, *salt);
+            present &= (block[bit / 64] >> (bit % 64)) & 1 == 1;
         }
-        true
+        present
     }
 
-    fn hash(&self, item: i32, seed: u32) -> u64 {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
+    /// 计算块下标和块内位置使用的32位哈希
+    #[inline]
+    fn locate(&self, item: i32) -> (usize, u32) {
+        let hash = mix64(item as u32 as u64);
+        // 乘法取高位代替取模，把高32位映射到 [0, 块数)
+        let block = (((hash >> 32) * self.blocks.len() as u64) >> 32) as usize;
+        (block, hash as u32)
+    }
 
-        let mut hasher = DefaultHasher::new();
-        item.hash(&mut hasher);
-        seed.hash(&mut hasher);
-        hasher.finish()
+    /// 块内位置：32位哈希乘以奇数盐后取高9位
+    ///
+    /// 每个位置用独立的盐，避免双重哈希的等差序列让不同元素的位置整体重合，
+    /// 实测误报率能贴近理论值
+    #[inline]
+    fn bit_in_block(hash: u32, salt: u32) -> usize {
+        (hash.wrapping_mul(salt) >> (32 - BLOCK_BITS.trailing_zeros())) as usize
     }
 
+    /// 按分块布局估算的误报率
     pub fn false_positive_rate(&self) -> f64 {
         if self.item_count == 0 {
             return 0.0;
         }
 
-        let bits_per_item = (self.bits.len() * 64) as f64 / self.item_count as f64;
-        (1.0 - (-1.0 / bits_per_item).exp()).powi(self.hash_count as i32)
+        Self::blocked_false_positive_rate(self.item_count, self.blocks.len(), self.hash_count)
+    }
+
+    /// 位图总位数
+    pub fn bit_count(&self) -> usize {
+        self.blocks.len() * BLOCK_BITS
+    }
+
+    /// 哈希函数个数
+    pub fn hash_count(&self) -> u32 {
+        self.hash_count
+    }
+
+    /// 位图占用的字节数
+    pub fn memory_usage_bytes(&self) -> usize {
+        self.blocks.len() * std::mem::size_of::<BloomBlock>()
     }
 }
 
-impl PhoneDataBloom {
-    pub fn new() -> Result<PhoneDataBloom> {
-        let data_file = File::open("phone.dat")?;
-        let mut data_file = BufReader::new(data_file);
-
-        // 解析版本号和索引偏移
-        let mut header_buffer = [0u8; 8];
-        data_file.read_exact(&mut header_buffer)?;
-        let version = String::from_utf8((&header_buffer[..4]).to_vec())?;
-        let index_offset = utils::four_u8_to_i32(&header_buffer[4..]) as u64;
-
-        // 读取记录区
-        let mut records = vec![0u8; index_offset as usize - 8];
-        data_file.read_exact(&mut records)?;
-
-        // 解析索引区并构建布隆过滤器
-        let mut index = Vec::new();
-        let mut index_item = [0u8; 9];
-        let mut bloom_filter = BloomFilter::new(517258, 0.01); // 1% 误报率
-
-        loop {
-            match data_file.read_exact(&mut index_item) {
-                Ok(_) => (),
-                Err(e) => match e.kind() {
-                    std::io::ErrorKind::UnexpectedEof => break,
-                    _ => (),
-                },
+impl MembershipFilter for BloomFilter {
+    fn contains(&self, item: i32) -> bool {
+        BloomFilter::contains(self, item)
+    }
+
+    fn filter_type(&self) -> FilterType {
+        FilterType::Bloom
+    }
+
+    fn bit_count(&self) -> usize {
+        BloomFilter::bit_count(self)
+    }
+
+    fn hash_count(&self) -> u32 {
+        BloomFilter::hash_count(self)
+    }
+
+    fn false_positive_rate(&self) -> f64 {
+        BloomFilter::false_positive_rate(self)
+    }
+
+    fn bits_set(&self) -> usize {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.0.iter())
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    fn memory_usage_bytes(&self) -> usize {
+        BloomFilter::memory_usage_bytes(self)
+    }
+}
+
+/// 异或过滤器构建时最多尝试的种子数，容量系数1.23下单个种子的失败概率很低
+const XOR_MAX_SEED_ATTEMPTS: usize = 64;
+
+/// 静态异或过滤器（Xor8）
+///
+/// 键集合加载后不再变化，可以一次性构建：每个键映射到三段中各一个槽位，
+/// 三个槽位的8位指纹异或等于键的指纹。查询只探测三个位置，
+/// 在相同误报率下比布隆过滤器更省内存。
+#[derive(Debug, Serialize)]
+pub struct XorFilter {
+    seed: u64,
+    block_length: usize,
+    fingerprints: Vec<u8>,
+}
+
+impl XorFilter {
+    /// 由键集合构建，键先排序去重；换种子仍无法剥离时返回错误
+    pub fn new(keys: &[i32]) -> Result<Self> {
+        let mut keys = keys.to_vec();
+        keys.sort_unstable();
+        keys.dedup();
+
+        let capacity = 32 + (1.23 * keys.len() as f64).ceil() as usize;
+        let block_length = capacity.div_ceil(3);
+        let slots = block_length * 3;
+        let mut seed = 0x243f6a8885a308d3u64;
+
+        for _ in 0..XOR_MAX_SEED_ATTEMPTS {
+            if let Some(fingerprints) = Self::try_build(&keys, seed, block_length, slots) {
+                return Ok(XorFilter {
+                    seed,
+                    block_length,
+                    fingerprints,
+                });
+            }
+            // 出现无法剥离的环，换一个种子重试
+            seed = mix64(seed);
+        }
+
+        Err(anyhow!("xor filter construction failed after {} seeds", XOR_MAX_SEED_ATTEMPTS))
+    }
+
+    /// 剥离法构建：反复取出只被一个键占用的槽位，再逆序写入指纹
+    fn try_build(keys: &[i32], seed: u64, block_length: usize, slots: usize) -> Option<Vec<u8>> {
+        let mut counts = vec![0u32; slots];
+        let mut xor_hashes = vec![0u64; slots];
+
+        for &key in keys {
+            let hash = mix64(key as u32 as u64 ^ seed);
+            for slot in Self::slots_of(hash, block_length) {
+                counts[slot] += 1;
+                xor_hashes[slot] ^= hash;
+            }
+        }
+
+        let mut queue: Vec<usize> = (0..slots).filter(|&slot| counts[slot] == 1).collect();
+        let mut stack: Vec<(u64, usize)> = Vec::with_capacity(keys.len());
+
+        while let Some(slot) = queue.pop() {
+            if counts[slot] != 1 {
+                continue;
+            }
+            let hash = xor_hashes[slot];
+            stack.push((hash, slot));
+            for other in Self::slots_of(hash, block_length) {
+                counts[other] -= 1;
+                xor_hashes[other] ^= hash;
+                if counts[other] == 1 {
+                    queue.push(other);
+                }
             }
+        }
+
+        if stack.len() != keys.len() {
+            return None;
+        }
+
+        let mut fingerprints = vec![0u8; slots];
+        for &(hash, slot) in stack.iter().rev() {
+            let [a, b, c] = Self::slots_of(hash, block_length);
+            fingerprints[slot] = 0;
+            fingerprints[slot] = Self::fingerprint(hash) ^ fingerprints[a] ^ fingerprints[b] ^ fingerprints[c];
+        }
+
+        Some(fingerprints)
+    }
+
+    /// 三段中各取一个槽位
+    #[inline]
+    fn slots_of(hash: u64, block_length: usize) -> [usize; 3] {
+        let reduce = |h: u64| (((h as u32 as u64) * block_length as u64) >> 32) as usize;
+        [
+            reduce(hash),
+            block_length + reduce(hash.rotate_left(21)),
+            2 * block_length + reduce(hash.rotate_left(42)),
+        ]
+    }
+
+    #[inline]
+    fn fingerprint(hash: u64) -> u8 {
+        (hash ^ (hash >> 32)) as u8
+    }
+}
+
+impl MembershipFilter for XorFilter {
+    #[inline]
+    fn contains(&self, item: i32) -> bool {
+        let hash = mix64(item as u32 as u64 ^ self.seed);
+        let [a, b, c] = Self::slots_of(hash, self.block_length);
+        let fingerprints = &self.fingerprints;
+        Self::fingerprint(hash) == unsafe {
+            fingerprints.get_unchecked(a) ^ fingerprints.get_unchecked(b) ^ fingerprints.get_unchecked(c)
+        }
+    }
+
+    fn filter_type(&self) -> FilterType {
+        FilterType::Xor
+    }
+
+    fn bit_count(&self) -> usize {
+        self.fingerprints.len() * 8
+    }
 
-            let phone_no_prefix = utils::four_u8_to_i32(&index_item[..4]);
-            let records_offset = utils::four_u8_to_i32(&index_item[4..8]);
-            let card_type = index_item[8];
+    fn hash_count(&self) -> u32 {
+        3
+    }
+
+    fn false_positive_rate(&self) -> f64 {
+        1.0 / 256.0
+    }
 
-            // 添加到布隆过滤器
-            bloom_filter.insert(phone_no_prefix);
+    fn bits_set(&self) -> usize {
+        self.fingerprints.iter().map(|fingerprint| fingerprint.count_ones() as usize).sum()
+    }
+}
+
+/// 64位整数混合函数（MurmurHash3的fmix64）
+#[inline]
+fn mix64(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51afd7ed558ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
+    h ^= h >> 33;
+    h
+}
 
-            index.push(Index {
-                phone_no_prefix,
-                records_offset,
-                card_type,
-            });
+/// PhoneDataBloom 构建器
+#[derive(Debug, Clone)]
+pub struct PhoneDataBloomBuilder {
+    filter_type: FilterType,
+    false_positive_rate: f64,
+}
+
+impl Default for PhoneDataBloomBuilder {
+    fn default() -> Self {
+        PhoneDataBloomBuilder {
+            filter_type: FilterType::Bloom,
+            false_positive_rate: DEFAULT_FALSE_POSITIVE_RATE,
         }
+    }
+}
+
+impl PhoneDataBloomBuilder {
+    /// 选择成员过滤器类型，默认为布隆过滤器
+    pub fn filter_type(mut self, filter_type: FilterType) -> Self {
+        self.filter_type = filter_type;
+        self
+    }
+
+    /// 设置布隆过滤器的目标误报率，异或过滤器的误报率固定为1/256
+    pub fn false_positive_rate(mut self, rate: f64) -> Self {
+        self.false_positive_rate = rate;
+        self
+    }
+
+    /// 从默认路径加载数据并构建
+    pub fn build(self) -> Result<PhoneDataBloom> {
+        self.build_from_path(DEFAULT_DATA_PATH)
+    }
+
+    /// 从指定路径加载数据并构建
+    pub fn build_from_path<P: AsRef<Path>>(self, path: P) -> Result<PhoneDataBloom> {
+        let (header, records, index) = utils::read_database(path)?;
+        let version = header.version;
+
+        // 按实际记录数构建成员过滤器
+        let filter: Box<dyn MembershipFilter> = match self.filter_type {
+            FilterType::Bloom => {
+                let mut bloom_filter = BloomFilter::new(index.len(), self.false_positive_rate);
+                for item in &index {
+                    bloom_filter.insert(item.phone_no_prefix);
+                }
+                Box::new(bloom_filter)
+            }
+            FilterType::Xor => {
+                let keys: Vec<i32> = index.iter().map(|item| item.phone_no_prefix).collect();
+                Box::new(XorFilter::new(&keys)?)
+            }
+        };
 
         Ok(PhoneDataBloom {
             version,
             records,
             index,
-            bloom_filter,
+            filter,
         })
     }
+}
+
+impl PhoneDataBloom {
+    pub fn new() -> Result<PhoneDataBloom> {
+        Self::builder().build()
+    }
+
+    /// 以默认参数从指定路径加载数据
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<PhoneDataBloom> {
+        Self::builder().build_from_path(path)
+    }
+
+    /// 创建构建器，用于配置误报率等参数
+    pub fn builder() -> PhoneDataBloomBuilder {
+        PhoneDataBloomBuilder::default()
+    }
 
     /// 布隆过滤器优化的查找 - 先快速过滤，再精确查找
     pub fn find(&self, no: &str) -> Result<PhoneNoInfo> {
         let len = no.len();
-        if len < 7 || len > 11 {
+        if !(7..=11).contains(&len) {
             return Err(ErrorKind::InvalidLength.into());
         }
 
         let phone_prefix = if len == 7 {
             no.parse::<i32>()?
         } else {
-            no[..7].parse::<i32>()?
+            no.get(..7).ok_or(ErrorKind::InvalidLength)?.parse::<i32>()?
         };
 
         // 快速布隆过滤器检查
-        if !self.bloom_filter.contains(phone_prefix) {
+        if !self.filter.contains(phone_prefix) {
             return Err(ErrorKind::NotFound.into());
         }
 
         // 布隆过滤器说可能存在，进行精确二分查找
-        let result = self.binary_search(phone_prefix);
+        let result = self.binary_search(phone_prefix, &mut 0);
 
         match result {
             Some(index) => {
@@ -164,112 +488,46 @@ impl PhoneDataBloom {
         }
     }
 
-    /// 批量查找优化 - 利用布隆过滤器快速排除不存在的号码
+    /// 批量查找优化 - 利用布隆过滤器快速排除不存在的号码，大批量时剩余号码排序后归并查找
     pub fn find_batch(&self, phones: &[&str]) -> Vec<Result<PhoneNoInfo>> {
-        phones.iter().map(|phone| {
-            let len = phone.len();
-            if len < 7 || len > 11 {
-                return Err(ErrorKind::InvalidLength.into());
-            }
-
-            let phone_prefix = if len == 7 {
-                phone.parse::<i32>()?
-            } else {
-                phone[..7].parse::<i32>()?
-            };
-
-            // 快速布隆过滤器检查
-            if !self.bloom_filter.contains(phone_prefix) {
-                return Err(ErrorKind::NotFound.into());
-            }
+        if phones.len() < MERGE_BATCH_THRESHOLD {
+            return phones.iter().map(|phone| self.find(phone)).collect();
+        }
 
-            // 精确查找
-            match self.binary_search(phone_prefix) {
-                Some(index) => {
-                    let record = utils::parse_record_data(&self.records, index.records_offset as usize)?;
-                    let card_type = CardType::from_u8(index.card_type)?;
-                    Ok(PhoneNoInfo {
-                        province: record.province,
-                        city: record.city,
-                        zip_code: record.zip_code,
-                        area_code: record.area_code,
-                        card_type: card_type.get_description(),
-                    })
-                }
-                None => Err(ErrorKind::NotFound.into()),
-            }
-        }).collect()
+        utils::merge_find_batch_filtered(
+            phones,
+            self.index.len(),
+            |prefix| self.filter.contains(prefix),
+            |i| self.index[i].phone_no_prefix,
+            |i| {
+                let record = utils::parse_record_data(&self.records, self.index[i].records_offset as usize)?;
+                utils::build_phone_info(&record, self.index[i].card_type)
+            },
+        )
     }
 
-    /// 统计查找效率
+    /// 分阶段计时的查找，被过滤器排除的号码结果为FilteredOut
     pub fn find_with_stats(&self, no: &str) -> (Result<PhoneNoInfo>, LookupStats) {
-        let start = std::time::Instant::now();
-
-        let len = no.len();
-        if len < 7 || len > 11 {
-            return (Err(ErrorKind::InvalidLength.into()), LookupStats {
-                bloom_filter_time: start.elapsed(),
-                binary_search_time: std::time::Duration::from_nanos(0),
-                bloom_positive: false,
-                found: false,
-            });
-        }
-
-        let phone_prefix = if len == 7 {
-            no.parse::<i32>().unwrap_or(0)
-        } else {
-            no[..7].parse::<i32>().unwrap_or(0)
-        };
-
-        // 布隆过滤器检查
-        let bloom_start = std::time::Instant::now();
-        let bloom_positive = self.bloom_filter.contains(phone_prefix);
-        let bloom_time = bloom_start.elapsed();
-
-        if !bloom_positive {
-            return (Err(ErrorKind::NotFound.into()), LookupStats {
-                bloom_filter_time: bloom_time,
-                binary_search_time: std::time::Duration::from_nanos(0),
-                bloom_positive: false,
-                found: false,
-            });
-        }
-
-        // 二分查找
-        let binary_start = std::time::Instant::now();
-        let result = match self.binary_search(phone_prefix) {
-            Some(index) => {
-                let record = utils::parse_record_data(&self.records, index.records_offset as usize).unwrap();
-                let card_type = CardType::from_u8(index.card_type).unwrap();
-                Ok(PhoneNoInfo {
-                    province: record.province,
-                    city: record.city,
-                    zip_code: record.zip_code,
-                    area_code: record.area_code,
-                    card_type: card_type.get_description(),
-                })
-            }
-            None => Err(ErrorKind::NotFound.into()),
-        };
-        let binary_time = binary_start.elapsed();
-        let found = result.is_ok();
-
-        (result, LookupStats {
-            bloom_filter_time: bloom_time,
-            binary_search_time: binary_time,
-            bloom_positive,
-            found,
-        })
+        utils::instrumented_find_filtered(
+            no,
+            Some(|prefix| self.filter.contains(prefix)),
+            |prefix, probes| self.binary_search(prefix, probes),
+            |index| {
+                let record = utils::parse_record_data(&self.records, index.records_offset as usize)?;
+                utils::build_phone_info(&record, index.card_type)
+            },
+        )
     }
 
     #[inline]
-    fn binary_search(&self, target: i32) -> Option<&Index> {
+    fn binary_search(&self, target: i32, probes: &mut u32) -> Option<&Index> {
         let mut left = 0usize;
         let mut right = self.index.len();
 
         while left < right {
             let mid = left + ((right - left) >> 1);
             let mid_index = unsafe { self.index.get_unchecked(mid) };
+            *probes += 1;
 
             match mid_index.phone_no_prefix.cmp(&target) {
                 std::cmp::Ordering::Equal => return Some(mid_index),
@@ -281,60 +539,134 @@ impl PhoneDataBloom {
         None
     }
 
-    /// 获取统计信息
+    /// 获取统计信息，不做探测，实测误报率为空
     pub fn stats(&self) -> BloomStats {
+        let bit_count = self.filter.bit_count();
+        let bits_set = self.filter.bits_set();
         BloomStats {
             total_entries: self.index.len(),
             version: self.version.clone(),
-            bloom_filter_bits: self.bloom_filter.bits.len() * 64,
-            bloom_filter_hash_count: self.bloom_filter.hash_count,
-            estimated_false_positive_rate: self.bloom_filter.false_positive_rate(),
-            memory_usage_bytes: self.records.len() +
-                self.index.len() * std::mem::size_of::<Index>() +
-                self.bloom_filter.bits.len() * std::mem::size_of::<u64>(),
+            filter_type: self.filter.filter_type(),
+            bloom_filter_bits: bit_count,
+            bloom_filter_hash_count: self.filter.hash_count(),
+            bits_per_key: bit_count as f64 / self.index.len().max(1) as f64,
+            estimated_false_positive_rate: self.filter.false_positive_rate(),
+            measured_false_positive_rate: None,
+            bits_set,
+            fill_ratio: if bit_count == 0 { 0.0 } else { bits_set as f64 / bit_count as f64 },
+            memory_usage_bytes: self.memory_usage_bytes(),
         }
     }
-}
 
-#[derive(Debug, Serialize)]
-pub struct LookupStats {
-    pub bloom_filter_time: std::time::Duration,
-    pub binary_search_time: std::time::Duration,
-    pub bloom_positive: bool,
-    pub found: bool,
+    /// 获取统计信息并运行`diagnostics`填入实测误报率，需要探测约70万个前缀
+    pub fn stats_with_diagnostics(&self) -> BloomStats {
+        BloomStats {
+            measured_false_positive_rate: Some(self.diagnostics().measured_false_positive_rate),
+            ..self.stats()
+        }
+    }
+
+    /// 过滤器诊断：用130–199号段中每一个不存在的7位前缀探测过滤器，得到真实误报率
+    pub fn diagnostics(&self) -> FilterDiagnostics {
+        let mut probed_prefixes = 0usize;
+        let mut false_positives = 0usize;
+
+        // 索引有序，游标随前缀单调前进即可跳过存在的前缀
+        let start = self.index.partition_point(|item| item.phone_no_prefix < DIAGNOSTIC_PREFIX_RANGE.start);
+        let mut members = self.index[start..].iter().map(|item| item.phone_no_prefix).peekable();
+
+        for prefix in DIAGNOSTIC_PREFIX_RANGE {
+            if members.next_if_eq(&prefix).is_some() {
+                continue;
+            }
+            probed_prefixes += 1;
+            if self.filter.contains(prefix) {
+                false_positives += 1;
+            }
+        }
+
+        let bit_count = self.filter.bit_count();
+        let bits_set = self.filter.bits_set();
+
+        FilterDiagnostics {
+            filter_type: self.filter.filter_type(),
+            probed_prefixes,
+            false_positives,
+            measured_false_positive_rate: if probed_prefixes == 0 {
+                0.0
+            } else {
+                false_positives as f64 / probed_prefixes as f64
+            },
+            estimated_false_positive_rate: self.filter.false_positive_rate(),
+            bit_count,
+            bits_set,
+            fill_ratio: if bit_count == 0 { 0.0 } else { bits_set as f64 / bit_count as f64 },
+        }
+    }
+
+    /// 当前使用的成员过滤器
+    pub fn filter(&self) -> &dyn MembershipFilter {
+        self.filter.as_ref()
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct BloomStats {
     pub total_entries: usize,
     pub version: String,
+    /// 成员过滤器类型
+    pub filter_type: FilterType,
     pub bloom_filter_bits: usize,
+    /// 每次查询探测的位置数（异或过滤器固定为3）
     pub bloom_filter_hash_count: u32,
+    /// 每个键占用的位数
+    pub bits_per_key: f64,
     pub estimated_false_positive_rate: f64,
+    /// 探测130–199号段全部不存在前缀得到的实测误报率，只有`stats_with_diagnostics`会填入
+    pub measured_false_positive_rate: Option<f64>,
+    /// 已置1的位数
+    pub bits_set: usize,
+    /// 置1位所占比例
+    pub fill_ratio: f64,
     pub memory_usage_bytes: usize,
 }
 
+/// 成员过滤器诊断结果
+#[derive(Debug, Clone, Serialize)]
+pub struct FilterDiagnostics {
+    pub filter_type: FilterType,
+    /// 探测的不存在前缀个数
+    pub probed_prefixes: usize,
+    /// 被过滤器误判为存在的个数
+    pub false_positives: usize,
+    pub measured_false_positive_rate: f64,
+    pub estimated_false_positive_rate: f64,
+    pub bit_count: usize,
+    pub bits_set: usize,
+    pub fill_ratio: f64,
+}
+
 
 impl PhoneLookup for PhoneDataBloom {
     fn find(&self, no: &str) -> Result<PhoneNoInfo> {
         let len = no.len();
-        if len < 7 || len > 11 {
+        if !(7..=11).contains(&len) {
             return Err(ErrorKind::InvalidLength.into());
         }
 
         let phone_prefix = if len == 7 {
             no.parse::<i32>()?
         } else {
-            no[..7].parse::<i32>()?
+            no.get(..7).ok_or(ErrorKind::InvalidLength)?.parse::<i32>()?
         };
 
         // 快速布隆过滤器检查
-        if !self.bloom_filter.contains(phone_prefix) {
+        if !self.filter.contains(phone_prefix) {
             return Err(ErrorKind::NotFound.into());
         }
 
         // 布隆过滤器说可能存在，进行精确二分查找
-        let result = self.binary_search(phone_prefix);
+        let result = self.binary_search(phone_prefix, &mut 0);
 
         match result {
             Some(index) => {
@@ -351,6 +683,14 @@ impl PhoneLookup for PhoneDataBloom {
             None => Err(ErrorKind::NotFound.into()),
         }
     }
+
+    fn find_batch(&self, phones: &[&str]) -> Vec<Result<PhoneNoInfo>> {
+        PhoneDataBloom::find_batch(self, phones)
+    }
+
+    fn find_with_stats(&self, no: &str) -> (Result<PhoneNoInfo>, LookupStats) {
+        PhoneDataBloom::find_with_stats(self, no)
+    }
 }
 
 impl PhoneStats for PhoneDataBloom {
@@ -365,7 +705,7 @@ impl PhoneStats for PhoneDataBloom {
     fn memory_usage_bytes(&self) -> usize {
         self.records.len() +
         self.index.len() * std::mem::size_of::<Index>() +
-        self.bloom_filter.bits.len() * std::mem::size_of::<u64>()
+        self.filter.memory_usage_bytes()
     }
 }
 
@@ -388,4 +728,100 @@ mod tests {
         let result = phone_data.find("99999999999");
         assert!(result.is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_bloom_filter_no_false_negatives() {
+        let mut filter = BloomFilter::new(10_000, 0.01);
+        for item in (1300000..1400000).step_by(10) {
+            filter.insert(item);
+        }
+        for item in (1300000..1400000).step_by(10) {
+            assert!(filter.contains(item), "false negative for {}", item);
+        }
+    }
+
+    #[test]
+    fn test_bloom_filter_false_positive_rate() {
+        for rate in [0.05, 0.01, 0.001] {
+            let mut filter = BloomFilter::new(50_000, rate);
+            for item in (1300000..1800000).step_by(10) {
+                filter.insert(item);
+            }
+            let false_positives = (1300000..1800000)
+                .filter(|item| item % 10 != 0 && filter.contains(*item))
+                .count();
+            let measured = false_positives as f64 / 450_000.0;
+            assert!(measured < rate * 1.3, "rate {} measured {}", rate, measured);
+        }
+    }
+
+    #[test]
+    fn test_builder_false_positive_rate() {
+        let loose = PhoneDataBloom::builder().false_positive_rate(0.05).build().unwrap();
+        let strict = PhoneDataBloom::builder().false_positive_rate(0.001).build().unwrap();
+        assert!(strict.stats().bloom_filter_bits > loose.stats().bloom_filter_bits);
+        assert!(strict.find("18086834111").is_ok());
+    }
+
+    #[test]
+    fn test_xor_filter_no_false_negatives() {
+        let keys: Vec<i32> = (1300000..1800000).step_by(10).collect();
+        let filter = XorFilter::new(&keys).unwrap();
+        for &key in &keys {
+            assert!(filter.contains(key), "false negative for {}", key);
+        }
+
+        let false_positives = (1300000..1800000)
+            .filter(|item| item % 10 != 0 && filter.contains(*item))
+            .count();
+        let measured = false_positives as f64 / 450_000.0;
+        assert!(measured < 2.0 / 256.0, "measured {}", measured);
+        assert!((filter.bit_count() as f64 / keys.len() as f64) < 10.5);
+    }
+
+    #[test]
+    fn test_xor_filter_duplicate_keys() {
+        // 重复的键会形成无法剥离的环，构建前需要去重
+        let mut keys: Vec<i32> = (1300000..1400000).step_by(7).collect();
+        keys.extend_from_slice(&keys.clone()[..100]);
+        let filter = XorFilter::new(&keys).unwrap();
+        assert!(keys.iter().all(|&key| filter.contains(key)));
+        assert!(XorFilter::new(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_xor_filter_lookup() {
+        // 相同误报率下异或过滤器应更省空间
+        let bloom_data = PhoneDataBloom::builder().false_positive_rate(1.0 / 256.0).build().unwrap();
+        let xor_data = PhoneDataBloom::builder().filter_type(FilterType::Xor).build().unwrap();
+        assert_eq!(xor_data.stats().filter_type, FilterType::Xor);
+        assert!(xor_data.stats().bits_per_key < bloom_data.stats().bits_per_key);
+        assert!(xor_data.diagnostics().measured_false_positive_rate < 2.0 / 256.0);
+
+        for prefix in (1300000..2000000).step_by(11) {
+            let phone = prefix.to_string();
+            assert_eq!(bloom_data.find(&phone).is_ok(), xor_data.find(&phone).is_ok(), "mismatch for {}", phone);
+        }
+    }
+
+    #[test]
+    fn test_diagnostics_probe_every_missing_prefix() {
+        let phone_data = PhoneDataBloom::new().unwrap();
+        let diagnostics = phone_data.diagnostics();
+
+        let members = phone_data
+            .index
+            .iter()
+            .filter(|item| DIAGNOSTIC_PREFIX_RANGE.contains(&item.phone_no_prefix))
+            .count();
+        assert_eq!(diagnostics.probed_prefixes + members, DIAGNOSTIC_PREFIX_RANGE.len());
+        assert!(diagnostics.false_positives <= diagnostics.probed_prefixes);
+        assert!(diagnostics.bits_set <= diagnostics.bit_count);
+        assert!(diagnostics.fill_ratio > 0.0 && diagnostics.fill_ratio < 1.0);
+
+        assert_eq!(phone_data.stats().measured_false_positive_rate, None);
+        let stats = phone_data.stats_with_diagnostics();
+        assert_eq!(stats.measured_false_positive_rate, Some(diagnostics.measured_false_positive_rate));
+        assert_eq!(stats.bits_set, diagnostics.bits_set);
+    }
+}
@@ -0,0 +1,216 @@
+use std::path::Path;
+use anyhow::Result;
+use serde_derive::Serialize;
+use crate::common::{utils, PhoneNoInfo, ErrorKind, PhoneLookup, PhoneStats, Index, LookupStats, DEFAULT_DATA_PATH};
+
+/// 每个缓存行可容纳的键数量（64字节 / 4字节）
+const KEYS_PER_LINE: usize = 16;
+
+/// 按缓存行对齐的键块，保证 `k * 16` 处的后代恰好落在同一缓存行
+#[derive(Debug, Clone, Copy, Serialize)]
+#[repr(C, align(64))]
+struct KeyLine([i32; KEYS_PER_LINE]);
+
+/// Eytzinger（BFS）布局的手机号数据
+///
+/// 键按完全二叉树的层序存放（下标从1开始），节点k的子节点为2k和2k+1，
+/// 前几层集中在少数缓存行中；查找时预取4层之后的后代所在缓存行，
+/// 把相互依赖的缓存未命中变成可以并行的内存请求。
+#[derive(Debug, Serialize)]
+pub struct PhoneDataEytzinger {
+    version: String,
+    records: Vec<u8>,
+    /// Eytzinger顺序的键，下标0为占位
+    keys: Vec<KeyLine>,
+    /// 与keys同序的记录区偏移
+    records_offsets: Vec<i32>,
+    /// 与keys同序的卡类型
+    card_types: Vec<u8>,
+    /// 有效记录数
+    len: usize,
+}
+
+impl PhoneDataEytzinger {
+    pub fn new() -> Result<PhoneDataEytzinger> {
+        Self::from_path(DEFAULT_DATA_PATH)
+    }
+
+    /// 从指定路径加载数据
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<PhoneDataEytzinger> {
+        let (header, records, index) = utils::read_database(path)?;
+        Ok(Self::from_index(header.version, records, &index))
+    }
+
+    /// 由有序索引构建Eytzinger布局
+    fn from_index(version: String, records: Vec<u8>, index: &[Index]) -> PhoneDataEytzinger {
+        let len = index.len();
+        let lines = (len + 1).div_ceil(KEYS_PER_LINE);
+        let mut keys = vec![KeyLine([i32::MAX; KEYS_PER_LINE]); lines];
+        let mut records_offsets = vec![0i32; len + 1];
+        let mut card_types = vec![0u8; len + 1];
+
+        let mut next = 0usize;
+        Self::fill(index, 1, &mut next, &mut keys, &mut records_offsets, &mut card_types);
+
+        PhoneDataEytzinger {
+            version,
+            records,
+            keys,
+            records_offsets,
+            card_types,
+            len,
+        }
+    }
+
+    /// 中序遍历隐式二叉树，把有序索引依次写入对应节点
+    fn fill(
+        index: &[Index],
+        k: usize,
+        next: &mut usize,
+        keys: &mut [KeyLine],
+        records_offsets: &mut [i32],
+        card_types: &mut [u8],
+    ) {
+        if k > index.len() {
+            return;
+        }
+        Self::fill(index, 2 * k, next, keys, records_offsets, card_types);
+
+        let item = &index[*next];
+        keys[k / KEYS_PER_LINE].0[k % KEYS_PER_LINE] = item.phone_no_prefix;
+        records_offsets[k] = item.records_offset;
+        card_types[k] = item.card_type;
+        *next += 1;
+
+        Self::fill(index, 2 * k + 1, next, keys, records_offsets, card_types);
+    }
+
+    /// 以连续i32切片的形式访问键数组
+    #[inline]
+    fn key_slice(&self) -> &[i32] {
+        // KeyLine为repr(C)的i32数组，内存布局与连续的i32相同
+        unsafe { std::slice::from_raw_parts(self.keys.as_ptr() as *const i32, self.keys.len() * KEYS_PER_LINE) }
+    }
+
+    /// Eytzinger查找：返回命中节点的下标
+    #[inline]
+    fn eytzinger_search(&self, target: i32, probes: &mut u32) -> Option<usize> {
+        let keys = self.key_slice();
+        let mut k = 1usize;
+
+        while k <= self.len {
+            *probes += 1;
+            // 预取4层之后的16个后代，它们恰好占满一个缓存行
+            self.prefetch_line(k * KEYS_PER_LINE);
+            let key = unsafe { *keys.get_unchecked(k) };
+            k = 2 * k + (key < target) as usize;
+        }
+
+        // 去掉末尾连续向右走的路径，得到第一个不小于目标的节点
+        k >>= k.trailing_ones() + 1;
+
+        if k != 0 && keys[k] == target { Some(k) } else { None }
+    }
+
+    #[inline]
+    fn prefetch_line(&self, _idx: usize) {
+        let ptr = (self.keys.as_ptr() as *const i32).wrapping_add(_idx);
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            core::arch::x86_64::_mm_prefetch(ptr as *const i8, core::arch::x86_64::_MM_HINT_T0);
+        }
+        #[cfg(target_arch = "x86")]
+        unsafe {
+            core::arch::x86::_mm_prefetch(ptr as *const i8, core::arch::x86::_MM_HINT_T0);
+        }
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            core::arch::asm!("prfm pldl1keep, [{addr}]", addr = in(reg) ptr, options(nostack, preserves_flags));
+        }
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64")))]
+        let _ = ptr;
+    }
+
+    #[inline]
+    fn build_phone_info(&self, k: usize) -> Result<PhoneNoInfo> {
+        let record = utils::parse_record_data(&self.records, self.records_offsets[k] as usize)?;
+        utils::build_phone_info(&record, self.card_types[k])
+    }
+}
+
+impl PhoneLookup for PhoneDataEytzinger {
+    fn find(&self, no: &str) -> Result<PhoneNoInfo> {
+        let len = no.len();
+        if !(7..=11).contains(&len) {
+            return Err(ErrorKind::InvalidLength.into());
+        }
+
+        let phone_prefix = if len == 7 {
+            no.parse::<i32>()?
+        } else {
+            no.get(..7).ok_or(ErrorKind::InvalidLength)?.parse::<i32>()?
+        };
+
+        match self.eytzinger_search(phone_prefix, &mut 0) {
+            Some(k) => self.build_phone_info(k),
+            None => Err(ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn find_with_stats(&self, no: &str) -> (Result<PhoneNoInfo>, LookupStats) {
+        utils::instrumented_find(
+            no,
+            |prefix, probes| self.eytzinger_search(prefix, probes),
+            |k| self.build_phone_info(k),
+        )
+    }
+}
+
+impl PhoneStats for PhoneDataEytzinger {
+    fn total_entries(&self) -> usize {
+        self.len
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn memory_usage_bytes(&self) -> usize {
+        self.records.len() +
+        self.keys.len() * std::mem::size_of::<KeyLine>() +
+        self.records_offsets.len() * std::mem::size_of::<i32>() +
+        self.card_types.len() * std::mem::size_of::<u8>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eytzinger_lookup() {
+        let phone_data = PhoneDataEytzinger::new().unwrap();
+        let result = phone_data.find("18086834111").unwrap();
+        assert!(!result.province.is_empty());
+        assert!(!result.city.is_empty());
+        assert!(!result.card_type.is_empty());
+    }
+
+    #[test]
+    fn test_eytzinger_matches_sorted_search() {
+        for len in [0usize, 1, 2, 3, 15, 16, 17, 31, 32, 33, 1000, 4097] {
+            let index: Vec<Index> = (0..len)
+                .map(|i| Index::new(1300000 + (i as i32) * 8 + (i as i32 % 7), i as i32, 1))
+                .collect();
+            let data = PhoneDataEytzinger::from_index("0000".to_string(), Vec::new(), &index);
+            let sorted: Vec<i32> = index.iter().map(|item| item.phone_no_prefix).collect();
+            let last = sorted.last().copied().unwrap_or(1300000);
+
+            for target in 1299990..=last + 10 {
+                let expected = sorted.binary_search(&target).ok();
+                let actual = data.eytzinger_search(target, &mut 0).map(|k| data.records_offsets[k] as usize);
+                assert_eq!(actual, expected, "len {} target {}", len, target);
+            }
+        }
+    }
+}
@@ -1,9 +1,8 @@
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufReader, Read};
+use std::path::Path;
 use anyhow::Result;
 use serde_derive::Serialize;
-use crate::common::{utils, PhoneNoInfo, ErrorKind, CardType, PhoneLookup, PhoneStats};
+use crate::common::{utils, PhoneNoInfo, ErrorKind, CardType, PhoneLookup, PhoneStats, LookupStats, DEFAULT_DATA_PATH};
 
 #[derive(Debug, Serialize)]
 pub struct PhoneDataHash {
@@ -26,51 +25,28 @@ struct PhoneRecord {
 impl PhoneDataHash {
     /// 创建新的哈希版本手机数据实例
     pub fn new() -> Result<PhoneDataHash> {
-        let data_file = File::open("phone.dat")?;
-        let mut data_file = BufReader::new(data_file);
-
-        // 解析版本号和索引偏移
-        let mut header_buffer = [0u8; 8];
-        data_file.read_exact(&mut header_buffer)?;
-        let version = String::from_utf8((&header_buffer[..4]).to_vec())?;
-        let index_offset = utils::four_u8_to_i32(&header_buffer[4..]) as u64;
-
-        // 读取记录区
-        let mut records = vec![0u8; index_offset as usize - 8];
-        data_file.read_exact(&mut records)?;
-
-        // 解析索引区并构建哈希表
-        let mut phone_map = HashMap::with_capacity(517258); // 预分配容量
-        let mut index_item = [0u8; 9];
-
-        loop {
-            match data_file.read_exact(&mut index_item) {
-                Ok(_) => (),
-                Err(e) => match e.kind() {
-                    std::io::ErrorKind::UnexpectedEof => break,
-                    _ => (),
-                },
-            }
-
-            let phone_no_prefix = utils::four_u8_to_i32(&index_item[..4]);
-            let records_offset = utils::four_u8_to_i32(&index_item[4..]);
-            let card_type = index_item[8];
+        Self::from_path(DEFAULT_DATA_PATH)
+    }
 
-            // 解析记录
-            let record = utils::parse_record_data(&records, records_offset as usize)?;
+    /// 从指定路径加载数据
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<PhoneDataHash> {
+        let (header, records, index) = utils::read_database(path)?;
 
-            // 插入到哈希表
-            phone_map.insert(phone_no_prefix, PhoneRecord {
+        // 解析全部记录并构建哈希表
+        let mut phone_map = HashMap::with_capacity(index.len());
+        for item in &index {
+            let record = utils::parse_record_data(&records, item.records_offset as usize)?;
+            phone_map.insert(item.phone_no_prefix, PhoneRecord {
                 province: record.province,
                 city: record.city,
                 zip_code: record.zip_code,
                 area_code: record.area_code,
-                card_type,
+                card_type: item.card_type,
             });
         }
 
         Ok(PhoneDataHash {
-            version,
+            version: header.version,
             phone_map,
         })
     }
@@ -78,7 +54,7 @@ impl PhoneDataHash {
     /// 使用哈希表查找手机号信息 - O(1) 平均时间复杂度
     pub fn find(&self, no: &str) -> Result<PhoneNoInfo> {
         let len = no.len();
-        if len < 7 || len > 11 {
+        if !(7..=11).contains(&len) {
             return Err(ErrorKind::InvalidLength.into());
         }
 
@@ -86,7 +62,7 @@ impl PhoneDataHash {
         let phone_prefix = if len == 7 {
             no.parse::<i32>()?
         } else {
-            no[..7].parse::<i32>()?
+            no.get(..7).ok_or(ErrorKind::InvalidLength)?.parse::<i32>()?
         };
 
         // 哈希表查找
@@ -123,7 +99,7 @@ pub struct HashMapStats {
 impl PhoneLookup for PhoneDataHash {
     fn find(&self, no: &str) -> Result<PhoneNoInfo> {
         let len = no.len();
-        if len < 7 || len > 11 {
+        if !(7..=11).contains(&len) {
             return Err(ErrorKind::InvalidLength.into());
         }
 
@@ -131,7 +107,7 @@ impl PhoneLookup for PhoneDataHash {
         let phone_prefix = if len == 7 {
             no.parse::<i32>()?
         } else {
-            no[..7].parse::<i32>()?
+            no.get(..7).ok_or(ErrorKind::InvalidLength)?.parse::<i32>()?
         };
 
         // HashMap查找
@@ -149,6 +125,26 @@ impl PhoneLookup for PhoneDataHash {
             None => Err(ErrorKind::NotFound.into()),
         }
     }
+
+    fn find_with_stats(&self, no: &str) -> (Result<PhoneNoInfo>, LookupStats) {
+        utils::instrumented_find(
+            no,
+            |prefix, probes| {
+                *probes += 1;
+                self.phone_map.get(&prefix)
+            },
+            |record| {
+                let card_type = CardType::from_u8(record.card_type)?;
+                Ok(PhoneNoInfo {
+                    province: record.province.clone(),
+                    city: record.city.clone(),
+                    zip_code: record.zip_code.clone(),
+                    area_code: record.area_code.clone(),
+                    card_type: card_type.get_description(),
+                })
+            },
+        )
+    }
 }
 
 impl PhoneStats for PhoneDataHash {
@@ -1,238 +1,370 @@
-use std::fs::File;
-use std::io::{BufReader, Read};
+use std::path::Path;
 use anyhow::Result;
 use serde_derive::Serialize;
-use crate::common::{utils, PhoneNoInfo, ErrorKind, CardType, PhoneLookup, PhoneStats, Index};
+use crate::common::{utils, PhoneNoInfo, ErrorKind, PhoneLookup, PhoneStats, Index, LookupStats, DEFAULT_DATA_PATH, MERGE_BATCH_THRESHOLD};
+
+/// 末端块大小（i32个数）：窄化阶段结束后由向量指令一次比较整块
+const BLOCK_SIZE: usize = 16;
+
+/// 键数组尾部填充的哨兵值，保证末端块的向量加载不越界
+const KEY_SENTINEL: i32 = i32::MAX;
+
+/// 向量指令集级别 - 构建时通过运行时CPU特性检测确定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SimdLevel {
+    Avx2,
+    Sse2,
+    Neon,
+    Scalar,
+}
+
+impl SimdLevel {
+    /// 检测当前CPU支持的最佳指令集
+    pub fn detect() -> SimdLevel {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if std::is_x86_feature_detected!("avx2") {
+                return SimdLevel::Avx2;
+            }
+            if std::is_x86_feature_detected!("sse2") {
+                return SimdLevel::Sse2;
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                return SimdLevel::Neon;
+            }
+        }
+        SimdLevel::Scalar
+    }
+
+    /// 当前CPU是否支持该指令集
+    pub fn is_supported(&self) -> bool {
+        match self {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Avx2 => std::is_x86_feature_detected!("avx2"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Sse2 => std::is_x86_feature_detected!("sse2"),
+            #[cfg(target_arch = "aarch64")]
+            SimdLevel::Neon => std::arch::is_aarch64_feature_detected!("neon"),
+            SimdLevel::Scalar => true,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+}
 
 #[derive(Debug, Serialize)]
 pub struct PhoneDataSimd {
     version: String,
     records: Vec<u8>,
-    index: Vec<Index>,
+    /// SoA布局的有序前缀键，尾部填充 BLOCK_SIZE 个哨兵
+    keys: Vec<i32>,
+    /// 与keys一一对应的记录区偏移
+    records_offsets: Vec<i32>,
+    /// 与keys一一对应的卡类型
+    card_types: Vec<u8>,
+    simd_level: SimdLevel,
 }
 
 
 impl PhoneDataSimd {
     pub fn new() -> Result<PhoneDataSimd> {
-        let data_file = File::open("phone.dat")?;
-        let mut data_file = BufReader::new(data_file);
-
-        // 解析版本号和索引偏移
-        let mut header_buffer = [0u8; 8];
-        data_file.read_exact(&mut header_buffer)?;
-        let version = String::from_utf8((&header_buffer[..4]).to_vec())?;
-        let index_offset = utils::four_u8_to_i32(&header_buffer[4..]) as u64;
-
-        // 读取记录区
-        let mut records = vec![0u8; index_offset as usize - 8];
-        data_file.read_exact(&mut records)?;
-
-        // 解析索引区
-        let mut index = Vec::new();
-        let mut index_item = [0u8; 9];
-
-        loop {
-            match data_file.read_exact(&mut index_item) {
-                Ok(_) => (),
-                Err(e) => match e.kind() {
-                    std::io::ErrorKind::UnexpectedEof => break,
-                    _ => (),
-                },
-            }
-            let phone_no_prefix = utils::four_u8_to_i32(&index_item[..4]);
-            let records_offset = utils::four_u8_to_i32(&index_item[4..8]);
-            let card_type = index_item[8];
-            index.push(Index {
-                phone_no_prefix,
-                records_offset,
-                card_type,
-            });
+        Self::from_path(DEFAULT_DATA_PATH)
+    }
+
+    /// 从指定路径加载数据
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<PhoneDataSimd> {
+        let (header, records, index) = utils::read_database(path)?;
+        Ok(Self::from_index(header.version, records, index))
+    }
+
+    /// 由有序索引构建SoA布局
+    pub(crate) fn from_index(version: String, records: Vec<u8>, index: Vec<Index>) -> PhoneDataSimd {
+        let mut keys = Vec::with_capacity(index.len() + BLOCK_SIZE);
+        let mut records_offsets = Vec::with_capacity(index.len());
+        let mut card_types = Vec::with_capacity(index.len());
+
+        for item in &index {
+            keys.push(item.phone_no_prefix);
+            records_offsets.push(item.records_offset);
+            card_types.push(item.card_type);
         }
+        keys.resize(index.len() + BLOCK_SIZE, KEY_SENTINEL);
 
-        Ok(PhoneDataSimd {
+        PhoneDataSimd {
             version,
             records,
-            index,
-        })
+            keys,
+            records_offsets,
+            card_types,
+            simd_level: SimdLevel::detect(),
+        }
+    }
+
+    /// 指定末端块比较所用的指令集，CPU不支持时回退到标量实现
+    pub fn with_simd_level(mut self, level: SimdLevel) -> Self {
+        self.simd_level = if level.is_supported() { level } else { SimdLevel::Scalar };
+        self
+    }
+
+    /// 当前使用的指令集
+    pub fn simd_level(&self) -> SimdLevel {
+        self.simd_level
     }
 
-    /// SIMD优化的二分查找 - 利用现代CPU的向量化指令
+    /// SIMD优化的查找 - 利用现代CPU的向量化指令
     pub fn find(&self, no: &str) -> Result<PhoneNoInfo> {
-        let len = no.len();
-        if len < 7 || len > 11 {
-            return Err(ErrorKind::InvalidLength.into());
+        let phone_prefix = utils::parse_phone_prefix(no)?;
+
+        match self.simd_search(phone_prefix, &mut 0) {
+            Some(pos) => self.build_phone_info(pos),
+            None => Err(ErrorKind::NotFound.into()),
         }
+    }
 
-        let phone_prefix = if len == 7 {
-            no.parse::<i32>()?
-        } else {
-            no[..7].parse::<i32>()?
-        };
+    /// 分阶段计时的查找，探测次数为窄化步数加一次块内比较
+    pub fn find_with_stats(&self, no: &str) -> (Result<PhoneNoInfo>, LookupStats) {
+        utils::instrumented_find(
+            no,
+            |prefix, probes| self.simd_search(prefix, probes),
+            |pos| self.build_phone_info(pos),
+        )
+    }
 
-        // 使用优化的二分查找，结合SIMD友好的内存访问模式
-        let result = self.simd_binary_search(phone_prefix);
-
-        match result {
-            Some(index) => {
-                let record = utils::parse_record_data(&self.records, index.records_offset as usize)?;
-                let card_type = CardType::from_u8(index.card_type)?;
-                Ok(PhoneNoInfo {
-                    province: record.province,
-                    city: record.city,
-                    zip_code: record.zip_code,
-                    area_code: record.area_code,
-                    card_type: card_type.get_description(),
-                })
-            }
-            None => Err(ErrorKind::NotFound.into()),
+    /// 按号段顺序遍历全部记录
+    pub fn entries(&self) -> impl Iterator<Item = Result<(i32, PhoneNoInfo)>> + '_ {
+        (0..self.len()).map(|pos| Ok((self.keys[pos], self.build_phone_info(pos)?)))
+    }
+
+    #[inline]
+    fn build_phone_info(&self, pos: usize) -> Result<PhoneNoInfo> {
+        let record = utils::parse_record_data(&self.records, self.records_offsets[pos] as usize)?;
+        utils::build_phone_info(&record, self.card_types[pos])
+    }
+
+    /// 有效记录数（不含尾部哨兵）
+    #[inline]
+    fn len(&self) -> usize {
+        self.records_offsets.len()
+    }
+
+    /// 向量化查找：无分支地窄化到一个末端块，再用向量比较定位目标
+    #[inline]
+    fn simd_search(&self, target: i32, probes: &mut u32) -> Option<usize> {
+        let base = self.narrow_to_block(target, probes);
+        *probes += 1;
+        self.match_in_block(base, target)
+    }
+
+    /// 无分支窄化：循环体只有条件传送，没有依赖比较结果的跳转
+    ///
+    /// 不变式：若目标存在，则位于 `[base, base + size)` 内
+    #[inline]
+    fn narrow_to_block(&self, target: i32, probes: &mut u32) -> usize {
+        let mut base = 0usize;
+        let mut size = self.len();
+
+        while size > BLOCK_SIZE {
+            *probes += 1;
+            let half = size / 2;
+            let mid = base + half;
+            // mid < base + size <= len，访问总在有效范围内
+            let mid_key = unsafe { *self.keys.get_unchecked(mid) };
+            base = if mid_key <= target { mid } else { base };
+            size -= half;
         }
+
+        base
     }
 
-    /// SIMD友好的二分查找实现
+    /// 在 `[base, base + BLOCK_SIZE)` 内比较目标，返回命中位置
     #[inline]
-    fn simd_binary_search(&self, target: i32) -> Option<&Index> {
-        let mut left = 0usize;
-        let mut right = self.index.len();
-
-        // 使用分支预测友好的循环结构
-        while left < right {
-            let mid = left + ((right - left) >> 1);
-            let mid_index = unsafe { self.index.get_unchecked(mid) };
-
-            // 使用比较结果进行分支优化
-            match mid_index.phone_no_prefix.cmp(&target) {
-                std::cmp::Ordering::Equal => return Some(mid_index),
-                std::cmp::Ordering::Greater => right = mid,
-                std::cmp::Ordering::Less => left = mid + 1,
-            }
+    fn match_in_block(&self, base: usize, target: i32) -> Option<usize> {
+        let block = &self.keys[base..base + BLOCK_SIZE];
+        let mask = match self.simd_level {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Avx2 => unsafe { x86::block_mask_avx2(block.as_ptr(), target) },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Sse2 => unsafe { x86::block_mask_sse2(block.as_ptr(), target) },
+            #[cfg(target_arch = "aarch64")]
+            SimdLevel::Neon => unsafe { neon::block_mask_neon(block.as_ptr(), target) },
+            _ => block_mask_scalar(block, target),
+        };
+
+        if mask == 0 {
+            return None;
         }
 
-        None
+        let pos = base + mask.trailing_zeros() as usize;
+        if pos < self.len() { Some(pos) } else { None }
     }
 
     /// 预取优化的查找 - 适用于批量查询
     pub fn find_with_prefetch(&self, no: &str) -> Result<PhoneNoInfo> {
-        let len = no.len();
-        if len < 7 || len > 11 {
-            return Err(ErrorKind::InvalidLength.into());
-        }
-
-        let phone_prefix = if len == 7 {
-            no.parse::<i32>()?
-        } else {
-            no[..7].parse::<i32>()?
-        };
+        let phone_prefix = utils::parse_phone_prefix(no)?;
 
-        let result = self.prefetch_binary_search(phone_prefix);
-
-        match result {
-            Some(index) => {
-                let record = utils::parse_record_data(&self.records, index.records_offset as usize)?;
-                let card_type = CardType::from_u8(index.card_type)?;
-                Ok(PhoneNoInfo {
-                    province: record.province,
-                    city: record.city,
-                    zip_code: record.zip_code,
-                    area_code: record.area_code,
-                    card_type: card_type.get_description(),
-                })
-            }
+        match self.prefetch_search(phone_prefix) {
+            Some(pos) => self.build_phone_info(pos),
             None => Err(ErrorKind::NotFound.into()),
         }
     }
 
-    /// 带预取的二分查找 - 在批量查询时性能更佳
+    /// 带预取的向量化查找 - 每一步同时预取下一轮两个可能的探测位置
     #[inline]
-    fn prefetch_binary_search(&self, target: i32) -> Option<&Index> {
-        let mut left = 0usize;
-        let mut right = self.index.len();
-
-        while left < right {
-            let mid = left + ((right - left) >> 1);
-
-            // 预取下一个可能的访问位置
-            if mid + 16 < self.index.len() {
-                self.prefetch_index(mid + 16);
-            }
-
-            let mid_index = unsafe { self.index.get_unchecked(mid) };
-
-            match mid_index.phone_no_prefix.cmp(&target) {
-                std::cmp::Ordering::Equal => return Some(mid_index),
-                std::cmp::Ordering::Greater => right = mid,
-                std::cmp::Ordering::Less => left = mid + 1,
-            }
+    fn prefetch_search(&self, target: i32) -> Option<usize> {
+        let mut base = 0usize;
+        let mut size = self.len();
+
+        while size > BLOCK_SIZE {
+            let half = size / 2;
+            let next_half = (size - half) / 2;
+            // 下一轮的mid只可能是 base + next_half 或 base + half + next_half
+            self.prefetch_key(base + next_half);
+            self.prefetch_key(base + half + next_half);
+
+            let mid = base + half;
+            let mid_key = unsafe { *self.keys.get_unchecked(mid) };
+            base = if mid_key <= target { mid } else { base };
+            size -= half;
         }
 
-        None
+        self.match_in_block(base, target)
     }
 
     #[inline]
-    fn prefetch_index(&self, _idx: usize) {
+    fn prefetch_key(&self, _idx: usize) {
         #[cfg(target_arch = "x86_64")]
         unsafe {
             core::arch::x86_64::_mm_prefetch(
-                self.index.as_ptr().add(_idx) as *const i8,
+                self.keys.as_ptr().add(_idx) as *const i8,
                 core::arch::x86_64::_MM_HINT_T0,
             );
         }
         #[cfg(target_arch = "x86")]
         unsafe {
             core::arch::x86::_mm_prefetch(
-                self.index.as_ptr().add(_idx) as *const i8,
+                self.keys.as_ptr().add(_idx) as *const i8,
                 core::arch::x86::_MM_HINT_T0,
             );
         }
         #[cfg(target_arch = "aarch64")]
         unsafe {
-            let ptr = self.index.as_ptr().add(_idx);
+            let ptr = self.keys.as_ptr().add(_idx);
             core::arch::asm!("prfm pldl1keep, [{addr}]", addr = in(reg) ptr, options(nostack, preserves_flags));
         }
     }
 
-    /// 批量查找优化 - 一次调用查找多个号码
+    /// 批量查找优化 - 小批量逐个预取查找，大批量排序后单次归并遍历键数组
     pub fn find_batch(&self, phones: &[&str]) -> Vec<Result<PhoneNoInfo>> {
-        phones.iter().map(|phone| self.find(phone)).collect()
+        if phones.len() < MERGE_BATCH_THRESHOLD {
+            return phones.iter().map(|phone| self.find_with_prefetch(phone)).collect();
+        }
+
+        utils::merge_find_batch(
+            phones,
+            self.len(),
+            |i| self.keys[i],
+            |i| self.build_phone_info(i),
+        )
     }
 }
 
+/// 标量回退：逐个比较，生成与向量实现相同格式的位掩码
+#[inline]
+fn block_mask_scalar(block: &[i32], target: i32) -> u32 {
+    block
+        .iter()
+        .enumerate()
+        .fold(0u32, |mask, (i, &key)| mask | (((key == target) as u32) << i))
+}
 
-impl PhoneLookup for PhoneDataSimd {
-    fn find(&self, no: &str) -> Result<PhoneNoInfo> {
-        let len = no.len();
-        if len < 7 || len > 11 {
-            return Err(ErrorKind::InvalidLength.into());
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86 {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::*;
+
+    /// AVX2：两次256位比较覆盖16个键
+    ///
+    /// # Safety
+    /// 调用方需保证CPU支持AVX2，且 `ptr` 起始处至少有16个可读的i32
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn block_mask_avx2(ptr: *const i32, target: i32) -> u32 {
+        unsafe {
+            let needle = _mm256_set1_epi32(target);
+            let lo = _mm256_loadu_si256(ptr as *const __m256i);
+            let hi = _mm256_loadu_si256(ptr.add(8) as *const __m256i);
+            let lo_mask = _mm256_movemask_ps(_mm256_castsi256_ps(_mm256_cmpeq_epi32(lo, needle))) as u32;
+            let hi_mask = _mm256_movemask_ps(_mm256_castsi256_ps(_mm256_cmpeq_epi32(hi, needle))) as u32;
+            lo_mask | (hi_mask << 8)
         }
+    }
 
-        let phone_prefix = if len == 7 {
-            no.parse::<i32>()?
-        } else {
-            no[..7].parse::<i32>()?
-        };
+    /// SSE2：四次128位比较覆盖16个键
+    ///
+    /// # Safety
+    /// 调用方需保证CPU支持SSE2，且 `ptr` 起始处至少有16个可读的i32
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn block_mask_sse2(ptr: *const i32, target: i32) -> u32 {
+        unsafe {
+            let needle = _mm_set1_epi32(target);
+            let mut mask = 0u32;
+            for lane in 0..4 {
+                let keys = _mm_loadu_si128(ptr.add(lane * 4) as *const __m128i);
+                let eq = _mm_movemask_ps(_mm_castsi128_ps(_mm_cmpeq_epi32(keys, needle))) as u32;
+                mask |= eq << (lane * 4);
+            }
+            mask
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use core::arch::aarch64::*;
 
-        // SIMD优化的二分查找
-        let result = self.simd_binary_search(phone_prefix);
-
-        match result {
-            Some(index) => {
-                let record = utils::parse_record_data(&self.records, index.records_offset as usize)?;
-                let card_type = CardType::from_u8(index.card_type)?;
-                Ok(PhoneNoInfo {
-                    province: record.province,
-                    city: record.city,
-                    zip_code: record.zip_code,
-                    area_code: record.area_code,
-                    card_type: card_type.get_description(),
-                })
+    /// NEON：没有movemask指令，用按位权重求和模拟
+    ///
+    /// # Safety
+    /// 调用方需保证CPU支持NEON，且 `ptr` 起始处至少有16个可读的i32
+    #[target_feature(enable = "neon")]
+    pub unsafe fn block_mask_neon(ptr: *const i32, target: i32) -> u32 {
+        unsafe {
+            let needle = vdupq_n_s32(target);
+            let weights: [u32; 4] = [1, 2, 4, 8];
+            let weights = vld1q_u32(weights.as_ptr());
+            let mut mask = 0u32;
+            for lane in 0..4 {
+                let keys = vld1q_s32(ptr.add(lane * 4));
+                let eq = vandq_u32(vceqq_s32(keys, needle), weights);
+                mask |= vaddvq_u32(eq) << (lane * 4);
             }
-            None => Err(ErrorKind::NotFound.into()),
+            mask
         }
     }
 }
 
+
+impl PhoneLookup for PhoneDataSimd {
+    fn find(&self, no: &str) -> Result<PhoneNoInfo> {
+        PhoneDataSimd::find(self, no)
+    }
+
+    fn find_with_stats(&self, no: &str) -> (Result<PhoneNoInfo>, LookupStats) {
+        PhoneDataSimd::find_with_stats(self, no)
+    }
+
+    fn find_batch(&self, phones: &[&str]) -> Vec<Result<PhoneNoInfo>> {
+        PhoneDataSimd::find_batch(self, phones)
+    }
+}
+
 impl PhoneStats for PhoneDataSimd {
     fn total_entries(&self) -> usize {
-        self.index.len()
+        self.len()
     }
 
     fn version(&self) -> &str {
@@ -240,7 +372,10 @@ impl PhoneStats for PhoneDataSimd {
     }
 
     fn memory_usage_bytes(&self) -> usize {
-        self.records.len() + self.index.len() * std::mem::size_of::<Index>()
+        self.records.len() +
+        self.keys.len() * std::mem::size_of::<i32>() +
+        self.records_offsets.len() * std::mem::size_of::<i32>() +
+        self.card_types.len() * std::mem::size_of::<u8>()
     }
 }
 
@@ -248,6 +383,16 @@ impl PhoneStats for PhoneDataSimd {
 mod tests {
     use super::*;
 
+    const ALL_LEVELS: [SimdLevel; 4] = [SimdLevel::Avx2, SimdLevel::Sse2, SimdLevel::Neon, SimdLevel::Scalar];
+
+    /// 构造只含键的数据集，步长不规则以覆盖块边界
+    fn synthetic(len: usize) -> PhoneDataSimd {
+        let index = (0..len)
+            .map(|i| Index::new(1300000 + (i as i32) * 8 + (i as i32 % 7), 8, 1))
+            .collect();
+        PhoneDataSimd::from_index("0000".to_string(), Vec::new(), index)
+    }
+
     #[test]
     fn test_simd_lookup() {
         let phone_data = PhoneDataSimd::new().unwrap();
@@ -264,4 +409,49 @@ mod tests {
         let results = phone_data.find_batch(&phones);
         assert_eq!(results.len(), 3);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_block_mask_matches_scalar() {
+        let block: Vec<i32> = (0..BLOCK_SIZE as i32).map(|i| i * 2).collect();
+        for level in ALL_LEVELS.iter().filter(|l| l.is_supported()) {
+            let data = PhoneDataSimd {
+                version: String::new(),
+                records: Vec::new(),
+                keys: block.clone(),
+                records_offsets: vec![0; BLOCK_SIZE],
+                card_types: vec![0; BLOCK_SIZE],
+                simd_level: *level,
+            };
+            for target in -1..(BLOCK_SIZE as i32 * 2 + 1) {
+                let expected = block.iter().position(|&k| k == target);
+                assert_eq!(data.match_in_block(0, target), expected, "{:?} target {}", level, target);
+            }
+        }
+    }
+
+    #[test]
+    fn test_differential_against_binary_search() {
+        for len in [0usize, 1, 2, 15, 16, 17, 31, 32, 33, 100, 1000, 4097] {
+            for level in ALL_LEVELS.iter().filter(|l| l.is_supported()) {
+                let data = synthetic(len).with_simd_level(*level);
+                let sorted = &data.keys[..data.len()];
+                let last = sorted.last().copied().unwrap_or(1300000);
+
+                for target in 1299990..=last + 10 {
+                    let expected = sorted.binary_search(&target).ok();
+                    assert_eq!(data.simd_search(target, &mut 0), expected, "{:?} len {} target {}", level, len, target);
+                    assert_eq!(data.prefetch_search(target), expected, "{:?} len {} target {}", level, len, target);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsupported_level_falls_back_to_scalar() {
+        for level in ALL_LEVELS {
+            let data = synthetic(10).with_simd_level(level);
+            let expected = if level.is_supported() { level } else { SimdLevel::Scalar };
+            assert_eq!(data.simd_level(), expected);
+        }
+    }
+}
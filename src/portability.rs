@@ -0,0 +1,169 @@
+use std::path::Path;
+use anyhow::{anyhow, Context, Result};
+use serde_derive::Serialize;
+
+use crate::common::{utils, CardType, PhoneNoInfo};
+
+/// 携号转网前后的运营商
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CarrierInfo {
+    /// 按号段得到的运营商
+    pub original_carrier: String,
+    /// 当前运营商，未转网时与`original_carrier`相同
+    pub current_carrier: String,
+    /// 是否在携号转网表中
+    pub ported: bool,
+}
+
+/// 携号转网表，按完整号码记录当前运营商
+///
+/// 每个号码和运营商代码打包为一个u64（`号码 << 8 | 代码`）并排序，每条记录占8字节，按号码二分查找
+#[derive(Debug, Clone, Default)]
+pub struct PortabilityTable {
+    entries: Vec<u64>,
+    /// 文件内容的摘要，用于让缓存随转网表失效
+    fingerprint: u64,
+}
+
+impl PortabilityTable {
+    /// 加载`number,carrier`格式的文件
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<PortabilityTable> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read portability table {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("invalid portability table {}", path.display()))
+    }
+
+    /// 解析`number,carrier`格式，运营商为代码或描述；`#`开头为注释，可带`number,carrier`表头
+    pub fn parse(content: &str) -> Result<PortabilityTable> {
+        let mut entries = Vec::new();
+        for (line_no, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line == "number,carrier" {
+                continue;
+            }
+            let (number, carrier) = line
+                .split_once(',')
+                .ok_or_else(|| anyhow!("line {}: expected number,carrier", line_no + 1))?;
+            let number = number.trim();
+            if number.len() != 11 || !number.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(anyhow!("line {}: {:?} is not an 11-digit number", line_no + 1, number));
+            }
+            let carrier: CardType = carrier.trim().parse().with_context(|| format!("line {}", line_no + 1))?;
+            entries.push(number.parse::<u64>()? << 8 | carrier.get_code() as u64);
+        }
+
+        entries.sort_unstable();
+        if let Some(pair) = entries.windows(2).find(|pair| pair[0] >> 8 == pair[1] >> 8) {
+            return Err(anyhow!("duplicate number {}", pair[0] >> 8));
+        }
+        entries.shrink_to_fit();
+
+        let bytes: Vec<u8> = entries.iter().flat_map(|entry| entry.to_le_bytes()).collect();
+        let fingerprint = utils::stable_hash(&[&bytes]);
+        Ok(PortabilityTable { entries, fingerprint })
+    }
+
+    /// 转网号码数
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 占用的内存字节数
+    pub fn memory_usage(&self) -> usize {
+        self.entries.capacity() * std::mem::size_of::<u64>()
+    }
+
+    /// 号码转入的运营商，只接受11位号码
+    pub fn get(&self, no: &str) -> Option<CardType> {
+        if no.len() != 11 {
+            return None;
+        }
+        let number = no.parse::<u64>().ok()?;
+        let i = self.entries.partition_point(|entry| entry >> 8 < number);
+        let entry = *self.entries.get(i)?;
+        (entry >> 8 == number).then(|| CardType::from_u8(entry as u8).ok()).flatten()
+    }
+
+    /// 按号码修正号段查询结果的运营商
+    pub fn resolve(&self, no: &str, info: &PhoneNoInfo) -> CarrierInfo {
+        match self.get(no) {
+            Some(carrier) => CarrierInfo {
+                original_carrier: info.card_type.clone(),
+                current_carrier: carrier.get_description(),
+                ported: true,
+            },
+            None => CarrierInfo {
+                original_carrier: info.card_type.clone(),
+                current_carrier: info.card_type.clone(),
+                ported: false,
+            },
+        }
+    }
+
+    /// 号码的缓存键后缀，号段内没有转网号码时为空
+    pub fn cache_key(&self, no: &str) -> Option<u64> {
+        let prefix = utils::parse_phone_prefix(no).ok()? as u64;
+        let start = self.entries.partition_point(|entry| entry >> 8 < prefix * 10_000);
+        if self.entries.get(start).is_none_or(|entry| entry >> 8 >= (prefix + 1) * 10_000) {
+            return None;
+        }
+        Some(utils::stable_hash(&[&self.fingerprint.to_le_bytes(), no.as_bytes()]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "\
+number,carrier
+# 转入中国移动
+18086834111,中国移动
+13800138000,2
+18086830000,7
+";
+
+    #[test]
+    fn test_lookup_ported_numbers() {
+        let table = PortabilityTable::parse(TABLE).unwrap();
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.memory_usage(), 24);
+        assert_eq!(table.get("18086834111").unwrap().get_code(), 1);
+        assert_eq!(table.get("13800138000").unwrap().get_code(), 2);
+        assert!(table.get("18086834112").is_none());
+        assert!(table.get("1808683").is_none());
+
+        let info = PhoneNoInfo::new("四川".into(), "成都".into(), "610000".into(), "028".into(), "中国电信".into());
+        let ported = table.resolve("18086830000", &info);
+        assert_eq!((ported.original_carrier.as_str(), ported.current_carrier.as_str(), ported.ported), ("中国电信", "中国广电", true));
+        let unchanged = table.resolve("18086830001", &info);
+        assert_eq!((unchanged.current_carrier.as_str(), unchanged.ported), ("中国电信", false));
+    }
+
+    #[test]
+    fn test_invalid_tables() {
+        for content in ["1808683411,中国移动", "18086834111", "18086834111,中国邮政", "18086834111,1\n18086834111,2"] {
+            assert!(PortabilityTable::parse(content).is_err(), "{}", content);
+        }
+        assert!(PortabilityTable::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let table = PortabilityTable::parse(TABLE).unwrap();
+        assert!(table.cache_key("15900000000").is_none());
+        assert!(table.cache_key("18086839999").is_some());
+        assert_ne!(table.cache_key("18086834111"), table.cache_key("18086834112"));
+        assert!(table.cache_key("18086840000").is_none());
+
+        // 同样的内容在不同实例、不同进程中得到同样的缓存键
+        assert_eq!(table.cache_key("18086834111"), PortabilityTable::parse(TABLE).unwrap().cache_key("18086834111"));
+        let other = PortabilityTable::parse("number,carrier\n18086834111,中国联通\n").unwrap();
+        assert_ne!(table.cache_key("18086834111"), other.cache_key("18086834111"));
+    }
+}
@@ -0,0 +1,1161 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServerHandle, ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CACHE_CONTROL, ETAG, IF_NONE_MATCH, RETRY_AFTER};
+use actix_web::middleware::{from_fn, Next};
+use actix_web::{get, web, App, HttpRequest, HttpResponse, HttpServer, Responder};
+use anyhow::{anyhow, Context, Result};
+use ed25519_dalek::VerifyingKey;
+use serde_derive::{Serialize, Deserialize};
+
+use crate::access_log::{format_timestamp, AccessLogEntry, AccessLogger, LogFormat};
+use crate::access::{AccessControl, AccessDecision, Quota, RateLimitStatus};
+use crate::common::{utils, LookupOutcome, PhoneStats, DEFAULT_DATA_PATH};
+use crate::integrity::{self, IntegrityPolicy, Verification};
+use crate::metrics::Metrics;
+use crate::openapi;
+use crate::overlay::{OverlaidInfo, Overlay};
+use crate::portability::{CarrierInfo, PortabilityTable};
+use crate::stats::DatasetStats;
+use crate::versioned::VersionedLookup;
+use crate::PhoneData;
+
+/// 完整号码的处理方式，用于不允许完整号码进入服务的场景
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PrivacyMode {
+    /// 接受完整号码
+    #[default]
+    Off,
+    /// 拒绝超过7位的号码
+    Reject,
+    /// 收到后只保留前7位
+    Truncate,
+}
+
+impl std::str::FromStr for PrivacyMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<PrivacyMode> {
+        match s {
+            "off" => Ok(PrivacyMode::Off),
+            "reject" => Ok(PrivacyMode::Reject),
+            "truncate" => Ok(PrivacyMode::Truncate),
+            _ => Err(anyhow!("unknown privacy mode {:?}, expected off, reject or truncate", s)),
+        }
+    }
+}
+
+/// HTTP服务配置
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// 监听地址
+    pub bind_addr: String,
+    /// 工作线程数
+    pub workers: usize,
+    /// 数据文件路径，启动和重新加载时读取
+    pub data_path: PathBuf,
+    /// 同时加载的历史版本数据文件，可通过`?version=`查询
+    pub archive_paths: Vec<PathBuf>,
+    /// 加载后用于自检的号码，数据中必须存在
+    pub self_test_phone: String,
+    /// 收到退出信号后，先置为未就绪，等待这段时间再关闭监听
+    pub readiness_delay: Duration,
+    /// 关闭监听后等待进行中请求完成的最长时间
+    pub drain_timeout: Duration,
+    /// API Key文件，配置后查询接口要求携带有效Key
+    pub api_keys_path: Option<PathBuf>,
+    /// 每个API Key的默认配额
+    pub key_quota: Quota,
+    /// 每个客户端IP的配额
+    pub ip_quota: Quota,
+    /// 管理Key，`/admin`下的接口要求携带；未配置时管理接口不可用
+    pub admin_key: Option<String>,
+    /// 是否信任`X-Forwarded-For`等代理头来识别客户端IP
+    pub trust_proxy: bool,
+    /// 查询接口对完整号码的处理方式
+    pub privacy_mode: PrivacyMode,
+    /// 访问日志格式
+    pub log_format: LogFormat,
+    /// 访问日志采样率，0到1之间
+    pub log_sample_rate: f64,
+    /// 查询结果的`Cache-Control` max-age，为0时要求客户端每次用`If-None-Match`重新验证
+    pub cache_max_age: Duration,
+    /// 人工覆盖文件（CSV或`.toml`），只作用于未指定版本的查询
+    pub overlay_path: Option<PathBuf>,
+    /// 携号转网表（`number,carrier`），只作用于未指定版本的查询
+    pub portability_path: Option<PathBuf>,
+    /// 数据文件签名公钥，配置后校验`.sig`文件
+    pub public_key: Option<VerifyingKey>,
+    /// 严格模式下拒绝加载未经校验的数据文件
+    pub strict_integrity: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_addr: "0.0.0.0:8080".to_string(),
+            workers: 200,
+            data_path: PathBuf::from(DEFAULT_DATA_PATH),
+            archive_paths: Vec::new(),
+            self_test_phone: "18086834111".to_string(),
+            readiness_delay: Duration::from_secs(5),
+            drain_timeout: Duration::from_secs(30),
+            api_keys_path: None,
+            key_quota: Quota::unlimited(),
+            ip_quota: Quota::unlimited(),
+            admin_key: None,
+            trust_proxy: false,
+            privacy_mode: PrivacyMode::Off,
+            log_format: LogFormat::Text,
+            log_sample_rate: 1.0,
+            cache_max_age: Duration::ZERO,
+            overlay_path: None,
+            portability_path: None,
+            public_key: None,
+            strict_integrity: false,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// 读取环境变量覆盖默认配置
+    ///
+    /// `PHONE_DATA_BIND`、`PHONE_DATA_WORKERS`、`PHONE_DATA_PATH`、`PHONE_DATA_ARCHIVE_PATHS`（按系统路径分隔符分隔）、
+    /// `PHONE_DATA_SELF_TEST_PHONE`、
+    /// `PHONE_DATA_READINESS_DELAY`、`PHONE_DATA_DRAIN_TIMEOUT`（秒）、`PHONE_DATA_API_KEYS`、
+    /// `PHONE_DATA_KEY_RATE`/`PHONE_DATA_KEY_BURST`、`PHONE_DATA_IP_RATE`/`PHONE_DATA_IP_BURST`、`PHONE_DATA_ADMIN_KEY`、
+    /// `PHONE_DATA_TRUST_PROXY`、`PHONE_DATA_PRIVACY_MODE`（off/reject/truncate）、
+    /// `PHONE_DATA_ACCESS_LOG`（off/text/json）、`PHONE_DATA_ACCESS_LOG_SAMPLE`、`PHONE_DATA_CACHE_MAX_AGE`（秒）、
+    /// `PHONE_DATA_OVERLAY`、`PHONE_DATA_MNP`、`PHONE_DATA_PUBLIC_KEY`（十六进制）、`PHONE_DATA_STRICT_INTEGRITY`
+    pub fn from_env() -> Result<ServerConfig> {
+        let mut config = ServerConfig::default();
+        if let Ok(bind_addr) = std::env::var("PHONE_DATA_BIND") {
+            config.bind_addr = bind_addr;
+        }
+        if let Ok(workers) = std::env::var("PHONE_DATA_WORKERS") {
+            config.workers = workers.parse().context("PHONE_DATA_WORKERS must be a positive integer")?;
+        }
+        if let Ok(data_path) = std::env::var("PHONE_DATA_PATH") {
+            config.data_path = PathBuf::from(data_path);
+        }
+        if let Some(paths) = std::env::var_os("PHONE_DATA_ARCHIVE_PATHS") {
+            config.archive_paths = std::env::split_paths(&paths).filter(|path| !path.as_os_str().is_empty()).collect();
+        }
+        if let Ok(phone) = std::env::var("PHONE_DATA_SELF_TEST_PHONE") {
+            config.self_test_phone = phone;
+        }
+        if let Ok(secs) = std::env::var("PHONE_DATA_READINESS_DELAY") {
+            let secs = secs.parse().context("PHONE_DATA_READINESS_DELAY must be a number of seconds")?;
+            config.readiness_delay = Duration::from_secs(secs);
+        }
+        if let Ok(secs) = std::env::var("PHONE_DATA_DRAIN_TIMEOUT") {
+            let secs = secs.parse().context("PHONE_DATA_DRAIN_TIMEOUT must be a number of seconds")?;
+            config.drain_timeout = Duration::from_secs(secs);
+        }
+        if let Ok(path) = std::env::var("PHONE_DATA_API_KEYS") {
+            config.api_keys_path = Some(PathBuf::from(path));
+        }
+        if let Some(quota) = quota_from_env("PHONE_DATA_KEY_RATE", "PHONE_DATA_KEY_BURST")? {
+            config.key_quota = quota;
+        }
+        if let Some(quota) = quota_from_env("PHONE_DATA_IP_RATE", "PHONE_DATA_IP_BURST")? {
+            config.ip_quota = quota;
+        }
+        if let Ok(key) = std::env::var("PHONE_DATA_ADMIN_KEY") {
+            if key.trim().is_empty() {
+                return Err(anyhow!("PHONE_DATA_ADMIN_KEY must not be empty"));
+            }
+            config.admin_key = Some(key.trim().to_string());
+        }
+        if let Ok(trust) = std::env::var("PHONE_DATA_TRUST_PROXY") {
+            config.trust_proxy = trust.parse().context("PHONE_DATA_TRUST_PROXY must be true or false")?;
+        }
+        if let Ok(mode) = std::env::var("PHONE_DATA_PRIVACY_MODE") {
+            config.privacy_mode = mode.parse().context("PHONE_DATA_PRIVACY_MODE")?;
+        }
+        if let Ok(format) = std::env::var("PHONE_DATA_ACCESS_LOG") {
+            config.log_format = format.parse().context("PHONE_DATA_ACCESS_LOG")?;
+        }
+        if let Ok(rate) = std::env::var("PHONE_DATA_ACCESS_LOG_SAMPLE") {
+            let rate: f64 = rate.parse().context("PHONE_DATA_ACCESS_LOG_SAMPLE must be a number between 0 and 1")?;
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(anyhow!("PHONE_DATA_ACCESS_LOG_SAMPLE must be between 0 and 1"));
+            }
+            config.log_sample_rate = rate;
+        }
+        if let Ok(secs) = std::env::var("PHONE_DATA_CACHE_MAX_AGE") {
+            let secs = secs.parse().context("PHONE_DATA_CACHE_MAX_AGE must be a number of seconds")?;
+            config.cache_max_age = Duration::from_secs(secs);
+        }
+        if let Ok(path) = std::env::var("PHONE_DATA_OVERLAY") {
+            config.overlay_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = std::env::var("PHONE_DATA_MNP") {
+            config.portability_path = Some(PathBuf::from(path));
+        }
+        if let Ok(key) = std::env::var("PHONE_DATA_PUBLIC_KEY") {
+            config.public_key = Some(integrity::parse_public_key(&key).context("PHONE_DATA_PUBLIC_KEY")?);
+        }
+        if let Ok(strict) = std::env::var("PHONE_DATA_STRICT_INTEGRITY") {
+            config.strict_integrity = strict.parse().context("PHONE_DATA_STRICT_INTEGRITY must be true or false")?;
+        }
+        Ok(config)
+    }
+
+    /// 按配置加载API Key并创建访问控制
+    pub fn access_control(&self) -> Result<AccessControl> {
+        AccessControl::load(self.api_keys_path.clone(), self.key_quota, self.ip_quota)
+    }
+
+    /// 按配置加载人工覆盖，未配置时为空
+    pub fn overlay(&self) -> Result<Overlay> {
+        match &self.overlay_path {
+            Some(path) => Overlay::load(path),
+            None => Ok(Overlay::default()),
+        }
+    }
+
+    /// 按配置加载携号转网表，未配置时为空
+    pub fn portability(&self) -> Result<PortabilityTable> {
+        match &self.portability_path {
+            Some(path) => PortabilityTable::load(path),
+            None => Ok(PortabilityTable::default()),
+        }
+    }
+
+    /// 数据文件的完整性校验策略
+    pub fn integrity_policy(&self) -> IntegrityPolicy {
+        IntegrityPolicy { public_key: self.public_key, strict: self.strict_integrity }
+    }
+
+    /// 按配置创建访问日志，输出到标准输出
+    pub fn access_logger(&self) -> AccessLogger {
+        AccessLogger::new(self.log_format, self.log_sample_rate)
+    }
+}
+
+/// 读取每秒请求数和突发数，未设置突发数时与每秒请求数相同
+fn quota_from_env(rate_var: &str, burst_var: &str) -> Result<Option<Quota>> {
+    let Ok(rate) = std::env::var(rate_var) else {
+        return Ok(None);
+    };
+    let rate: f64 = rate.parse().with_context(|| format!("{} must be a number of requests per second", rate_var))?;
+    let burst = match std::env::var(burst_var) {
+        Ok(burst) => burst.parse().with_context(|| format!("{} must be a positive integer", burst_var))?,
+        Err(_) => rate.ceil() as u32,
+    };
+    Ok(Some(Quota::try_new(rate, burst).with_context(|| format!("{} must be a positive number of requests per second", rate_var))?))
+}
+
+/// 已加载的数据及加载信息
+pub struct Database {
+    /// 按版本号区分的全部数据，至少有一个版本
+    pub versions: VersionedLookup<PhoneData>,
+    /// 加载完成的时间
+    pub loaded_at: SystemTime,
+    /// 加载耗时
+    pub load_duration: Duration,
+    /// 所有数据文件都通过的校验
+    pub verification: Verification,
+    /// 最新版本的统计报告，首次请求时计算
+    stats: OnceLock<DatasetStats>,
+}
+
+impl Database {
+    /// 按配置加载主数据文件和历史版本，逐个校验完整性并自检，任一版本不通过时整体不被使用
+    pub fn load(config: &ServerConfig) -> Result<Database> {
+        let start = Instant::now();
+        let policy = config.integrity_policy();
+        let mut versions = VersionedLookup::new();
+        let mut verification = Verification::Signature;
+        for path in std::iter::once(&config.data_path).chain(&config.archive_paths) {
+            let phone_data = load_verified(path, &policy, &mut verification)
+                .with_context(|| format!("failed to load {}", path.display()))?;
+            if phone_data.total_entries() == 0 {
+                return Err(anyhow!("self-test failed: {} is empty", path.display()));
+            }
+            phone_data
+                .find(&config.self_test_phone)
+                .map_err(|e| anyhow!("self-test lookup of {} in {} failed: {}", config.self_test_phone, path.display(), e))?;
+            versions.insert(phone_data).with_context(|| format!("failed to load {}", path.display()))?;
+        }
+
+        Ok(Database {
+            versions,
+            loaded_at: SystemTime::now(),
+            load_duration: start.elapsed(),
+            verification,
+            stats: OnceLock::new(),
+        })
+    }
+
+    /// 只包含一个版本的数据
+    pub fn single(phone_data: PhoneData) -> Database {
+        let mut versions = VersionedLookup::new();
+        versions.insert(phone_data).expect("first version cannot be a duplicate");
+        Database { versions, loaded_at: SystemTime::now(), load_duration: Duration::ZERO, verification: Verification::None, stats: OnceLock::new() }
+    }
+
+    /// 最新版本，未指定版本的查询使用这份数据
+    pub fn phone_data(&self) -> &PhoneData {
+        self.versions.latest().expect("database holds at least one version")
+    }
+
+    /// 最新版本的统计报告，计算一次后缓存
+    pub fn stats(&self) -> Result<&DatasetStats> {
+        if let Some(stats) = self.stats.get() {
+            return Ok(stats);
+        }
+        let phone_data = self.phone_data();
+        let entries = phone_data.entries().collect::<Result<Vec<_>>>()?;
+        let _ = self.stats.set(DatasetStats::compute(phone_data.version().to_string(), &entries));
+        Ok(self.stats.get().expect("stats were just set"))
+    }
+}
+
+/// 读取并校验数据文件，`verification`取所有文件中最弱的校验
+fn load_verified(path: &Path, policy: &IntegrityPolicy, verification: &mut Verification) -> Result<PhoneData> {
+    let data = std::fs::read(path)?;
+    *verification = (*verification).min(policy.verify(path, &data)?);
+    let (header, records, index) = utils::parse_database(&data)?;
+    Ok(PhoneData::from_index(header.version, records, index))
+}
+
+/// 各worker共享的运行时状态
+pub struct AppState {
+    /// 当前数据，重新加载时整体替换
+    db: RwLock<Arc<Database>>,
+    /// 人工覆盖，重新加载时整体替换
+    overlay: RwLock<Arc<Overlay>>,
+    /// 携号转网表，默认为空
+    pub portability: PortabilityTable,
+    pub metrics: Metrics,
+    /// API Key认证和限流
+    pub access: AccessControl,
+    /// 访问日志，默认不输出
+    pub access_log: AccessLogger,
+    /// 是否接受流量
+    ready: AtomicBool,
+    /// 进行中的请求数
+    in_flight: AtomicUsize,
+    /// 是否已开始停机排空
+    draining: AtomicBool,
+    /// 排空期间完成的请求数
+    drained: AtomicUsize,
+    /// 排空期间被中止的请求数
+    aborted: AtomicUsize,
+}
+
+/// 停机排空结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrainReport {
+    /// 开始排空后正常完成的请求数
+    pub drained: usize,
+    /// 未能在排空超时前完成的请求数
+    pub aborted: usize,
+}
+
+/// 进行中请求的计数守卫，未调用`finish`就被丢弃的请求视为中止
+struct InFlight {
+    state: web::Data<AppState>,
+    finished: bool,
+}
+
+impl InFlight {
+    fn start(state: web::Data<AppState>) -> InFlight {
+        state.in_flight.fetch_add(1, Ordering::AcqRel);
+        InFlight { state, finished: false }
+    }
+
+    fn finish(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.state.in_flight.fetch_sub(1, Ordering::AcqRel);
+        if self.state.draining.load(Ordering::Acquire) {
+            let counter = if self.finished { &self.state.drained } else { &self.state.aborted };
+            counter.fetch_add(1, Ordering::AcqRel);
+        }
+    }
+}
+
+impl AppState {
+    /// 用已加载并自检通过的数据创建状态，创建后即为就绪
+    pub fn new(db: Database) -> AppState {
+        AppState {
+            db: RwLock::new(Arc::new(db)),
+            overlay: RwLock::new(Arc::new(Overlay::default())),
+            portability: PortabilityTable::default(),
+            metrics: Metrics::new(),
+            access: AccessControl::default(),
+            access_log: AccessLogger::default(),
+            ready: AtomicBool::new(true),
+            in_flight: AtomicUsize::new(0),
+            draining: AtomicBool::new(false),
+            drained: AtomicUsize::new(0),
+            aborted: AtomicUsize::new(0),
+        }
+    }
+
+    /// 启用API Key认证和限流，默认不做限制
+    pub fn with_access_control(mut self, access: AccessControl) -> AppState {
+        self.access = access;
+        self
+    }
+
+    /// 启用访问日志
+    pub fn with_access_log(mut self, access_log: AccessLogger) -> AppState {
+        self.access_log = access_log;
+        self
+    }
+
+    /// 启用人工覆盖
+    pub fn with_overlay(self, overlay: Overlay) -> AppState {
+        self.replace_overlay(overlay);
+        self
+    }
+
+    /// 启用携号转网表
+    pub fn with_portability(mut self, portability: PortabilityTable) -> AppState {
+        self.portability = portability;
+        self
+    }
+
+    /// 当前数据
+    pub fn db(&self) -> Arc<Database> {
+        self.db.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// 替换当前数据，正在处理的请求继续使用原数据
+    pub fn replace_db(&self, db: Database) {
+        *self.db.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(db);
+    }
+
+    /// 当前人工覆盖
+    pub fn overlay(&self) -> Arc<Overlay> {
+        self.overlay.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// 替换人工覆盖
+    pub fn replace_overlay(&self, overlay: Overlay) {
+        *self.overlay.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(overlay);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    pub fn set_ready(&self, ready: bool) {
+        self.ready.store(ready, Ordering::Release);
+    }
+
+    /// 进入停机排空：先置为未就绪，之后完成的请求计入已排空
+    pub fn begin_drain(&self) {
+        self.set_ready(false);
+        self.draining.store(true, Ordering::Release);
+    }
+
+    /// 当前进行中的请求数
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// 排空结果，仍未完成的请求计为中止
+    pub fn drain_report(&self) -> DrainReport {
+        DrainReport {
+            drained: self.drained.load(Ordering::Acquire),
+            aborted: self.aborted.load(Ordering::Acquire) + self.in_flight(),
+        }
+    }
+
+    /// 查找并记录指标，查找结果附在响应上供访问日志使用
+    ///
+    /// 未指定版本时叠加人工覆盖并按携号转网表修正运营商，指定版本的查询始终返回该版本的原始数据
+    ///
+    /// 查找结果只取决于号码前7位、数据版本和涉及该号段的覆盖及转网号码，据此生成`ETag`；
+    /// `If-None-Match`命中时返回304，但仍会查找以保证指标和日志完整
+    fn lookup(&self, req: &HttpRequest, config: &ServerConfig, phone: &str, version: Option<&str>) -> HttpResponse {
+        let db = self.db();
+        let Some(phone_data) = db.versions.select(version) else {
+            let message = format!("未加载数据版本 {}", version.unwrap_or_default());
+            return HttpResponse::NotFound().json(Message::<()>::err_with_code(404, &message));
+        };
+        let overlay = if version.is_none() { self.overlay() } else { Arc::new(Overlay::default()) };
+        let no_portability = PortabilityTable::default();
+        let portability = if version.is_none() { &self.portability } else { &no_portability };
+        let (result, mut stats) = phone_data.find_with_stats(phone);
+        let result = overlay.apply(phone, result);
+        if result.as_ref().is_ok_and(|info| info.applied.is_some()) {
+            stats.outcome = LookupOutcome::Found;
+        }
+        let observed = result.as_ref().map(|overlaid| overlaid.info.clone()).map_err(|e| anyhow!("{:#}", e));
+        self.metrics.observe_lookup(&observed, &stats);
+
+        let etag = (stats.outcome != LookupOutcome::InvalidLength)
+            .then(|| lookup_etag(phone_data.version(), utils::phone_prefix(phone), [overlay.cache_key(phone), portability.cache_key(phone)]));
+        let not_modified = etag.as_deref().is_some_and(|etag| {
+            req.headers()
+                .get(IF_NONE_MATCH)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| etag_matches(value, etag))
+        });
+
+        let mut response = if not_modified {
+            HttpResponse::NotModified().finish()
+        } else {
+            match result {
+                Ok(info) => {
+                    let carrier = portability.resolve(phone, &info.info);
+                    HttpResponse::Ok().json(Message::ok(LookupData { info, carrier }))
+                }
+                Err(_) => HttpResponse::Ok().json(Message::<()>::err("查询失败")),
+            }
+        };
+        if let Some(etag) = etag {
+            let headers = response.headers_mut();
+            headers.insert(ETAG, HeaderValue::from_str(&etag).expect("etag is visible ascii"));
+            let cache_control = cache_control(config.cache_max_age, self.access.auth_enabled());
+            headers.insert(CACHE_CONTROL, HeaderValue::from_str(&cache_control).expect("cache-control is visible ascii"));
+        }
+        response.extensions_mut().insert(LookupRecord {
+            db_version: Some(phone_data.version().to_string()),
+            ..LookupRecord::new(phone, stats.outcome)
+        });
+        response
+    }
+}
+
+/// 查找结果的强校验`ETag`，数据版本、涉及该号段的覆盖或转网号码变化后自动失效
+///
+/// `variants`为覆盖和转网表给出的缓存键，都为空时同一号段共用一个`ETag`
+fn lookup_etag(version: &str, prefix: &str, variants: [Option<u64>; 2]) -> String {
+    let version: String = version.chars().filter(|c| c.is_ascii_graphic() && *c != '"').collect();
+    if variants.iter().all(Option::is_none) {
+        return format!("\"{}-{}\"", version, prefix);
+    }
+    // 每个缓存键编码为1字节的有无标记加8字节小端值，摘要跨Rust版本稳定
+    let mut encoded = Vec::with_capacity(variants.len() * 9);
+    for variant in variants {
+        encoded.push(variant.is_some() as u8);
+        encoded.extend_from_slice(&variant.unwrap_or(0).to_le_bytes());
+    }
+    format!("\"{}-{}-{:016x}\"", version, prefix, utils::stable_hash(&[&encoded]))
+}
+
+/// `If-None-Match`是否命中，按弱比较处理`W/`前缀，支持`*`和逗号分隔的多个值
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// 启用API Key认证时只允许客户端缓存，避免共享缓存绕过认证
+fn cache_control(max_age: Duration, private: bool) -> String {
+    let scope = if private { "private" } else { "public" };
+    if max_age.is_zero() {
+        format!("{}, no-cache", scope)
+    } else {
+        format!("{}, max-age={}", scope, max_age.as_secs())
+    }
+}
+
+/// 查询接口返回的数据：叠加覆盖后的归属地和携号转网后的运营商
+#[derive(Debug, Serialize)]
+pub(crate) struct LookupData {
+    #[serde(flatten)]
+    info: OverlaidInfo,
+    #[serde(flatten)]
+    carrier: CarrierInfo,
+}
+
+/// 一次查找的号码和结果，只保存脱敏后的号码
+#[derive(Debug, Clone)]
+struct LookupRecord {
+    phone: String,
+    outcome: LookupOutcome,
+    /// 实际查询的数据版本
+    db_version: Option<String>,
+}
+
+impl LookupRecord {
+    fn new(phone: &str, outcome: LookupOutcome) -> Self {
+        LookupRecord { phone: utils::mask_phone_no(phone), outcome, db_version: None }
+    }
+}
+
+/// 统一响应结构
+#[derive(Debug, Serialize)]
+pub struct Message<T>
+where
+    T: serde::Serialize,
+{
+    pub code: i32,
+    pub data: Option<T>,
+    pub success: bool,
+    pub result: String,
+}
+
+impl<T: serde::Serialize> Message<T> {
+    pub fn ok(data: T) -> Self {
+        Message { code: 0, result: "ok".to_owned(), data: Some(data), success: true }
+    }
+    pub fn err(message: &str) -> Self {
+        Self::err_with_code(-1, message)
+    }
+    pub fn err_with_code(code: i32, message: &str) -> Self {
+        Message { code, result: message.to_owned(), data: None, success: false }
+    }
+}
+
+
+#[derive(Debug, Deserialize)]
+struct IParams {
+    phone: String,
+    /// 查询的数据版本，默认最新版本
+    version: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VersionParams {
+    version: Option<String>,
+}
+
+async fn query_phone(req: HttpRequest, config: web::Data<ServerConfig>, state: web::Data<AppState>, info: web::Query<IParams>) -> impl Responder {
+    let params: IParams = info.into_inner();
+    query_with_privacy(&req, &config, &state, &params.phone, params.version.as_deref())
+}
+
+async fn query_phone2(
+    req: HttpRequest,
+    config: web::Data<ServerConfig>,
+    state: web::Data<AppState>,
+    phone: web::Path<String>,
+    params: web::Query<VersionParams>,
+) -> impl Responder {
+    let str = phone.into_inner();
+    query_with_privacy(&req, &config, &state, &str, params.version.as_deref())
+}
+
+/// 按隐私模式处理完整号码后查询，拒绝时只回显脱敏后的号码
+fn query_with_privacy(req: &HttpRequest, config: &ServerConfig, state: &AppState, phone: &str, version: Option<&str>) -> HttpResponse {
+    match config.privacy_mode {
+        PrivacyMode::Off => state.lookup(req, config, phone, version),
+        PrivacyMode::Truncate => state.lookup(req, config, utils::phone_prefix(phone), version),
+        PrivacyMode::Reject if utils::phone_len(phone) > utils::PHONE_PREFIX_LEN => {
+            let message = format!("只接受号码前7位: {}", utils::mask_phone_no(phone));
+            let mut response = HttpResponse::BadRequest().json(Message::<()>::err_with_code(400, &message));
+            response.extensions_mut().insert(LookupRecord::new(phone, LookupOutcome::InvalidLength));
+            response
+        }
+        PrivacyMode::Reject => state.lookup(req, config, phone, version),
+    }
+}
+
+/// 号段查询，只接受号码前7位
+async fn query_prefix(
+    req: HttpRequest,
+    config: web::Data<ServerConfig>,
+    state: web::Data<AppState>,
+    prefix: web::Path<String>,
+    params: web::Query<VersionParams>,
+) -> impl Responder {
+    let prefix = prefix.into_inner();
+    if prefix.len() != utils::PHONE_PREFIX_LEN || !prefix.bytes().all(|b| b.is_ascii_digit()) {
+        let message = format!("号段必须是7位数字: {}", utils::mask_phone_no(&prefix));
+        let mut response = HttpResponse::BadRequest().json(Message::<()>::err_with_code(400, &message));
+        response.extensions_mut().insert(LookupRecord::new(&prefix, LookupOutcome::InvalidLength));
+        return response;
+    }
+    state.lookup(&req, &config, &prefix, params.version.as_deref())
+}
+
+/// 超出配额时返回的限流信息
+#[derive(Debug, Serialize)]
+pub(crate) struct RateLimited {
+    limit: u32,
+    remaining: u32,
+    /// 距离下一个令牌可用的秒数
+    retry_after: u64,
+}
+
+/// 不足一秒按一秒计，避免客户端立即重试
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs_f64().ceil() as u64
+}
+
+fn set_rate_limit_headers(headers: &mut HeaderMap, status: &RateLimitStatus) {
+    for (name, value) in [
+        ("x-ratelimit-limit", status.limit as u64),
+        ("x-ratelimit-remaining", status.remaining as u64),
+        ("x-ratelimit-reset", ceil_secs(status.reset_after)),
+    ] {
+        headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+    }
+    if let Some(retry_after) = status.retry_after {
+        headers.insert(RETRY_AFTER, HeaderValue::from(ceil_secs(retry_after)));
+    }
+}
+
+/// 请求中的API Key，支持`X-API-Key`头和`Authorization: Bearer`
+fn api_key(req: &ServiceRequest) -> Option<&str> {
+    let headers = req.headers();
+    if let Some(key) = headers.get("x-api-key").and_then(|value| value.to_str().ok()) {
+        return Some(key.trim());
+    }
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// 请求中的管理Key，支持`X-Admin-Key`头和`Authorization: Bearer`
+fn admin_key(req: &ServiceRequest) -> Option<&str> {
+    let headers = req.headers();
+    if let Some(key) = headers.get("x-admin-key").and_then(|value| value.to_str().ok()) {
+        return Some(key.trim());
+    }
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// 逐字节比较，耗时不随第一个不同字节的位置变化
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// 客户端IP，只有信任代理时才读取转发头
+fn client_ip(req: &ServiceRequest, trust_proxy: bool) -> String {
+    if trust_proxy && let Some(ip) = req.connection_info().realip_remote_addr() {
+        return ip.to_string();
+    }
+    req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_else(|| "unknown".to_string())
+}
+
+/// 查询接口的认证和限流中间件
+async fn access_control(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let (Some(state), Some(config)) = (
+        req.app_data::<web::Data<AppState>>().cloned(),
+        req.app_data::<web::Data<ServerConfig>>().cloned(),
+    ) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    let ip = client_ip(&req, config.trust_proxy);
+    match state.access.check(api_key(&req), &ip, Instant::now()) {
+        AccessDecision::Allowed { rate_limit, .. } => {
+            let mut response = next.call(req).await?.map_into_boxed_body();
+            if let Some(status) = rate_limit {
+                set_rate_limit_headers(response.headers_mut(), &status);
+            }
+            Ok(response)
+        }
+        AccessDecision::Unauthorized => {
+            let response = HttpResponse::Unauthorized().json(Message::<()>::err_with_code(401, "无效的API Key"));
+            Ok(req.into_response(response))
+        }
+        AccessDecision::Limited(status) => {
+            let body = Message {
+                data: Some(RateLimited {
+                    limit: status.limit,
+                    remaining: status.remaining,
+                    retry_after: status.retry_after.map(ceil_secs).unwrap_or(0),
+                }),
+                ..Message::err_with_code(429, "请求过于频繁")
+            };
+            let mut response = HttpResponse::TooManyRequests().json(body);
+            set_rate_limit_headers(response.headers_mut(), &status);
+            Ok(req.into_response(response))
+        }
+    }
+}
+
+/// 统计报告的输出格式
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum StatsFormat {
+    #[default]
+    Json,
+    Table,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatsParams {
+    #[serde(default)]
+    format: StatsFormat,
+}
+
+/// 当前数据的统计报告，`?format=table`时返回文本表格
+#[get("/stats")]
+async fn dataset_stats(state: web::Data<AppState>, params: web::Query<StatsParams>) -> impl Responder {
+    let db = state.db();
+    let stats = match web::block(move || db.stats().cloned()).await {
+        Ok(Ok(stats)) => stats,
+        _ => return HttpResponse::Ok().json(Message::<()>::err("统计失败")),
+    };
+    match params.format {
+        StatsFormat::Json => HttpResponse::Ok().json(Message::ok(stats)),
+        StatsFormat::Table => HttpResponse::Ok().content_type("text/plain; charset=utf-8").body(stats.table()),
+    }
+}
+
+/// Prometheus文本格式的指标
+#[get("/metrics")]
+async fn metrics(state: web::Data<AppState>) -> impl Responder {
+    let body = state.metrics.render(state.db().phone_data());
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(body)
+}
+
+/// 管理接口的认证中间件：未配置管理Key时返回403，Key缺少或错误时返回401
+async fn admin_auth(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let expected = req.app_data::<web::Data<ServerConfig>>().and_then(|config| config.admin_key.clone());
+    let Some(expected) = expected else {
+        let response = HttpResponse::Forbidden().json(Message::<()>::err_with_code(403, "未配置管理Key，管理接口不可用"));
+        return Ok(req.into_response(response));
+    };
+    if !admin_key(&req).is_some_and(|key| constant_time_eq(key.as_bytes(), expected.as_bytes())) {
+        let response = HttpResponse::Unauthorized().json(Message::<()>::err_with_code(401, "无效的管理Key"));
+        return Ok(req.into_response(response));
+    }
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ReloadResult {
+    version: String,
+    total_entries: usize,
+}
+
+/// 重新加载数据文件，加载或自检失败时继续使用原数据
+async fn reload(config: web::Data<ServerConfig>, state: web::Data<AppState>) -> impl Responder {
+    let loaded = web::block(move || Database::load(&config)).await;
+    let msg = match loaded {
+        Ok(Ok(db)) => {
+            let result = ReloadResult {
+                version: db.phone_data().version().to_string(),
+                total_entries: db.phone_data().total_entries(),
+            };
+            state.replace_db(db);
+            state.metrics.observe_reload(true);
+            Message::ok(result)
+        }
+        _ => {
+            state.metrics.observe_reload(false);
+            Message::err("重新加载失败")
+        }
+    };
+    HttpResponse::Ok().json(msg)
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct KeysReloadResult {
+    keys: usize,
+}
+
+/// 重新读取API Key文件，读取失败时继续使用原列表
+async fn reload_keys(state: web::Data<AppState>) -> impl Responder {
+    let reload_state = state.clone();
+    let msg = match web::block(move || reload_state.access.reload_keys()).await {
+        Ok(Ok(keys)) => Message::ok(KeysReloadResult { keys }),
+        _ => Message::err("重新加载API Key失败"),
+    };
+    HttpResponse::Ok().json(msg)
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct OverlayReloadResult {
+    entries: usize,
+}
+
+/// 重新读取人工覆盖文件，读取失败时继续使用原覆盖
+async fn reload_overlay(config: web::Data<ServerConfig>, state: web::Data<AppState>) -> impl Responder {
+    if config.overlay_path.is_none() {
+        return HttpResponse::Ok().json(Message::<()>::err("未配置人工覆盖文件"));
+    }
+    let msg = match web::block(move || config.overlay()).await {
+        Ok(Ok(overlay)) => {
+            let result = OverlayReloadResult { entries: overlay.len() };
+            state.replace_overlay(overlay);
+            Message::ok(result)
+        }
+        _ => Message::err("重新加载人工覆盖失败"),
+    };
+    HttpResponse::Ok().json(msg)
+}
+
+/// 已加载的数据版本及条目数
+#[get("/versions")]
+async fn list_versions(state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(Message::ok(state.db().versions.versions()))
+}
+
+/// OpenAPI 3文档
+#[get("/openapi.json")]
+async fn openapi_json() -> impl Responder {
+    HttpResponse::Ok().json(openapi::spec())
+}
+
+/// 读取`/openapi.json`渲染的接口文档页面
+#[get("/docs")]
+async fn docs() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(openapi::DOCS_HTML)
+}
+
+/// 存活探针：进程能处理请求即返回200
+#[get("/healthz")]
+async fn healthz() -> impl Responder {
+    HttpResponse::Ok().json(Message::ok("alive"))
+}
+
+/// 就绪探针：数据加载且自检通过后才返回200
+#[get("/readyz")]
+async fn readyz(state: web::Data<AppState>) -> impl Responder {
+    if state.is_ready() {
+        HttpResponse::Ok().json(Message::ok("ready"))
+    } else {
+        HttpResponse::ServiceUnavailable().json(Message::<()>::err("not ready"))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ServiceInfo {
+    /// 查找实现
+    backend: &'static str,
+    /// 数据版本
+    version: String,
+    total_entries: usize,
+    memory_usage_bytes: usize,
+    /// 数据加载完成的Unix时间戳（秒）
+    loaded_at: u64,
+    /// 数据加载耗时（毫秒）
+    load_duration_ms: u128,
+    /// 数据文件通过的校验
+    verification: Verification,
+    /// 服务构建版本
+    build_version: &'static str,
+}
+
+/// 服务和当前数据的基本信息
+#[get("/info")]
+async fn service_info(state: web::Data<AppState>) -> impl Responder {
+    let db = state.db();
+    let info = ServiceInfo {
+        backend: backend_name(),
+        version: db.phone_data().version().to_string(),
+        total_entries: db.phone_data().total_entries(),
+        memory_usage_bytes: db.versions.iter().map(|data| data.memory_usage_bytes()).sum(),
+        loaded_at: db.loaded_at.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        load_duration_ms: db.load_duration.as_millis(),
+        verification: db.verification,
+        build_version: env!("CARGO_PKG_VERSION"),
+    };
+    HttpResponse::Ok().json(Message::ok(info))
+}
+
+/// 查找实现的类型名，去掉模块路径
+fn backend_name() -> &'static str {
+    let name = std::any::type_name::<PhoneData>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// 构建应用，`config`和`state`在所有worker之间共享
+pub fn build_app(
+    config: web::Data<ServerConfig>,
+    state: web::Data<AppState>,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    let metrics_state = state.clone();
+    App::new()
+        .app_data(config)
+        .app_data(state)
+        .wrap_fn(move |req, srv| {
+            // 以路由模板作为endpoint标签，未匹配的路径统一归入一类
+            let endpoint = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+            let method = req.method().to_string();
+            let state = metrics_state.clone();
+            let in_flight = InFlight::start(state.clone());
+            let start = Instant::now();
+            let response = srv.call(req);
+            async move {
+                let response = response.await;
+                in_flight.finish();
+                let response = response?;
+                let latency = start.elapsed();
+                state.metrics.observe_request(&endpoint, response.status().as_u16(), latency);
+                if state.access_log.sample() {
+                    let record = response.response().extensions().get::<LookupRecord>().cloned();
+                    let db_version = record
+                        .as_ref()
+                        .and_then(|r| r.db_version.clone())
+                        .unwrap_or_else(|| state.db().phone_data().version().to_string());
+                    state.access_log.log(&AccessLogEntry {
+                        timestamp: format_timestamp(SystemTime::now()),
+                        method,
+                        route: endpoint,
+                        status: response.status().as_u16(),
+                        latency_ms: latency.as_secs_f64() * 1000.0,
+                        error_kind: record.as_ref().map(|r| r.outcome).filter(|o| *o != LookupOutcome::Found).map(|o| o.as_str()),
+                        backend: backend_name(),
+                        db_version,
+                        phone: record.map(|r| r.phone),
+                    });
+                }
+                Ok(response)
+            }
+        })
+        .service(healthz)
+        .service(readyz)
+        .service(service_info)
+        .service(web::resource("/query").wrap(from_fn(access_control)).route(web::get().to(query_phone)))
+        .service(web::resource("/query2/{phone}").wrap(from_fn(access_control)).route(web::get().to(query_phone2)))
+        .service(web::resource("/prefix/{prefix}").wrap(from_fn(access_control)).route(web::get().to(query_prefix)))
+        .service(metrics)
+        .service(dataset_stats)
+        .service(
+            web::scope("/admin")
+                .wrap(from_fn(admin_auth))
+                .route("/reload", web::post().to(reload))
+                .route("/keys/reload", web::post().to(reload_keys))
+                .route("/overlay/reload", web::post().to(reload_overlay)),
+        )
+        .service(list_versions)
+        .service(openapi_json)
+        .service(docs)
+}
+
+/// 启动HTTP服务，收到SIGTERM或SIGINT后优雅停机
+pub async fn serve(config: ServerConfig, state: AppState) -> std::io::Result<()> {
+    let bind_addr = config.bind_addr.clone();
+    let workers = config.workers;
+    let readiness_delay = config.readiness_delay;
+    let drain_timeout = config.drain_timeout;
+    let config = web::Data::new(config);
+    let state = web::Data::new(state);
+
+    let app_state = state.clone();
+    let server = HttpServer::new(move || build_app(config.clone(), app_state.clone()))
+        .workers(workers)
+        .disable_signals()
+        .shutdown_timeout(drain_timeout.as_secs())
+        .bind(bind_addr)?
+        .run();
+
+    let handle = server.handle();
+    let shutdown_state = state.clone();
+    actix_web::rt::spawn(async move {
+        let signal = wait_for_signal().await;
+        println!("received {}, shutting down", signal);
+        shutdown(&handle, &shutdown_state, readiness_delay).await;
+    });
+
+    server.await?;
+
+    let report = state.drain_report();
+    println!("shutdown complete: {} requests drained, {} aborted", report.drained, report.aborted);
+    Ok(())
+}
+
+/// 优雅停机：先置为未就绪并等待负载均衡摘除，再关闭监听，最多等待排空超时
+pub async fn shutdown(handle: &ServerHandle, state: &AppState, readiness_delay: Duration) {
+    state.begin_drain();
+    println!("readiness set to not ready, closing listener in {:?}", readiness_delay);
+    actix_web::rt::time::sleep(readiness_delay).await;
+
+    println!("listener closed, draining {} in-flight requests", state.in_flight());
+    handle.stop(true).await;
+}
+
+/// 等待退出信号，返回信号名称
+async fn wait_for_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = terminate.recv() => "SIGTERM",
+                    _ = tokio::signal::ctrl_c() => "SIGINT",
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "SIGINT"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_state() -> web::Data<AppState> {
+        let phone_data = PhoneData::from_index("0000".to_string(), Vec::new(), Vec::new());
+        web::Data::new(AppState::new(Database::single(phone_data)))
+    }
+
+    #[test]
+    fn test_drain_counts_finished_and_dropped_requests() {
+        let state = empty_state();
+
+        // 排空前完成的请求不计入
+        InFlight::start(state.clone()).finish();
+        let pending = InFlight::start(state.clone());
+        let dropped = InFlight::start(state.clone());
+        let still_running = InFlight::start(state.clone());
+        assert_eq!(state.in_flight(), 3);
+
+        state.begin_drain();
+        assert!(!state.is_ready());
+        pending.finish();
+        drop(dropped);
+
+        assert_eq!(state.drain_report(), DrainReport { drained: 1, aborted: 2 });
+        drop(still_running);
+        assert_eq!(state.drain_report(), DrainReport { drained: 1, aborted: 2 });
+    }
+
+    #[test]
+    fn test_etag_matching_and_cache_control() {
+        let etag = lookup_etag("2410", "1808683", [None, None]);
+        assert_eq!(etag, "\"2410-1808683\"");
+        assert!(etag_matches("\"2410-1808683\"", &etag));
+        assert!(etag_matches("W/\"2410-1808683\"", &etag));
+        assert!(etag_matches("\"2409-1808683\", \"2410-1808683\"", &etag));
+        assert!(etag_matches("*", &etag));
+        assert!(!etag_matches("\"2409-1808683\"", &etag));
+        assert_eq!(lookup_etag("24\"1\n0", "1808683", [None, None]), "\"2410-1808683\"");
+        let overlaid = lookup_etag("2410", "1808683", [Some(1), None]);
+        assert!(overlaid.starts_with("\"2410-1808683-") && overlaid.len() == etag.len() + 17, "{}", overlaid);
+        assert_ne!(overlaid, lookup_etag("2410", "1808683", [None, Some(1)]));
+
+        assert_eq!(cache_control(Duration::ZERO, false), "public, no-cache");
+        assert_eq!(cache_control(Duration::from_secs(3600), true), "private, max-age=3600");
+    }
+}
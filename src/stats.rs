@@ -0,0 +1,211 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::Path;
+use anyhow::Result;
+use serde_derive::Serialize;
+
+use crate::common::PhoneNoInfo;
+use crate::diff::{load_entries, pad};
+
+/// 每个3位号段包含的前7位数量
+pub const PREFIXES_PER_SEGMENT: usize = 10_000;
+
+/// 报告中列出的空缺数
+pub const LARGEST_GAPS: usize = 10;
+
+/// 一个省份的号段数和城市数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ProvinceStats {
+    pub prefixes: usize,
+    /// 不同城市数
+    pub cities: usize,
+}
+
+/// 一个3位号段的覆盖情况
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SegmentStats {
+    /// 号码前3位，如130
+    pub segment: i32,
+    pub prefixes: usize,
+    /// 已收录的前7位占号段内10000个的比例
+    pub coverage: f64,
+}
+
+/// 号段内连续未收录的前7位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PrefixGap {
+    /// 第一个未收录的前7位
+    pub start: i32,
+    /// 最后一个未收录的前7位
+    pub end: i32,
+    pub length: usize,
+}
+
+/// 数据内容的统计报告
+#[derive(Debug, Clone, Serialize)]
+pub struct DatasetStats {
+    pub version: String,
+    pub total_prefixes: usize,
+    /// 不同城市数，不同省份的同名城市分别计数
+    pub distinct_cities: usize,
+    pub by_carrier: BTreeMap<String, usize>,
+    pub by_province: BTreeMap<String, ProvinceStats>,
+    /// 按号段升序，只包含至少收录了一个前7位的号段
+    pub by_segment: Vec<SegmentStats>,
+    /// 已收录号段内最长的空缺，按长度降序
+    pub largest_gaps: Vec<PrefixGap>,
+}
+
+/// 统计数据文件
+pub fn dataset_stats<P: AsRef<Path>>(path: P) -> Result<DatasetStats> {
+    let (version, entries) = load_entries(path)?;
+    Ok(DatasetStats::compute(version, &entries))
+}
+
+impl DatasetStats {
+    /// 统计按号段排序的记录
+    pub fn compute(version: String, entries: &[(i32, PhoneNoInfo)]) -> DatasetStats {
+        let mut by_carrier = BTreeMap::new();
+        let mut by_province: BTreeMap<String, ProvinceStats> = BTreeMap::new();
+        let mut province_cities: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        let mut by_segment: Vec<SegmentStats> = Vec::new();
+        let mut gaps = Vec::new();
+
+        let mut next = 0;
+        for (prefix, info) in entries {
+            *by_carrier.entry(info.card_type.clone()).or_default() += 1;
+            by_province.entry(info.province.clone()).or_default().prefixes += 1;
+            province_cities.entry(&info.province).or_default().insert(&info.city);
+
+            let segment = prefix / PREFIXES_PER_SEGMENT as i32;
+            if by_segment.last().is_none_or(|last| last.segment != segment) {
+                if let Some(last) = by_segment.last() {
+                    push_gap(&mut gaps, next, segment_end(last.segment));
+                }
+                by_segment.push(SegmentStats { segment, prefixes: 0, coverage: 0.0 });
+                next = segment * PREFIXES_PER_SEGMENT as i32;
+            }
+            push_gap(&mut gaps, next, prefix - 1);
+            by_segment.last_mut().expect("segment was just pushed").prefixes += 1;
+            next = prefix + 1;
+        }
+        if let Some(last) = by_segment.last() {
+            push_gap(&mut gaps, next, segment_end(last.segment));
+        }
+
+        let mut distinct_cities = 0;
+        for (province, cities) in province_cities {
+            by_province.get_mut(province).expect("province was counted").cities = cities.len();
+            distinct_cities += cities.len();
+        }
+        for segment in &mut by_segment {
+            segment.coverage = segment.prefixes as f64 / PREFIXES_PER_SEGMENT as f64;
+        }
+        gaps.sort_by_key(|gap: &PrefixGap| (std::cmp::Reverse(gap.length), gap.start));
+        gaps.truncate(LARGEST_GAPS);
+
+        DatasetStats {
+            version,
+            total_prefixes: entries.len(),
+            distinct_cities,
+            by_carrier,
+            by_province,
+            by_segment,
+            largest_gaps: gaps,
+        }
+    }
+
+    /// JSON格式
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// 便于人读的表格
+    pub fn table(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "version {}: {} prefixes, {} distinct cities", self.version, self.total_prefixes, self.distinct_cities);
+
+        let _ = writeln!(out, "\n{}{:>10}", pad("carrier", 24), "prefixes");
+        for (carrier, prefixes) in &self.by_carrier {
+            let _ = writeln!(out, "{}{:>10}", pad(carrier, 24), prefixes);
+        }
+
+        let _ = writeln!(out, "\n{}{:>10}{:>10}", pad("province", 24), "prefixes", "cities");
+        for (province, stats) in &self.by_province {
+            let _ = writeln!(out, "{}{:>10}{:>10}", pad(province, 24), stats.prefixes, stats.cities);
+        }
+
+        let _ = writeln!(out, "\n{:<24}{:>10}{:>10}", "segment", "prefixes", "coverage");
+        for segment in &self.by_segment {
+            let _ = writeln!(out, "{:<24}{:>10}{:>9.2}%", segment.segment, segment.prefixes, segment.coverage * 100.0);
+        }
+
+        let _ = writeln!(out, "\n{:<24}{:>10}", "largest gaps", "length");
+        for gap in &self.largest_gaps {
+            let _ = writeln!(out, "{:<24}{:>10}", format!("{}-{}", gap.start, gap.end), gap.length);
+        }
+        out
+    }
+}
+
+/// 号段内最后一个前7位
+fn segment_end(segment: i32) -> i32 {
+    (segment + 1) * PREFIXES_PER_SEGMENT as i32 - 1
+}
+
+fn push_gap(gaps: &mut Vec<PrefixGap>, start: i32, end: i32) {
+    if start <= end {
+        gaps.push(PrefixGap { start, end, length: (end - start + 1) as usize });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(province: &str, city: &str, card_type: &str) -> PhoneNoInfo {
+        PhoneNoInfo::new(province.to_string(), city.to_string(), "000000".to_string(), "000".to_string(), card_type.to_string())
+    }
+
+    fn sample_stats() -> DatasetStats {
+        let entries = vec![
+            (1300000, info("北京", "北京", "中国联通")),
+            (1300001, info("北京", "北京", "中国联通")),
+            (1305000, info("吉林", "吉林", "中国联通")),
+            (1309999, info("吉林", "长春", "中国联通")),
+            (1808683, info("四川", "成都", "中国电信")),
+        ];
+        DatasetStats::compute("2410".to_string(), &entries)
+    }
+
+    #[test]
+    fn test_compute() {
+        let stats = sample_stats();
+        assert_eq!(stats.total_prefixes, 5);
+        assert_eq!(stats.distinct_cities, 4);
+        assert_eq!(stats.by_carrier["中国联通"], 4);
+        assert_eq!(stats.by_province["吉林"], ProvinceStats { prefixes: 2, cities: 2 });
+        assert_eq!(stats.by_province["北京"], ProvinceStats { prefixes: 2, cities: 1 });
+
+        let segments: Vec<(i32, usize)> = stats.by_segment.iter().map(|s| (s.segment, s.prefixes)).collect();
+        assert_eq!(segments, vec![(130, 4), (180, 1)]);
+        assert_eq!(stats.by_segment[0].coverage, 0.0004);
+
+        let gaps: Vec<(i32, i32, usize)> = stats.largest_gaps.iter().map(|g| (g.start, g.end, g.length)).collect();
+        assert_eq!(gaps, vec![(1800000, 1808682, 8683), (1300002, 1304999, 4998), (1305001, 1309998, 4998), (1808684, 1809999, 1316)]);
+    }
+
+    #[test]
+    fn test_empty_and_table() {
+        let empty = DatasetStats::compute("0000".to_string(), &[]);
+        assert_eq!((empty.total_prefixes, empty.distinct_cities), (0, 0));
+        assert!(empty.by_segment.is_empty() && empty.largest_gaps.is_empty());
+
+        let table = sample_stats().table();
+        assert!(table.starts_with("version 2410: 5 prefixes, 4 distinct cities\n"));
+        assert!(table.contains(&format!("{}{:>10}{:>10}\n", pad("吉林", 24), 2, 2)));
+        assert!(table.contains(&format!("{:<24}{:>10}{:>10}\n", 130, 4, "0.04%")));
+        assert!(table.contains(&format!("{:<24}{:>10}\n", "1800000-1808682", 8683)));
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&sample_stats().to_json()).unwrap()["by_carrier"]["中国电信"], 1);
+    }
+}
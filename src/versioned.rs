@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use anyhow::{anyhow, Result};
+use serde_derive::Serialize;
+
+use crate::common::{ErrorKind, PhoneLookup, PhoneNoInfo, PhoneStats};
+
+/// 一个已加载版本的概况
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VersionInfo {
+    pub version: String,
+    pub total_entries: usize,
+    /// 是否为默认查询的最新版本
+    pub latest: bool,
+}
+
+/// 按头部版本号同时持有多个版本的数据，用于按历史版本复现查询结果
+///
+/// 版本号按字符串排序，最大的为最新版本（phone.dat的版本号为`YYMM`格式）
+#[derive(Debug)]
+pub struct VersionedLookup<T> {
+    versions: BTreeMap<String, T>,
+}
+
+impl<T> Default for VersionedLookup<T> {
+    fn default() -> Self {
+        VersionedLookup { versions: BTreeMap::new() }
+    }
+}
+
+impl<T: PhoneLookup + PhoneStats> VersionedLookup<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 加入一个版本，版本号重复时返回错误
+    pub fn insert(&mut self, data: T) -> Result<()> {
+        let version = data.version().to_string();
+        if self.versions.contains_key(&version) {
+            return Err(anyhow!("database version {} is loaded twice", version));
+        }
+        self.versions.insert(version, data);
+        Ok(())
+    }
+
+    /// 最新版本
+    pub fn latest(&self) -> Option<&T> {
+        self.versions.values().next_back()
+    }
+
+    /// 指定版本
+    pub fn get(&self, version: &str) -> Option<&T> {
+        self.versions.get(version)
+    }
+
+    /// 按版本选择数据，未指定时使用最新版本
+    pub fn select(&self, version: Option<&str>) -> Option<&T> {
+        match version {
+            Some(version) => self.get(version),
+            None => self.latest(),
+        }
+    }
+
+    /// 在指定版本中查找，版本不存在时返回NotFound
+    pub fn find(&self, no: &str, version: Option<&str>) -> Result<PhoneNoInfo> {
+        self.select(version).ok_or(ErrorKind::NotFound)?.find(no)
+    }
+
+    /// 已加载的版本，按版本号升序
+    pub fn versions(&self) -> Vec<VersionInfo> {
+        let latest = self.versions.keys().next_back();
+        self.versions
+            .iter()
+            .map(|(version, data)| VersionInfo {
+                version: version.clone(),
+                total_entries: data.total_entries(),
+                latest: Some(version) == latest,
+            })
+            .collect()
+    }
+
+    /// 全部版本的数据，按版本号升序
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.versions.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.versions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Index;
+    use crate::PhoneData;
+
+    fn data(version: &str, city: &str) -> PhoneData {
+        let record = format!("四川|{}|610000|028\0", city);
+        PhoneData::from_index(version.to_string(), record.into_bytes(), vec![Index::new(1808683, 8, 3)])
+    }
+
+    #[test]
+    fn test_select_versions() {
+        let mut versioned = VersionedLookup::new();
+        versioned.insert(data("2406", "绵阳")).unwrap();
+        versioned.insert(data("2410", "成都")).unwrap();
+        versioned.insert(data("2312", "德阳")).unwrap();
+        assert!(versioned.insert(data("2410", "成都")).is_err());
+
+        assert_eq!(versioned.len(), 3);
+        assert_eq!(versioned.latest().unwrap().version(), "2410");
+        assert_eq!(versioned.find("18086834111", None).unwrap().city, "成都");
+        assert_eq!(versioned.find("18086834111", Some("2406")).unwrap().city, "绵阳");
+        assert!(versioned.find("18086834111", Some("2101")).is_err());
+
+        let versions: Vec<(String, bool)> = versioned.versions().into_iter().map(|v| (v.version, v.latest)).collect();
+        assert_eq!(versions, vec![("2312".to_string(), false), ("2406".to_string(), false), ("2410".to_string(), true)]);
+        assert!(VersionedLookup::<PhoneData>::new().latest().is_none());
+    }
+}
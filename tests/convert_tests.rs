@@ -0,0 +1,99 @@
+use std::path::Path;
+use std::process::Command;
+use phone_data::compression::{self, Compression};
+use phone_data::{binary_search, PhoneData, PhoneDataBloom, PhoneDataEytzinger, PhoneDataHash, PhoneDataV2, PhoneLookup, PhoneStats};
+
+/// 写入v1测试数据文件，`entries`为(号段, 记录, 卡类型)
+fn write_database(path: &Path, version: &str, entries: &[(i32, &str, u8)]) {
+    let mut body = Vec::new();
+    let mut offsets = Vec::new();
+    for (_, record, _) in entries {
+        offsets.push(8 + body.len() as i32);
+        body.extend_from_slice(record.as_bytes());
+        body.push(0);
+    }
+
+    let mut data = Vec::new();
+    data.extend_from_slice(version.as_bytes());
+    data.extend_from_slice(&(8 + body.len() as i32).to_le_bytes());
+    data.extend_from_slice(&body);
+    for ((prefix, _, card_type), offset) in entries.iter().zip(offsets) {
+        data.extend_from_slice(&prefix.to_le_bytes());
+        data.extend_from_slice(&offset.to_le_bytes());
+        data.push(*card_type);
+    }
+    std::fs::write(path, data).unwrap();
+}
+
+#[test]
+fn test_convert_cli() {
+    let dir = std::env::temp_dir();
+    let input = dir.join(format!("phone_data_convert_v1_{}.dat", std::process::id()));
+    let output = dir.join(format!("phone_data_convert_v2_{}.dat", std::process::id()));
+    write_database(&input, "2410", &[
+        (1300000, "北京|北京|100000|010", 2),
+        (1300001, "北京|北京|100000|010", 2),
+        (1808683, "四川|成都|610000|028", 3),
+    ]);
+
+    let run = |args: &[&Path]| Command::new(env!("CARGO_BIN_EXE_phone_data")).arg("convert").args(args).output().unwrap();
+    let result = run(&[&input, &output]);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    let stdout = String::from_utf8(result.stdout).unwrap();
+    assert!(stdout.starts_with("converted version 2410: 3 entries, 2 records"), "{}", stdout);
+
+    let v2 = PhoneDataV2::from_path(&output).unwrap();
+    assert_eq!((v2.version(), v2.total_entries()), ("2410", 3));
+    assert_eq!(v2.find("18086834111").unwrap().city, "成都");
+    // v1读取入口同样能加载v2文件
+    assert_eq!(PhoneData::from_path(&output).unwrap().find("13000011234").unwrap().card_type, "中国联通");
+
+    assert!(!run(&[&input]).status.success());
+    assert!(!run(&[Path::new("/nonexistent/phone.dat"), &output]).status.success());
+}
+
+#[test]
+fn test_compressed_databases_load_in_every_constructor() {
+    let dir = std::env::temp_dir();
+    let plain = dir.join(format!("phone_data_compressed_{}.dat", std::process::id()));
+    write_database(&plain, "2410", &[(1300000, "北京|北京|100000|010", 2), (1808683, "四川|成都|610000|028", 3)]);
+    let data = std::fs::read(&plain).unwrap();
+
+    for compression in [Compression::Gzip, Compression::Zstd] {
+        let path = dir.join(format!("phone_data_compressed_{:?}_{}.dat", compression, std::process::id()));
+        std::fs::write(&path, compression::compress(&data, compression).unwrap()).unwrap();
+
+        let finders: Vec<Box<dyn PhoneLookup>> = vec![
+            Box::new(binary_search::PhoneData::from_path(&path).unwrap()),
+            Box::new(PhoneData::from_path(&path).unwrap()),
+            Box::new(PhoneDataHash::from_path(&path).unwrap()),
+            Box::new(PhoneDataBloom::from_path(&path).unwrap()),
+            Box::new(PhoneDataEytzinger::from_path(&path).unwrap()),
+            Box::new(PhoneDataV2::from_path(&path).unwrap()),
+        ];
+        for finder in finders {
+            assert_eq!(finder.find("18086834111").unwrap().city, "成都", "{:?}", compression);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn test_convert_cli_compressed_output() {
+    let dir = std::env::temp_dir();
+    let input = dir.join(format!("phone_data_convert_zstd_v1_{}.dat", std::process::id()));
+    let output = dir.join(format!("phone_data_convert_zstd_v2_{}.dat.zst", std::process::id()));
+    write_database(&input, "2410", &[(1300000, "北京|北京|100000|010", 2), (1808683, "四川|成都|610000|028", 3)]);
+
+    let run = |compress: &str| {
+        Command::new(env!("CARGO_BIN_EXE_phone_data")).arg("convert").arg(&input).arg(&output).args(["--compress", compress]).output().unwrap()
+    };
+    let result = run("zstd");
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    let written = std::fs::read(&output).unwrap();
+    assert_eq!(Compression::detect(&written), Compression::Zstd);
+    assert_eq!(PhoneDataV2::from_path(&output).unwrap().find("13000001234").unwrap().province, "北京");
+    assert_eq!(PhoneData::from_path(&output).unwrap().total_entries(), 2);
+
+    assert!(!run("lz4").status.success());
+}
@@ -4,7 +4,7 @@ include!("../src/lib.rs");
 fn phone_test() {
     let phone_data = PhoneData::new().unwrap();
     let phone = "18086834111";
-    let result = phone_data.find(&phone);
+    let result = phone_data.find(phone);
     let mut res = false;
     if let Ok(data) = result {
         println!("res: {:?}", data);
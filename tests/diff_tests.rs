@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use phone_data::diff::{diff_databases, ChangeCounts, ChangeKind};
+
+/// 写入测试数据文件，`entries`为(号段, 记录, 卡类型)
+fn write_database(path: &Path, version: &str, entries: &[(i32, &str, u8)]) {
+    let mut body = Vec::new();
+    let mut offsets = Vec::new();
+    for (_, record, _) in entries {
+        offsets.push(8 + body.len() as i32);
+        body.extend_from_slice(record.as_bytes());
+        body.push(0);
+    }
+
+    let mut data = Vec::new();
+    data.extend_from_slice(version.as_bytes());
+    data.extend_from_slice(&(8 + body.len() as i32).to_le_bytes());
+    data.extend_from_slice(&body);
+    for ((prefix, _, card_type), offset) in entries.iter().zip(offsets) {
+        data.extend_from_slice(&prefix.to_le_bytes());
+        data.extend_from_slice(&offset.to_le_bytes());
+        data.push(*card_type);
+    }
+    std::fs::write(path, data).unwrap();
+}
+
+fn fixture_pair(name: &str) -> (PathBuf, PathBuf) {
+    let dir = std::env::temp_dir();
+    let old = dir.join(format!("phone_data_diff_{}_old_{}.dat", name, std::process::id()));
+    let new = dir.join(format!("phone_data_diff_{}_new_{}.dat", name, std::process::id()));
+    write_database(&old, "2409", &[
+        (1300000, "北京|北京|100000|010", 2),
+        (1300001, "北京|北京|100000|010", 2),
+        (1808683, "四川|成都|610000|028", 3),
+    ]);
+    write_database(&new, "2410", &[
+        (1300000, "北京|北京|100000|010", 2),
+        (1808683, "四川|绵阳|621000|0816", 1),
+        (1990000, "广东|广州|510000|020", 3),
+    ]);
+    (old, new)
+}
+
+#[test]
+fn test_diff_databases() {
+    let (old, new) = fixture_pair("lib");
+    let diff = diff_databases(&old, &new).unwrap();
+    assert_eq!((diff.old_version.as_str(), diff.new_version.as_str()), ("2409", "2410"));
+    assert_eq!(diff.summary.total, ChangeCounts { added: 1, removed: 1, moved: 1, carrier_changed: 1 });
+
+    let moved = diff.changes.iter().find(|c| c.kind == ChangeKind::Moved).unwrap();
+    assert_eq!(moved.prefix, 1808683);
+    assert_eq!(moved.old.as_ref().unwrap().city, "成都");
+    assert_eq!(moved.new.as_ref().unwrap().city, "绵阳");
+    assert_eq!(diff.summary.by_carrier["中国移动"].carrier_changed, 1);
+    assert_eq!(diff.summary.by_province["广东"].added, 1);
+
+    assert!(diff_databases(&old, "/nonexistent/phone.dat").is_err());
+}
+
+#[test]
+fn test_diff_cli() {
+    let (old, new) = fixture_pair("cli");
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_phone_data")).arg("diff").args(args).output().unwrap();
+        (output.status.success(), String::from_utf8(output.stdout).unwrap())
+    };
+    let (old, new) = (old.to_str().unwrap(), new.to_str().unwrap());
+
+    let (ok, json) = run(&[old, new]);
+    assert!(ok);
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["summary"]["total"]["added"], 1);
+
+    let (ok, csv) = run(&[old, new, "--format", "csv"]);
+    assert!(ok);
+    assert_eq!(csv.lines().count(), 5);
+    assert!(csv.contains("removed,1300001,北京,北京,100000,010,中国联通,,,,,"));
+
+    let (ok, summary) = run(&[old, new, "--format=summary"]);
+    assert!(ok);
+    assert!(summary.starts_with("2409 (3 entries) -> 2410 (3 entries)"));
+
+    assert!(!run(&[old]).0);
+    assert!(!run(&[old, new, "--format", "xml"]).0);
+}
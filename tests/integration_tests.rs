@@ -1,5 +1,8 @@
-use phone_data::{PhoneData, PhoneLookup, PhoneDataHash, PhoneDataSimd, PhoneDataBloom};
-use phone_data::common::{PhoneStats, ErrorKind};
+use phone_data::{PhoneData, PhoneLookup, PhoneDataHash, PhoneDataSimd, PhoneDataBloom, PhoneDataEytzinger};
+use phone_data::binary_search;
+use phone_data::{ParallelConfig, ParallelLookup};
+use phone_data::phone_simd::SimdLevel;
+use phone_data::common::{PhoneStats, ErrorKind, LookupOutcome};
 
 /// 集成测试模块 - 测试所有算法实现的兼容性
 #[cfg(test)]
@@ -13,6 +16,7 @@ mod integration_tests {
         let hash_data = PhoneDataHash::new().expect("Failed to create hash data");
         let simd_data = PhoneDataSimd::new().expect("Failed to create SIMD data");
         let bloom_data = PhoneDataBloom::new().expect("Failed to create bloom data");
+        let eytzinger_data = PhoneDataEytzinger::new().expect("Failed to create eytzinger data");
 
         // 使用相同的测试用例验证所有实现
         let test_phones = vec![
@@ -28,23 +32,28 @@ mod integration_tests {
             let hash_result = hash_data.find(phone);
             let simd_result = simd_data.find(phone);
             let bloom_result = bloom_data.find(phone);
+            let eytzinger_result = eytzinger_data.find(phone);
 
             // 所有实现应该返回相同的结果
             assert!(binary_result.is_ok(), "Binary search failed for {}", phone);
             assert!(hash_result.is_ok(), "Hash lookup failed for {}", phone);
             assert!(simd_result.is_ok(), "SIMD lookup failed for {}", phone);
             assert!(bloom_result.is_ok(), "Bloom lookup failed for {}", phone);
+            assert!(eytzinger_result.is_ok(), "Eytzinger lookup failed for {}", phone);
 
             // 验证结果的省份字段不为空
             let binary_info = binary_result.unwrap();
             let hash_info = hash_result.unwrap();
             let simd_info = simd_result.unwrap();
             let bloom_info = bloom_result.unwrap();
+            let eytzinger_info = eytzinger_result.unwrap();
 
             assert!(!binary_info.province.is_empty(), "Binary search returned empty province");
             assert!(!hash_info.province.is_empty(), "Hash lookup returned empty province");
             assert!(!simd_info.province.is_empty(), "SIMD lookup returned empty province");
             assert!(!bloom_info.province.is_empty(), "Bloom lookup returned empty province");
+            assert_eq!(binary_info.city, eytzinger_info.city, "Eytzinger lookup returned different city");
+            assert_eq!(binary_info.card_type, eytzinger_info.card_type, "Eytzinger lookup returned different card type");
         }
     }
 
@@ -55,6 +64,7 @@ mod integration_tests {
         let hash_data = PhoneDataHash::new().expect("Failed to create hash data");
         let simd_data = PhoneDataSimd::new().expect("Failed to create SIMD data");
         let bloom_data = PhoneDataBloom::new().expect("Failed to create bloom data");
+        let eytzinger_data = PhoneDataEytzinger::new().expect("Failed to create eytzinger data");
 
         let invalid_phones = vec![
             "99999999999",  // 不存在的号段
@@ -70,12 +80,14 @@ mod integration_tests {
             let hash_result = hash_data.find(phone);
             let simd_result = simd_data.find(phone);
             let bloom_result = bloom_data.find(phone);
+            let eytzinger_result = eytzinger_data.find(phone);
 
             // 所有实现都应该失败
             assert!(binary_result.is_err(), "Binary search should fail for {}", phone);
             assert!(hash_result.is_err(), "Hash lookup should fail for {}", phone);
             assert!(simd_result.is_err(), "SIMD lookup should fail for {}", phone);
             assert!(bloom_result.is_err(), "Bloom lookup should fail for {}", phone);
+            assert!(eytzinger_result.is_err(), "Eytzinger lookup should fail for {}", phone);
 
             // 验证错误类型一致
             assert!(matches!(binary_result.err().unwrap().downcast_ref::<ErrorKind>(), Some(&ErrorKind::NotFound)));
@@ -83,6 +95,7 @@ mod integration_tests {
             assert!(matches!(simd_result.err().unwrap().downcast_ref::<ErrorKind>(), Some(&ErrorKind::NotFound)));
             // 布隆过滤器可能提前过滤，但也应该返回NotFound
             assert!(matches!(bloom_result.err().unwrap().downcast_ref::<ErrorKind>(), Some(&ErrorKind::NotFound)));
+            assert!(matches!(eytzinger_result.err().unwrap().downcast_ref::<ErrorKind>(), Some(&ErrorKind::NotFound)));
         }
     }
 
@@ -93,6 +106,7 @@ mod integration_tests {
         let hash_data = PhoneDataHash::new().expect("Failed to create hash data");
         let simd_data = PhoneDataSimd::new().expect("Failed to create SIMD data");
         let bloom_data = PhoneDataBloom::new().expect("Failed to create bloom data");
+        let eytzinger_data = PhoneDataEytzinger::new().expect("Failed to create eytzinger data");
 
         let test_phones = vec!["18086834111", "13800138000", "15900000000"];
 
@@ -100,10 +114,12 @@ mod integration_tests {
         let hash_results = hash_data.find_batch(&test_phones);
         let simd_results = simd_data.find_batch(&test_phones);
         let bloom_results = bloom_data.find_batch(&test_phones);
+        let eytzinger_results = eytzinger_data.find_batch(&test_phones);
 
         assert_eq!(binary_results.len(), hash_results.len());
         assert_eq!(binary_results.len(), simd_results.len());
         assert_eq!(binary_results.len(), bloom_results.len());
+        assert_eq!(binary_results.len(), eytzinger_results.len());
         assert_eq!(binary_results.len(), test_phones.len());
 
         for (i, phone) in test_phones.iter().enumerate() {
@@ -111,6 +127,7 @@ mod integration_tests {
             assert!(hash_results[i].is_ok(), "Hash lookup batch failed for {}", phone);
             assert!(simd_results[i].is_ok(), "SIMD lookup batch failed for {}", phone);
             assert!(bloom_results[i].is_ok(), "Bloom lookup batch failed for {}", phone);
+            assert!(eytzinger_results[i].is_ok(), "Eytzinger lookup batch failed for {}", phone);
         }
     }
 
@@ -121,15 +138,18 @@ mod integration_tests {
         let hash_data = PhoneDataHash::new().expect("Failed to create hash data");
         let simd_data = PhoneDataSimd::new().expect("Failed to create SIMD data");
         let bloom_data = PhoneDataBloom::new().expect("Failed to create bloom data");
+        let eytzinger_data = PhoneDataEytzinger::new().expect("Failed to create eytzinger data");
 
         // 所有实现应该有相同的记录数和版本
         assert_eq!(binary_data.total_entries(), hash_data.total_entries());
         assert_eq!(binary_data.total_entries(), simd_data.total_entries());
         assert_eq!(binary_data.total_entries(), bloom_data.total_entries());
+        assert_eq!(binary_data.total_entries(), eytzinger_data.total_entries());
 
         assert_eq!(binary_data.version(), hash_data.version());
         assert_eq!(binary_data.version(), simd_data.version());
         assert_eq!(binary_data.version(), bloom_data.version());
+        assert_eq!(binary_data.version(), eytzinger_data.version());
 
         // 内存使用量应该相同（对于相同的数据结构）
         assert_eq!(binary_data.memory_usage_bytes(), simd_data.memory_usage_bytes());
@@ -202,4 +222,166 @@ mod integration_tests {
                 "Known phone number {} should be found", phone);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_simd_matches_binary_search() {
+        // 差分测试：向量化查找与二分查找在整个号段范围内结果一致
+        let binary_data = binary_search::PhoneData::new().expect("Failed to create binary search data");
+
+        for level in [SimdLevel::Avx2, SimdLevel::Sse2, SimdLevel::Neon, SimdLevel::Scalar] {
+            if !level.is_supported() {
+                continue;
+            }
+            let simd_data = PhoneDataSimd::new().expect("Failed to create SIMD data").with_simd_level(level);
+
+            for prefix in (1300000..2000000).step_by(7) {
+                let phone = prefix.to_string();
+                let binary_result = binary_data.find(&phone);
+                let simd_result = simd_data.find(&phone);
+                let prefetch_result = simd_data.find_with_prefetch(&phone);
+
+                match binary_result {
+                    Ok(expected) => {
+                        let simd_info = simd_result.expect("SIMD lookup should succeed");
+                        let prefetch_info = prefetch_result.expect("Prefetch lookup should succeed");
+                        assert_eq!(expected.city, simd_info.city, "{:?} city mismatch for {}", level, phone);
+                        assert_eq!(expected.card_type, simd_info.card_type, "{:?} card type mismatch for {}", level, phone);
+                        assert_eq!(expected.city, prefetch_info.city, "{:?} city mismatch for {}", level, phone);
+                    }
+                    Err(_) => {
+                        assert!(simd_result.is_err(), "{:?} SIMD should fail for {}", level, phone);
+                        assert!(prefetch_result.is_err(), "{:?} prefetch should fail for {}", level, phone);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_eytzinger_matches_binary_search() {
+        // 差分测试：Eytzinger布局与二分查找在整个号段范围内结果一致
+        let binary_data = binary_search::PhoneData::new().expect("Failed to create binary search data");
+        let eytzinger_data = PhoneDataEytzinger::new().expect("Failed to create eytzinger data");
+
+        for prefix in (1300000..2000000).step_by(7) {
+            let phone = prefix.to_string();
+            match binary_data.find(&phone) {
+                Ok(expected) => {
+                    let info = eytzinger_data.find(&phone).expect("Eytzinger lookup should succeed");
+                    assert_eq!(expected.city, info.city, "city mismatch for {}", phone);
+                    assert_eq!(expected.card_type, info.card_type, "card type mismatch for {}", phone);
+                }
+                Err(_) => assert!(eytzinger_data.find(&phone).is_err(), "Eytzinger should fail for {}", phone),
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_batch_matches_single_lookup() {
+        // 大批量走有序归并路径，结果需与逐个查找一致并保持输入顺序
+        let binary_data = binary_search::PhoneData::new().expect("Failed to create binary search data");
+        let simd_data = PhoneDataSimd::new().expect("Failed to create SIMD data");
+        let bloom_data = PhoneDataBloom::new().expect("Failed to create bloom data");
+
+        let test_phones: Vec<String> = (0..5000u64)
+            .map(|i| {
+                if i % 97 == 0 {
+                    "123".to_string()
+                } else {
+                    (13000000000 + (i * 2654435761) % 7000000000).to_string()
+                }
+            })
+            .collect();
+        let test_phones: Vec<&str> = test_phones.iter().map(|phone| phone.as_str()).collect();
+
+        let batches = [
+            ("binary", binary_data.find_batch(&test_phones)),
+            ("simd", simd_data.find_batch(&test_phones)),
+            ("bloom", bloom_data.find_batch(&test_phones)),
+        ];
+
+        for (name, results) in &batches {
+            assert_eq!(results.len(), test_phones.len());
+            for (phone, result) in test_phones.iter().zip(results) {
+                match binary_data.find(phone) {
+                    Ok(expected) => {
+                        let info = result.as_ref().expect("Batch lookup should succeed");
+                        assert_eq!(expected.city, info.city, "{} city mismatch for {}", name, phone);
+                        assert_eq!(expected.card_type, info.card_type, "{} card type mismatch for {}", name, phone);
+                    }
+                    Err(_) => assert!(result.is_err(), "{} batch should fail for {}", name, phone),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parallel_batch_matches_sequential() {
+        let simd_data = PhoneDataSimd::new().expect("Failed to create SIMD data");
+        let test_phones: Vec<String> = (0..20000u64)
+            .map(|i| (13000000000 + (i * 2654435761) % 7000000000).to_string())
+            .collect();
+        let test_phones: Vec<&str> = test_phones.iter().map(|phone| phone.as_str()).collect();
+
+        let config = ParallelConfig::new().with_threads(4).with_chunk_size(1000);
+        let sequential = simd_data.find_batch(&test_phones);
+        let parallel = simd_data.find_batch_parallel(&test_phones, &config);
+        let streamed: Vec<_> = simd_data.find_iter_parallel(test_phones.iter().copied(), &config).collect();
+
+        assert_eq!(sequential.len(), parallel.len());
+        assert_eq!(sequential.len(), streamed.len());
+        for (i, expected) in sequential.iter().enumerate() {
+            let (phone, streamed_result) = &streamed[i];
+            assert_eq!(*phone, test_phones[i]);
+            match expected {
+                Ok(info) => {
+                    assert_eq!(info.city, parallel[i].as_ref().expect("Parallel lookup should succeed").city);
+                    assert_eq!(info.city, streamed_result.as_ref().expect("Streamed lookup should succeed").city);
+                }
+                Err(_) => {
+                    assert!(parallel[i].is_err());
+                    assert!(streamed_result.is_err());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_with_stats_on_every_backend() {
+        // 插桩查找在所有实现上都应与find结果一致，并报告结果分类和探测次数
+        let backends: Vec<(&str, Box<dyn PhoneLookup>)> = vec![
+            ("binary", Box::new(binary_search::PhoneData::new().expect("Failed to create binary search data"))),
+            ("hash", Box::new(PhoneDataHash::new().expect("Failed to create hash data"))),
+            ("simd", Box::new(PhoneDataSimd::new().expect("Failed to create SIMD data"))),
+            ("bloom", Box::new(PhoneDataBloom::new().expect("Failed to create bloom data"))),
+            ("eytzinger", Box::new(PhoneDataEytzinger::new().expect("Failed to create eytzinger data"))),
+        ];
+
+        for (name, lookup) in &backends {
+            let (result, stats) = lookup.find_with_stats("18086834111");
+            assert!(result.is_ok(), "{} instrumented lookup should succeed", name);
+            assert_eq!(stats.outcome, LookupOutcome::Found, "{}", name);
+            assert!(stats.probes > 0, "{} should count probes", name);
+
+            let (result, stats) = lookup.find_with_stats("123");
+            assert!(result.is_err());
+            assert_eq!(stats.outcome, LookupOutcome::InvalidLength, "{}", name);
+            assert_eq!(stats.probes, 0, "{}", name);
+
+            for prefix in (1300000..2000000).step_by(997) {
+                let phone = prefix.to_string();
+                let (result, stats) = lookup.find_with_stats(&phone);
+                match lookup.find(&phone) {
+                    Ok(expected) => {
+                        assert_eq!(expected.city, result.expect("Instrumented lookup should succeed").city);
+                        assert_eq!(stats.outcome, LookupOutcome::Found);
+                    }
+                    Err(_) => {
+                        assert!(result.is_err(), "{} should fail for {}", name, phone);
+                        assert!(matches!(stats.outcome, LookupOutcome::NotFound | LookupOutcome::FilteredOut));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,969 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use actix_web::{test, web, HttpServer};
+use serde_json::Value;
+use phone_data::PhoneStats;
+use phone_data::integrity::{self, Verification};
+use phone_data::access::Quota;
+use phone_data::access_log::{AccessLogger, LogFormat};
+use phone_data::server::{build_app, shutdown, AppState, Database, PrivacyMode, ServerConfig};
+
+/// 写入一个只有三个号段的测试数据文件
+fn write_fixture(path: &Path, version: &str) {
+    let records: [&str; 2] = ["北京|北京|100000|010", "四川|成都|610000|028"];
+    let mut body = Vec::new();
+    let mut offsets = Vec::new();
+    for record in records {
+        offsets.push(8 + body.len() as i32);
+        body.extend_from_slice(record.as_bytes());
+        body.push(0);
+    }
+
+    let mut data = Vec::new();
+    data.extend_from_slice(version.as_bytes());
+    data.extend_from_slice(&(8 + body.len() as i32).to_le_bytes());
+    data.extend_from_slice(&body);
+    for (prefix, record, card_type) in [(1300000i32, 0, 2u8), (1380013, 0, 1), (1808683, 1, 3)] {
+        data.extend_from_slice(&prefix.to_le_bytes());
+        data.extend_from_slice(&offsets[record].to_le_bytes());
+        data.push(card_type);
+    }
+    std::fs::write(path, data).unwrap();
+}
+
+fn fixture_config(name: &str) -> ServerConfig {
+    let data_path: PathBuf = std::env::temp_dir().join(format!("phone_data_{}_{}.dat", name, std::process::id()));
+    write_fixture(&data_path, "9999");
+    ServerConfig {
+        data_path,
+        admin_key: Some(ADMIN_KEY.to_string()),
+        ..ServerConfig::default()
+    }
+}
+
+const ADMIN_KEY: &str = "admin-secret";
+
+/// 携带管理Key的POST请求
+fn admin_post(uri: &str) -> test::TestRequest {
+    test::TestRequest::post().uri(uri).insert_header(("X-Admin-Key", ADMIN_KEY))
+}
+
+/// 按配置加载数据和API Key，返回共享的配置和状态
+fn fixture_state(config: ServerConfig) -> (web::Data<ServerConfig>, web::Data<AppState>) {
+    let db = Database::load(&config).expect("fixture should load");
+    let access = config.access_control().expect("fixture api keys should load");
+    let overlay = config.overlay().expect("fixture overlay should load");
+    let portability = config.portability().expect("fixture portability table should load");
+    let state = AppState::new(db)
+        .with_access_control(access)
+        .with_overlay(overlay)
+        .with_portability(portability);
+    (web::Data::new(config), web::Data::new(state))
+}
+
+#[actix_web::test]
+async fn test_query_routes() {
+    let (config, state) = fixture_state(fixture_config("query"));
+    let app = test::init_service(build_app(config, state)).await;
+
+    let req = test::TestRequest::get().uri("/query2/18086834111").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["success"], true);
+    assert_eq!(body["data"]["city"], "成都");
+    assert_eq!(body["data"]["card_type"], "中国电信");
+
+    let req = test::TestRequest::get().uri("/query?phone=13800138000").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["province"], "北京");
+    assert_eq!(body["data"]["card_type"], "中国移动");
+
+    let req = test::TestRequest::get().uri("/query?phone=15900000000").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["success"], false);
+    assert_eq!(body["code"], -1);
+    assert!(body["data"].is_null());
+}
+
+#[actix_web::test]
+async fn test_prefix_query_and_privacy_modes() {
+    let (config, state) = fixture_state(fixture_config("prefix"));
+    let app = test::init_service(build_app(config, state)).await;
+
+    let req = test::TestRequest::get().uri("/prefix/1808683").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["city"], "成都");
+    for uri in ["/prefix/18086834111", "/prefix/180868", "/prefix/18o8683"] {
+        let resp = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(resp.status(), 400, "{}", uri);
+    }
+
+    let config = ServerConfig {
+        privacy_mode: PrivacyMode::Reject,
+        ..fixture_config("privacy_reject")
+    };
+    let (config, state) = fixture_state(config);
+    let app = test::init_service(build_app(config, state)).await;
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/query2/18086834111").to_request()).await;
+    assert_eq!(resp.status(), 400);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], 400);
+    let result = body["result"].as_str().unwrap();
+    assert!(result.contains("1808683****") && !result.contains("4111"), "{}", result);
+    let req = test::TestRequest::get().uri("/query?phone=1808683").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["city"], "成都");
+
+    let config = ServerConfig {
+        privacy_mode: PrivacyMode::Truncate,
+        ..fixture_config("privacy_truncate")
+    };
+    let (config, state) = fixture_state(config);
+    let app = test::init_service(build_app(config, state)).await;
+    let req = test::TestRequest::get().uri("/query?phone=18086834111").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["city"], "成都");
+
+    assert!("reject".parse::<PrivacyMode>().is_ok());
+    assert!("mask".parse::<PrivacyMode>().is_err());
+
+    // 非ASCII号码在各隐私模式下都按格式错误处理，不能让worker panic
+    for privacy_mode in [PrivacyMode::Off, PrivacyMode::Reject, PrivacyMode::Truncate] {
+        let config = ServerConfig { privacy_mode, ..fixture_config("privacy_non_ascii") };
+        let (config, state) = fixture_state(config);
+        let app = test::init_service(build_app(config, state)).await;
+        for uri in ["/query2/123456%C3%A912", "/query?phone=123456%C3%A9", "/prefix/123456%C3%A9"] {
+            let resp = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+            assert!(resp.status() == 200 || resp.status() == 400, "{:?} {} returned {}", privacy_mode, uri, resp.status());
+            let body: Value = test::read_body_json(resp).await;
+            assert_eq!(body["success"], false, "{:?} {}", privacy_mode, uri);
+        }
+    }
+}
+
+#[actix_web::test]
+async fn test_version_pinned_queries() {
+    let config = fixture_config("versions");
+    let archive = config.data_path.with_extension("2406.dat");
+    write_fixture(&archive, "2406");
+    let config = ServerConfig {
+        archive_paths: vec![archive.clone()],
+        ..config
+    };
+    let (config, state) = fixture_state(config);
+    let app = test::init_service(build_app(config, state.clone())).await;
+
+    let req = test::TestRequest::get().uri("/versions").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"], serde_json::json!([
+        {"version": "2406", "total_entries": 3, "latest": false},
+        {"version": "9999", "total_entries": 3, "latest": true},
+    ]));
+
+    // 默认查询最新版本，指定版本时ETag随之变化
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/query2/18086834111").to_request()).await;
+    assert_eq!(header(&resp, "etag"), "\"9999-1808683\"");
+    for uri in ["/query2/18086834111?version=2406", "/query?phone=18086834111&version=2406", "/prefix/1808683?version=2406"] {
+        let resp = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(resp.status(), 200, "{}", uri);
+        assert_eq!(header(&resp, "etag"), "\"2406-1808683\"", "{}", uri);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["data"]["city"], "成都");
+    }
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/query2/18086834111?version=2101").to_request()).await;
+    assert_eq!(resp.status(), 404);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], 404);
+    assert!(state.db().versions.get("2406").is_some());
+
+    // 版本号重复时启动失败
+    let duplicate = ServerConfig {
+        archive_paths: vec![archive.clone(), archive],
+        ..fixture_config("versions_duplicate")
+    };
+    let error = Database::load(&duplicate).err().expect("duplicate versions should fail");
+    assert!(format!("{:#}", error).contains("loaded twice"), "{:#}", error);
+}
+
+#[actix_web::test]
+async fn test_probes_and_info() {
+    let (config, state) = fixture_state(fixture_config("probes"));
+    let app = test::init_service(build_app(config, state.clone())).await;
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/healthz").to_request()).await;
+    assert_eq!(resp.status(), 200);
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/readyz").to_request()).await;
+    assert_eq!(resp.status(), 200);
+
+    state.set_ready(false);
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/readyz").to_request()).await;
+    assert_eq!(resp.status(), 503);
+
+    let req = test::TestRequest::get().uri("/info").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["backend"], "PhoneDataSimd");
+    assert_eq!(body["data"]["version"], "9999");
+    assert_eq!(body["data"]["total_entries"], 3);
+    assert!(body["data"]["memory_usage_bytes"].as_u64().unwrap() > 0);
+    assert_eq!(body["data"]["verification"], "none");
+
+    for (method, uri) in [("GET", "/"), ("POST", "/echo"), ("POST", "/hey")] {
+        let req = if method == "GET" { test::TestRequest::get() } else { test::TestRequest::post() };
+        let resp = test::call_service(&app, req.uri(uri).to_request()).await;
+        assert_eq!(resp.status(), 404, "{} {} should be removed", method, uri);
+    }
+}
+
+#[actix_web::test]
+async fn test_dataset_stats() {
+    let (config, state) = fixture_state(fixture_config("stats"));
+    let app = test::init_service(build_app(config, state)).await;
+
+    let req = test::TestRequest::get().uri("/stats").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let stats = &body["data"];
+    assert_eq!((stats["version"].as_str(), stats["total_prefixes"].as_u64()), (Some("9999"), Some(3)));
+    assert_eq!(stats["distinct_cities"], 2);
+    assert_eq!(stats["by_carrier"]["中国移动"], 1);
+    assert_eq!(stats["by_province"]["北京"]["prefixes"], 2);
+    assert_eq!(stats["by_segment"].as_array().unwrap().len(), 3);
+    assert_eq!(stats["largest_gaps"][0]["length"], 9999);
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/stats?format=table").to_request()).await;
+    assert!(header(&resp, "content-type").starts_with("text/plain"));
+    let table = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(table.starts_with("version 9999: 3 prefixes, 2 distinct cities"), "{}", table);
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/stats?format=xml").to_request()).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_metrics_scrape() {
+    let (config, state) = fixture_state(fixture_config("metrics"));
+    let app = test::init_service(build_app(config, state)).await;
+
+    test::call_service(&app, test::TestRequest::get().uri("/query2/18086834111").to_request()).await;
+    test::call_service(&app, test::TestRequest::get().uri("/query2/123").to_request()).await;
+
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+    assert!(body.contains("phone_data_http_requests_total{endpoint=\"/query2/{phone}\",status=\"200\"} 2"));
+    assert!(body.contains("phone_data_lookups_total{outcome=\"found\"} 1"));
+    assert!(body.contains("phone_data_lookups_total{outcome=\"invalid_length\"} 1"));
+    assert!(body.contains("phone_data_hits_by_province_total{province=\"四川\"} 1"));
+    assert!(body.contains("phone_data_database_info{version=\"9999\"} 1"));
+    assert!(body.contains("phone_data_database_entries 3"));
+}
+
+#[actix_web::test]
+async fn test_reload_swaps_data_and_keeps_it_on_failure() {
+    let config = fixture_config("reload");
+    let data_path = config.data_path.clone();
+    let (config, state) = fixture_state(config);
+    let app = test::init_service(build_app(config, state.clone())).await;
+
+    write_fixture(&data_path, "9998");
+    let req = admin_post("/admin/reload").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["version"], "9998");
+
+    // 截断的文件无法通过加载，继续使用原数据
+    std::fs::write(&data_path, b"9997").unwrap();
+    let req = admin_post("/admin/reload").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["success"], false);
+    assert_eq!(state.db().phone_data().version(), "9998");
+
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+    assert!(body.contains("phone_data_reloads_total{result=\"success\"} 1"));
+    assert!(body.contains("phone_data_reloads_total{result=\"failure\"} 1"));
+}
+
+#[actix_web::test]
+async fn test_startup_fails_on_missing_or_untested_data() {
+    let missing = ServerConfig {
+        data_path: PathBuf::from("/nonexistent/phone.dat"),
+        ..ServerConfig::default()
+    };
+    assert!(Database::load(&missing).is_err());
+
+    let config = ServerConfig {
+        self_test_phone: "15900000000".to_string(),
+        ..fixture_config("self_test")
+    };
+    let error = Database::load(&config).err().expect("self-test should fail");
+    assert!(error.to_string().contains("self-test"));
+}
+
+#[actix_web::test]
+async fn test_strict_integrity_on_startup_and_reload() {
+    use ed25519_dalek::{Signer, SigningKey};
+
+    let signing_key = SigningKey::from_bytes(&[42; 32]);
+    let config = ServerConfig {
+        public_key: Some(signing_key.verifying_key()),
+        strict_integrity: true,
+        ..fixture_config("strict")
+    };
+    let data_path = config.data_path.clone();
+    let sign = |path: &Path| {
+        let data = std::fs::read(path).unwrap();
+        std::fs::write(integrity::checksum_path(path), integrity::sha256_hex(&data)).unwrap();
+        std::fs::write(integrity::signature_path(path), signing_key.sign(&data).to_bytes()).unwrap();
+    };
+    let _ = std::fs::remove_file(integrity::checksum_path(&data_path));
+    let _ = std::fs::remove_file(integrity::signature_path(&data_path));
+
+    let error = Database::load(&config).err().expect("unsigned data should be refused");
+    assert!(format!("{:#}", error).contains("strict mode"), "{:#}", error);
+    sign(&data_path);
+    assert_eq!(Database::load(&config).unwrap().verification, Verification::Signature);
+
+    let (config, state) = fixture_state(config);
+    let app = test::init_service(build_app(config, state.clone())).await;
+    let req = test::TestRequest::get().uri("/info").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["verification"], "signature");
+
+    // 替换数据但未重新签名，重新加载被拒绝并继续使用原数据
+    write_fixture(&data_path, "9998");
+    let req = admin_post("/admin/reload").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["success"], false);
+    assert_eq!(state.db().phone_data().version(), "9999");
+
+    sign(&data_path);
+    let req = admin_post("/admin/reload").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["version"], "9998");
+}
+
+fn header<B>(resp: &actix_web::dev::ServiceResponse<B>, name: &str) -> String {
+    resp.headers().get(name).and_then(|value| value.to_str().ok()).unwrap_or_default().to_string()
+}
+
+#[actix_web::test]
+async fn test_api_key_auth_and_rate_limit() {
+    let keys_path = std::env::temp_dir().join(format!("phone_data_keys_{}.txt", std::process::id()));
+    std::fs::write(&keys_path, "# test keys\nk1,billing\nk2,risk,1,1\n").unwrap();
+    let config = ServerConfig {
+        api_keys_path: Some(keys_path.clone()),
+        key_quota: Quota::new(1.0, 2),
+        ..fixture_config("access")
+    };
+    let (config, state) = fixture_state(config);
+    let app = test::init_service(build_app(config, state)).await;
+
+    // 未携带或携带无效Key
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/query2/18086834111").to_request()).await;
+    assert_eq!(resp.status(), 401);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], 401);
+    assert_eq!(body["success"], false);
+    let req = test::TestRequest::get().uri("/query2/18086834111").insert_header(("X-API-Key", "nope")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 401);
+
+    // 探针不受认证影响
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/healthz").to_request()).await;
+    assert_eq!(resp.status(), 200);
+
+    let req = test::TestRequest::get().uri("/query2/18086834111").insert_header(("X-API-Key", "k1")).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(header(&resp, "x-ratelimit-limit"), "2");
+    assert_eq!(header(&resp, "x-ratelimit-remaining"), "1");
+    assert!(!header(&resp, "x-ratelimit-reset").is_empty());
+
+    let req = test::TestRequest::get().uri("/query?phone=13800138000").insert_header(("Authorization", "Bearer k1")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+    let req = test::TestRequest::get().uri("/query2/18086834111").insert_header(("X-API-Key", "k1")).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 429);
+    assert_eq!(header(&resp, "x-ratelimit-remaining"), "0");
+    assert_eq!(header(&resp, "retry-after"), "1");
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], 429);
+    assert_eq!(body["success"], false);
+    assert_eq!(body["data"]["limit"], 2);
+    assert_eq!(body["data"]["retry_after"], 1);
+
+    // 单独配置配额的Key使用自己的桶
+    let req = test::TestRequest::get().uri("/query2/18086834111").insert_header(("X-API-Key", "k2")).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(header(&resp, "x-ratelimit-limit"), "1");
+
+    // 重新加载后新Key立即生效，读取失败时保留原列表
+    std::fs::write(&keys_path, "k3\n").unwrap();
+    let req = admin_post("/admin/keys/reload").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["keys"], 1);
+    let req = test::TestRequest::get().uri("/query2/18086834111").insert_header(("X-API-Key", "k3")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+    std::fs::write(&keys_path, "k4\nk4\n").unwrap();
+    let req = admin_post("/admin/keys/reload").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["success"], false);
+    let req = test::TestRequest::get().uri("/query2/18086834111").insert_header(("X-API-Key", "k1")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 401);
+}
+
+#[actix_web::test]
+async fn test_admin_routes_require_admin_key() {
+    let keys_path = std::env::temp_dir().join(format!("phone_data_admin_keys_{}.txt", std::process::id()));
+    std::fs::write(&keys_path, "k1\n").unwrap();
+    let config = ServerConfig {
+        api_keys_path: Some(keys_path),
+        ..fixture_config("admin")
+    };
+    let (config, state) = fixture_state(config);
+    let app = test::init_service(build_app(config, state)).await;
+
+    for uri in ["/admin/reload", "/admin/keys/reload", "/admin/overlay/reload"] {
+        // 查询用的API Key不能调用管理接口
+        for req in [
+            test::TestRequest::post().uri(uri),
+            test::TestRequest::post().uri(uri).insert_header(("X-API-Key", "k1")),
+            test::TestRequest::post().uri(uri).insert_header(("X-Admin-Key", "k1")),
+        ] {
+            let resp = test::call_service(&app, req.to_request()).await;
+            assert_eq!(resp.status(), 401, "{}", uri);
+            let body: Value = test::read_body_json(resp).await;
+            assert_eq!(body["code"], 401);
+        }
+        let req = test::TestRequest::post().uri(uri).insert_header(("Authorization", format!("Bearer {}", ADMIN_KEY)));
+        assert_eq!(test::call_service(&app, req.to_request()).await.status(), 200, "{}", uri);
+    }
+    // 未通过认证的重新加载不计入指标
+    let metrics = String::from_utf8(test::call_and_read_body(&app, test::TestRequest::get().uri("/metrics").to_request()).await.to_vec()).unwrap();
+    assert!(metrics.contains("phone_data_reloads_total{result=\"success\"} 1"), "{}", metrics);
+
+    // 未配置管理Key时管理接口不可用
+    let config = ServerConfig { admin_key: None, ..fixture_config("admin_disabled") };
+    let (config, state) = fixture_state(config);
+    let app = test::init_service(build_app(config, state)).await;
+    let resp = test::call_service(&app, admin_post("/admin/reload").to_request()).await;
+    assert_eq!(resp.status(), 403);
+}
+
+#[actix_web::test]
+async fn test_rate_limit_per_ip_without_keys() {
+    let config = ServerConfig {
+        ip_quota: Quota::new(0.001, 1),
+        ..fixture_config("ip_limit")
+    };
+    let (config, state) = fixture_state(config);
+    let app = test::init_service(build_app(config, state)).await;
+
+    let peer: SocketAddr = "10.0.0.1:5000".parse().unwrap();
+    let req = test::TestRequest::get().uri("/query2/18086834111").peer_addr(peer).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    let req = test::TestRequest::get().uri("/query2/18086834111").peer_addr(peer).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 429);
+
+    // 未信任代理时忽略转发头
+    let req = test::TestRequest::get()
+        .uri("/query2/18086834111")
+        .peer_addr(peer)
+        .insert_header(("X-Forwarded-For", "10.0.0.9"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 429);
+
+    let other: SocketAddr = "10.0.0.2:5000".parse().unwrap();
+    let req = test::TestRequest::get().uri("/query2/18086834111").peer_addr(other).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+}
+
+/// 收集访问日志输出的共享缓冲区
+#[derive(Clone, Default)]
+struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl LogBuffer {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap().lines().map(str::to_string).collect()
+    }
+}
+
+#[actix_web::test]
+async fn test_access_log_masks_numbers() {
+    let config = fixture_config("access_log");
+    let db = Database::load(&config).unwrap();
+    let buffer = LogBuffer::default();
+    let logger = AccessLogger::new(LogFormat::Json, 1.0).with_writer(Box::new(buffer.clone()));
+    let state = web::Data::new(AppState::new(db).with_access_log(logger));
+    let app = test::init_service(build_app(web::Data::new(config), state)).await;
+
+    test::call_service(&app, test::TestRequest::get().uri("/query2/18086834111").to_request()).await;
+    test::call_service(&app, test::TestRequest::get().uri("/query?phone=15900001234").to_request()).await;
+    test::call_service(&app, test::TestRequest::get().uri("/healthz").to_request()).await;
+
+    let lines = buffer.lines();
+    assert_eq!(lines.len(), 3);
+    assert!(lines.iter().all(|line| !line.contains("4111") && !line.contains("1234")), "{:?}", lines);
+
+    let found: Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(found["route"], "/query2/{phone}");
+    assert_eq!(found["method"], "GET");
+    assert_eq!(found["status"], 200);
+    assert_eq!(found["phone"], "1808683****");
+    assert!(found["error_kind"].is_null());
+    assert_eq!(found["backend"], "PhoneDataSimd");
+    assert_eq!(found["db_version"], "9999");
+    assert!(found["latency_ms"].as_f64().unwrap() >= 0.0);
+    assert!(found["timestamp"].as_str().unwrap().ends_with('Z'));
+
+    let missing: Value = serde_json::from_str(&lines[1]).unwrap();
+    assert_eq!(missing["error_kind"], "not_found");
+    assert_eq!(missing["phone"], "1590000****");
+
+    let probe: Value = serde_json::from_str(&lines[2]).unwrap();
+    assert_eq!(probe["route"], "/healthz");
+    assert!(probe["phone"].is_null());
+}
+
+#[actix_web::test]
+async fn test_access_log_sampling() {
+    let config = fixture_config("access_log_sample");
+    let db = Database::load(&config).unwrap();
+    let buffer = LogBuffer::default();
+    let logger = AccessLogger::new(LogFormat::Text, 0.25).with_writer(Box::new(buffer.clone()));
+    let state = web::Data::new(AppState::new(db).with_access_log(logger));
+    let app = test::init_service(build_app(web::Data::new(config), state)).await;
+
+    for _ in 0..8 {
+        test::call_service(&app, test::TestRequest::get().uri("/query2/18086834111").to_request()).await;
+    }
+    let lines = buffer.lines();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains(" GET /query2/{phone} 200 "), "{}", lines[0]);
+    assert!(lines[0].contains("phone=1808683****"), "{}", lines[0]);
+}
+
+/// 按OpenAPI Schema校验JSON值，只支持文档中用到的关键字
+fn validate(spec: &Value, schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let name = reference.rsplit('/').next().unwrap();
+        return validate(spec, &spec["components"]["schemas"][name], value, at);
+    }
+    if value.is_null() && schema["nullable"] == true {
+        return Ok(());
+    }
+    if let Some(all_of) = schema["allOf"].as_array() {
+        return all_of.iter().try_for_each(|schema| validate(spec, schema, value, at));
+    }
+    if let Some(variants) = schema["enum"].as_array() && !variants.contains(value) {
+        return Err(format!("{}: {} is not one of {:?}", at, value, variants));
+    }
+
+    let matches = match schema["type"].as_str() {
+        Some("object") => value.is_object(),
+        Some("string") => value.is_string(),
+        Some("integer") => value.is_i64() || value.is_u64(),
+        Some("number") => value.is_number(),
+        Some("boolean") => value.is_boolean(),
+        Some("array") => value.is_array(),
+        other => return Err(format!("{}: unsupported schema type {:?}", at, other)),
+    };
+    if !matches {
+        return Err(format!("{}: expected {}, got {}", at, schema["type"], value));
+    }
+
+    if let Some(items) = value.as_array() {
+        for (i, item) in items.iter().enumerate() {
+            validate(spec, &schema["items"], item, &format!("{}[{}]", at, i))?;
+        }
+    }
+    if let Some(object) = value.as_object() {
+        let properties = schema["properties"].as_object().cloned().unwrap_or_default();
+        for name in schema["required"].as_array().into_iter().flatten() {
+            let name = name.as_str().unwrap();
+            if !object.contains_key(name) {
+                return Err(format!("{}: missing field {}", at, name));
+            }
+        }
+        for (name, field) in object {
+            match properties.get(name) {
+                Some(field_schema) => validate(spec, field_schema, field, &format!("{}.{}", at, name))?,
+                None if schema["additionalProperties"] == false => {
+                    return Err(format!("{}: undocumented field {}", at, name));
+                }
+                None if schema["additionalProperties"].is_object() => {
+                    validate(spec, &schema["additionalProperties"], field, &format!("{}.{}", at, name))?
+                }
+                None => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 校验响应的状态码和响应体与文档一致
+async fn check_against_spec<B: actix_web::body::MessageBody>(
+    spec: &Value,
+    path: &str,
+    method: &str,
+    resp: actix_web::dev::ServiceResponse<B>,
+) {
+    let status = resp.status().as_u16().to_string();
+    let documented = &spec["paths"][path][method]["responses"][&status];
+    assert!(documented.is_object(), "{} {} returned undocumented status {}", method, path, status);
+
+    for name in documented["headers"].as_object().into_iter().flat_map(|headers| headers.keys()) {
+        if !name.starts_with("X-RateLimit") {
+            assert!(!header(&resp, name).is_empty(), "{} {} {} is missing header {}", method, path, status, name);
+        }
+    }
+
+    let content_type = header(&resp, "content-type");
+    let body = test::read_body(resp).await;
+    let Some(content) = documented["content"].as_object() else {
+        assert!(body.is_empty(), "{} {} {} should have no body", method, path, status);
+        return;
+    };
+    let (media_type, media) = content.iter().next().unwrap();
+    assert!(content_type.starts_with(media_type.as_str()), "{} {}: {} vs {}", method, path, content_type, media_type);
+    if media_type == "application/json" {
+        let value: Value = serde_json::from_slice(&body).unwrap();
+        if let Err(e) = validate(spec, &media["schema"], &value, &format!("{} {} {}", method, path, status)) {
+            panic!("spec drift: {}\nbody: {}", e, value);
+        }
+    }
+}
+
+/// 源码中注册的路由
+fn registered_routes() -> Vec<String> {
+    let source = include_str!("../src/server.rs");
+    let mut routes = Vec::new();
+    for marker in ["#[get(\"", "#[post(\"", "web::resource(\""] {
+        for part in source.split(marker).skip(1) {
+            routes.push(part[..part.find('"').unwrap()].to_string());
+        }
+    }
+    // scope内的路由相对于scope路径注册
+    for part in source.split("web::scope(\"").skip(1) {
+        let prefix = &part[..part.find('"').unwrap()];
+        let body = &part[..part.find("\n        )").unwrap()];
+        for route in body.split(".route(\"").skip(1) {
+            routes.push(format!("{}{}", prefix, &route[..route.find('"').unwrap()]));
+        }
+    }
+    routes
+}
+
+#[actix_web::test]
+async fn test_openapi_matches_handlers() {
+    let (config, state) = fixture_state(fixture_config("openapi"));
+    let app = test::init_service(build_app(config, state)).await;
+
+    let spec: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/openapi.json").to_request()).await;
+    assert_eq!(spec["openapi"], "3.0.3");
+    let paths = spec["paths"].as_object().unwrap();
+
+    // 每个路由都有文档，每个文档路径都有路由
+    let routes = registered_routes();
+    for route in &routes {
+        if route != "/openapi.json" && route != "/docs" {
+            assert!(paths.contains_key(route), "route {} is missing from the OpenAPI document", route);
+        }
+    }
+    for path in paths.keys() {
+        assert!(routes.contains(path), "documented path {} has no handler", path);
+    }
+
+    // 按文档中的示例参数调用每个接口，响应需与文档一致
+    for (path, methods) in paths {
+        for (method, operation) in methods.as_object().unwrap() {
+            let mut uri = path.clone();
+            let mut query = Vec::new();
+            for param in operation["parameters"].as_array().unwrap() {
+                let name = param["name"].as_str().unwrap();
+                let example = match &param["example"] {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                match param["in"].as_str().unwrap() {
+                    "path" => uri = uri.replace(&format!("{{{}}}", name), &example),
+                    _ => query.push(format!("{}={}", name, example)),
+                }
+            }
+            if !query.is_empty() {
+                uri = format!("{}?{}", uri, query.join("&"));
+            }
+            let mut req = match method.as_str() {
+                "get" => test::TestRequest::get(),
+                "post" => test::TestRequest::post(),
+                other => panic!("unexpected method {}", other),
+            };
+            if operation["security"].to_string().contains("AdminKey") {
+                req = req.insert_header(("X-Admin-Key", ADMIN_KEY));
+            }
+            let resp = test::call_service(&app, req.uri(&uri).to_request()).await;
+            assert!(resp.status().is_success(), "{} {} returned {}", method, uri, resp.status());
+            check_against_spec(&spec, path, method, resp).await;
+        }
+    }
+
+    // 错误响应
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/query2/15900000000").to_request()).await;
+    check_against_spec(&spec, "/query2/{phone}", "get", resp).await;
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/prefix/123").to_request()).await;
+    check_against_spec(&spec, "/prefix/{prefix}", "get", resp).await;
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/docs").to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert!(header(&resp, "content-type").starts_with("text/html"));
+}
+
+#[actix_web::test]
+async fn test_lookup_etag_and_conditional_requests() {
+    let config = ServerConfig {
+        cache_max_age: Duration::from_secs(3600),
+        ..fixture_config("etag")
+    };
+    let data_path = config.data_path.clone();
+    let (config, state) = fixture_state(config);
+    let app = test::init_service(build_app(config, state)).await;
+    let spec: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/openapi.json").to_request()).await;
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/query2/18086834111").to_request()).await;
+    assert_eq!(resp.status(), 200);
+    let etag = header(&resp, "etag");
+    assert_eq!(etag, "\"9999-1808683\"");
+    assert_eq!(header(&resp, "cache-control"), "public, max-age=3600");
+
+    // 同一号段的号码结果相同，共用ETag
+    for uri in ["/query2/18086830000", "/query?phone=1808683", "/prefix/1808683"] {
+        let req = test::TestRequest::get().uri(uri).insert_header(("If-None-Match", etag.as_str())).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 304, "{}", uri);
+        assert_eq!(header(&resp, "etag"), etag);
+        let path = if uri.starts_with("/query2") { "/query2/{phone}" } else if uri.starts_with("/query") { "/query" } else { "/prefix/{prefix}" };
+        check_against_spec(&spec, path, "get", resp).await;
+    }
+
+    // 未找到的结果同样可以缓存，长度不合法的请求不带缓存头
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/query2/15900000000").to_request()).await;
+    assert_eq!(header(&resp, "etag"), "\"9999-1590000\"");
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/query2/123").to_request()).await;
+    assert!(header(&resp, "etag").is_empty());
+    assert!(header(&resp, "cache-control").is_empty());
+
+    // 数据版本变化后旧ETag失效
+    write_fixture(&data_path, "9998");
+    test::call_service(&app, admin_post("/admin/reload").to_request()).await;
+    let req = test::TestRequest::get().uri("/query2/18086834111").insert_header(("If-None-Match", etag.as_str())).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(header(&resp, "etag"), "\"9998-1808683\"");
+
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+    assert!(body.contains("phone_data_http_requests_total{endpoint=\"/query2/{phone}\",status=\"304\"} 1"));
+    assert!(body.contains("phone_data_lookups_total{outcome=\"found\"} 5"));
+}
+
+#[actix_web::test]
+async fn test_overlay_overrides_and_reload() {
+    let config = fixture_config("overlay");
+    let archive = config.data_path.with_extension("2406.dat");
+    write_fixture(&archive, "2406");
+    let overlay_path = config.data_path.with_extension("overlay.csv");
+    std::fs::write(&overlay_path, "\
+key,city,zip_code,area_code,reason,source
+1808683,绵阳,621000,0816,城市错误,TICKET-1
+").unwrap();
+    let config = ServerConfig {
+        archive_paths: vec![archive],
+        overlay_path: Some(overlay_path.clone()),
+        ..config
+    };
+    let (config, state) = fixture_state(config);
+    let app = test::init_service(build_app(config, state)).await;
+    let spec: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/openapi.json").to_request()).await;
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/query2/18086834111").to_request()).await;
+    let etag = header(&resp, "etag");
+    assert!(etag.starts_with("\"9999-1808683-"), "{}", etag);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["city"], "绵阳");
+    assert_eq!(body["data"]["card_type"], "中国电信");
+    assert_eq!(body["data"]["override"], serde_json::json!({"level": "prefix", "reason": "城市错误", "source": "TICKET-1"}));
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/query2/18086834111").to_request()).await;
+    check_against_spec(&spec, "/query2/{phone}", "get", resp).await;
+
+    // 未覆盖的号段和指定版本的查询返回原始数据
+    let body: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/query?phone=13800138000").to_request()).await;
+    assert!(body["data"]["override"].is_null());
+    let body: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/prefix/1808683?version=2406").to_request()).await;
+    assert_eq!(body["data"]["city"], "成都");
+    assert!(body["data"]["override"].is_null());
+
+    // 完整号码级覆盖优先，覆盖全部字段时基础数据中没有的号段也能查到
+    std::fs::write(&overlay_path, "\
+key,province,city,zip_code,area_code,card_type,reason,source
+1808683,,绵阳,621000,0816,,城市错误,TICKET-1
+18086830000,,,,,中国移动,携号转网,TICKET-2
+1990000,广东,广州,510000,020,3,新放号,运营商公告
+").unwrap();
+    let body: Value = test::call_and_read_body_json(&app, admin_post("/admin/overlay/reload").to_request()).await;
+    assert_eq!(body["data"]["entries"], 3);
+
+    let req = test::TestRequest::get().uri("/query2/18086834111").insert_header(("If-None-Match", etag.as_str())).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200, "overlay changes invalidate the etag");
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/query2/18086830000").to_request()).await;
+    assert_ne!(header(&resp, "etag"), etag);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["card_type"], "中国移动");
+    assert_eq!(body["data"]["city"], "成都");
+    assert_eq!(body["data"]["override"]["level"], "number");
+    let body: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/query2/19900001234").to_request()).await;
+    assert_eq!(body["data"]["city"], "广州");
+    assert_eq!(body["data"]["override"]["source"], "运营商公告");
+
+    // 文件格式错误时继续使用原覆盖
+    std::fs::write(&overlay_path, "key,city\n1808683,德阳\n").unwrap();
+    let body: Value = test::call_and_read_body_json(&app, admin_post("/admin/overlay/reload").to_request()).await;
+    assert_eq!(body["success"], false);
+    let body: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/query2/19900001234").to_request()).await;
+    assert_eq!(body["data"]["city"], "广州");
+
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+    // 覆盖补上的号段计为命中
+    assert!(body.contains("phone_data_lookups_total{outcome=\"found\"} 8"), "{}", body);
+}
+
+#[actix_web::test]
+async fn test_ported_numbers_report_current_carrier() {
+    let config = fixture_config("mnp");
+    let mnp_path = config.data_path.with_extension("mnp.csv");
+    std::fs::write(&mnp_path, "number,carrier\n18086834111,中国移动\n").unwrap();
+    let config = ServerConfig {
+        portability_path: Some(mnp_path),
+        ..config
+    };
+    let (config, state) = fixture_state(config);
+    let app = test::init_service(build_app(config, state)).await;
+    let spec: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/openapi.json").to_request()).await;
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/query2/18086834111").to_request()).await;
+    let ported_etag = header(&resp, "etag");
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["card_type"], "中国电信");
+    assert_eq!(body["data"]["original_carrier"], "中国电信");
+    assert_eq!(body["data"]["current_carrier"], "中国移动");
+    assert_eq!(body["data"]["ported"], true);
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/query2/18086834111").to_request()).await;
+    check_against_spec(&spec, "/query2/{phone}", "get", resp).await;
+
+    // 同号段未转网的号码不能命中转网号码的缓存
+    let req = test::TestRequest::get().uri("/query?phone=18086834112").insert_header(("If-None-Match", ported_etag.as_str())).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["current_carrier"], "中国电信");
+    assert_eq!(body["data"]["ported"], false);
+
+    // 号段查询和指定版本的查询不涉及完整号码
+    for uri in ["/prefix/1808683", "/query2/18086834111?version=9999"] {
+        let body: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(body["data"]["ported"], false, "{}", uri);
+    }
+
+    let bad = ServerConfig {
+        portability_path: Some(PathBuf::from("/nonexistent/mnp.csv")),
+        ..fixture_config("mnp_missing")
+    };
+    assert!(bad.portability().is_err());
+}
+
+#[actix_web::test]
+async fn test_openapi_documents_auth_and_rate_limit_errors() {
+    let keys_path = std::env::temp_dir().join(format!("phone_data_openapi_keys_{}.txt", std::process::id()));
+    std::fs::write(&keys_path, "k1\n").unwrap();
+    let config = ServerConfig {
+        api_keys_path: Some(keys_path),
+        key_quota: Quota::new(0.001, 1),
+        ..fixture_config("openapi_errors")
+    };
+    let (config, state) = fixture_state(config);
+    let app = test::init_service(build_app(config, state)).await;
+    let spec: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/openapi.json").to_request()).await;
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/query2/18086834111").to_request()).await;
+    assert_eq!(resp.status(), 401);
+    check_against_spec(&spec, "/query2/{phone}", "get", resp).await;
+
+    for expected in [200, 429] {
+        let req = test::TestRequest::get().uri("/query2/18086834111").insert_header(("X-API-Key", "k1")).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), expected);
+        if expected == 200 {
+            assert_eq!(header(&resp, "cache-control"), "private, no-cache");
+        }
+        check_against_spec(&spec, "/query2/{phone}", "get", resp).await;
+    }
+}
+
+/// 用阻塞连接发送一个GET请求，返回状态行
+fn blocking_get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response.lines().next().unwrap_or_default().to_string()
+}
+
+#[actix_web::test]
+async fn test_graceful_shutdown_flips_readiness_before_closing() {
+    let (config, state) = fixture_state(fixture_config("shutdown"));
+    let app_state = state.clone();
+    let server = HttpServer::new(move || build_app(config.clone(), app_state.clone()))
+        .workers(1)
+        .disable_signals()
+        .bind("127.0.0.1:0")
+        .unwrap();
+    let addr = server.addrs()[0];
+    let server = server.run();
+    let handle = server.handle();
+    let running = actix_web::rt::spawn(server);
+
+    let status = web::block(move || blocking_get(addr, "/readyz")).await.unwrap();
+    assert!(status.contains("200"), "{}", status);
+
+    // 停机开始后监听仍保持一段时间，此时就绪探针已返回503，请求仍能完成
+    let shutdown_state = state.clone();
+    let stopping = actix_web::rt::spawn(async move {
+        shutdown(&handle, &shutdown_state, Duration::from_millis(300)).await;
+    });
+    actix_web::rt::time::sleep(Duration::from_millis(50)).await;
+    assert!(!state.is_ready());
+
+    let status = web::block(move || blocking_get(addr, "/readyz")).await.unwrap();
+    assert!(status.contains("503"), "{}", status);
+
+    stopping.await.unwrap();
+    running.await.unwrap().unwrap();
+
+    let report = state.drain_report();
+    assert_eq!(report.drained, 1);
+    assert_eq!(report.aborted, 0);
+    assert!(TcpStream::connect(addr).is_err(), "listener should be closed");
+}
@@ -0,0 +1,72 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use phone_data::stats::{dataset_stats, ProvinceStats};
+
+/// 写入测试数据文件，`entries`为(号段, 记录, 卡类型)
+fn write_database(path: &Path, version: &str, entries: &[(i32, &str, u8)]) {
+    let mut body = Vec::new();
+    let mut offsets = Vec::new();
+    for (_, record, _) in entries {
+        offsets.push(8 + body.len() as i32);
+        body.extend_from_slice(record.as_bytes());
+        body.push(0);
+    }
+
+    let mut data = Vec::new();
+    data.extend_from_slice(version.as_bytes());
+    data.extend_from_slice(&(8 + body.len() as i32).to_le_bytes());
+    data.extend_from_slice(&body);
+    for ((prefix, _, card_type), offset) in entries.iter().zip(offsets) {
+        data.extend_from_slice(&prefix.to_le_bytes());
+        data.extend_from_slice(&offset.to_le_bytes());
+        data.push(*card_type);
+    }
+    std::fs::write(path, data).unwrap();
+}
+
+fn fixture(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("phone_data_stats_{}_{}.dat", name, std::process::id()));
+    write_database(&path, "2410", &[
+        (1300000, "北京|北京|100000|010", 2),
+        (1300001, "北京|北京|100000|010", 2),
+        (1390000, "广东|广州|510000|020", 1),
+        (1390001, "广东|深圳|518000|0755", 1),
+    ]);
+    path
+}
+
+#[test]
+fn test_dataset_stats() {
+    let stats = dataset_stats(fixture("lib")).unwrap();
+    assert_eq!((stats.version.as_str(), stats.total_prefixes, stats.distinct_cities), ("2410", 4, 3));
+    assert_eq!(stats.by_carrier["中国联通"], 2);
+    assert_eq!(stats.by_province["广东"], ProvinceStats { prefixes: 2, cities: 2 });
+    assert_eq!(stats.by_segment.iter().map(|s| s.segment).collect::<Vec<_>>(), vec![130, 139]);
+    assert_eq!((stats.largest_gaps[0].start, stats.largest_gaps[0].length), (1300002, 9998));
+
+    assert!(dataset_stats("/nonexistent/phone.dat").is_err());
+}
+
+#[test]
+fn test_stats_cli() {
+    let path = fixture("cli");
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_phone_data")).arg("stats").args(args).output().unwrap();
+        (output.status.success(), String::from_utf8(output.stdout).unwrap())
+    };
+    let path = path.to_str().unwrap();
+
+    let (ok, json) = run(&[path]);
+    assert!(ok);
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["by_province"]["北京"]["cities"], 1);
+    assert_eq!(json["by_segment"][1]["coverage"], 0.0002);
+
+    let (ok, table) = run(&[path, "--format=table"]);
+    assert!(ok);
+    assert!(table.starts_with("version 2410: 4 prefixes, 3 distinct cities\n"), "{}", table);
+    assert!(table.contains("1300002-1309999"));
+
+    assert!(!run(&[path, "--format", "csv"]).0);
+    assert!(!run(&[path, path]).0);
+}